@@ -0,0 +1,3 @@
+ALTER TABLE sessions
+    ADD COLUMN user_agent_family TEXT,
+    ADD COLUMN ip_subnet TEXT;
@@ -0,0 +1,2 @@
+ALTER TABLE sessions ADD COLUMN user_agent_family TEXT;
+ALTER TABLE sessions ADD COLUMN ip_subnet TEXT;
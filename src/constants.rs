@@ -2,7 +2,8 @@ use std::{env, str::FromStr, fmt::Debug, net::{SocketAddr, Ipv4Addr, IpAddr}};
 
 use http::HeaderName;
 use lazy_static::lazy_static;
-use regex::Regex;
+
+use crate::{service::sessions::{SessionBindingMode, BindingEnforcement}, cookies::{CookieConfig, CookieProtection, CookieSeal, SameSitePolicy}, extract::TokenSources, passwords::PasswordPolicy, repository::{backend::RepositoryBackend, client::ServiceAuthMode, revocation::RevocationBackend}};
 
 fn load_env_or_default<T>(var: &str, default: T) -> T
 where
@@ -21,22 +22,127 @@ where
 // - PGDATABASE
 // - PGUSER
 // - PGPASSWORD
-pub const PASSWORD_SPECIAL_CHARS: &str = "!@#$%^&*";
+// bcrypt ignores everything past it
+pub const PASSWORD_MAX_BYTES: usize = 72;
 pub const SESSION_ID_LENGTH: usize = 64;
+pub const REFRESH_TOKEN_LENGTH: usize = 64;
+pub const CSRF_TOKEN_LENGTH: usize = 32;
+pub const PERSONAL_TOKEN_PREFIX: &str = "agp_";
+pub const PERSONAL_TOKEN_LENGTH: usize = 48;
+pub const PERSONAL_TOKEN_SCOPES: [&str; 3] = ["documents:read", "documents:write", "documents:compile"];
 
 lazy_static! {
     pub static ref HASH_COST: u32 = load_env_or_default("BCRYPT_HASH_COST", 12);
     
     pub static ref SERVER_URL: SocketAddr = load_env_or_default("SERVER_URL", SocketAddr::new(IpAddr::V4(Ipv4Addr::new(0, 0, 0, 0)), 3100));
+    // comma separated origins allowed to call the service from a browser, e.g. https://*.agartex.com, empty disables CORS
+    pub static ref CORS_ALLOWED_ORIGINS: String = load_env_or_default("CORS_ALLOWED_ORIGINS", String::new());
+    pub static ref CORS_MAX_AGE_SECONDS: u64 = load_env_or_default("CORS_MAX_AGE_SECONDS", 60 * 10); // 10 minutes
     pub static ref RESOURCE_MANAGEMENT_URL: String = load_env_or_default("RESOURCE_MANAGEMENT_URL", String::from("http://localhost:3200"));
+
+    pub static ref HTTP_CONNECT_TIMEOUT_MS: u64 = load_env_or_default("HTTP_CONNECT_TIMEOUT_MS", 1000);
+    pub static ref HTTP_REQUEST_TIMEOUT_MS: u64 = load_env_or_default("HTTP_REQUEST_TIMEOUT_MS", 5000);
+    pub static ref HTTP_MAX_RETRIES: u32 = load_env_or_default("HTTP_MAX_RETRIES", 2);
+    pub static ref HTTP_RETRY_BASE_DELAY_MS: u64 = load_env_or_default("HTTP_RETRY_BASE_DELAY_MS", 50);
+    pub static ref HTTP_BREAKER_THRESHOLD: u32 = load_env_or_default("HTTP_BREAKER_THRESHOLD", 5);
+    pub static ref HTTP_BREAKER_COOLDOWN_MS: u64 = load_env_or_default("HTTP_BREAKER_COOLDOWN_MS", 30 * 1000);
+
+    // credentials sent to resource management, mutual TLS can be combined with either mode
+    pub static ref SERVICE_AUTH_MODE: ServiceAuthMode = load_env_or_default("SERVICE_AUTH_MODE", ServiceAuthMode::None);
+    pub static ref SERVICE_AUTH_HEADER: String = load_env_or_default("SERVICE_AUTH_HEADER", String::from("X-Service-Token")).to_lowercase();
+    pub static ref SERVICE_AUTH_SECRET: String = load_env_or_default("SERVICE_AUTH_SECRET", String::new());
+    pub static ref SERVICE_TLS_CERT: String = load_env_or_default("SERVICE_TLS_CERT", String::new());
+    pub static ref SERVICE_TLS_KEY: String = load_env_or_default("SERVICE_TLS_KEY", String::new());
+    pub static ref SERVICE_TLS_CA: String = load_env_or_default("SERVICE_TLS_CA", String::new());
+
+    // http proxies to resource management, postgres connects using the PG* variables, sqlite uses a local file, memory is meant for local development
+    pub static ref REPOSITORY_BACKEND: RepositoryBackend = load_env_or_default("REPOSITORY_BACKEND", RepositoryBackend::Http);
+    pub static ref PG_MAX_CONNECTIONS: u32 = load_env_or_default("PG_MAX_CONNECTIONS", 10);
+    pub static ref SQLITE_PATH: String = load_env_or_default("SQLITE_PATH", String::from("agartex-authentication.db"));
+    pub static ref SQLITE_MAX_CONNECTIONS: u32 = load_env_or_default("SQLITE_MAX_CONNECTIONS", 4);
+    // empty keeps the memory backend purely in memory
+    pub static ref MEMORY_SNAPSHOT_PATH: String = load_env_or_default("MEMORY_SNAPSHOT_PATH", String::new());
+    pub static ref MEMORY_SNAPSHOT_DEBOUNCE_MILLIS: u64 = load_env_or_default("MEMORY_SNAPSHOT_DEBOUNCE_MILLIS", 1000);
     
     pub static ref SESSION_COOKIE_NAME: String = load_env_or_default("SESSION_COOKIE_NAME", String::from("RSESSID"));
     pub static ref SESSION_LENGTH_SECONDS: i64 = load_env_or_default("SESSION_LENGTH_SECONDS", 60 * 60 * 24 * 30); // 30 days
+    // how long the previous id of a rotated session stays valid for requests already in flight
+    pub static ref SESSION_ROTATION_GRACE_SECONDS: i64 = load_env_or_default("SESSION_ROTATION_GRACE_SECONDS", 30);
+    // how long after logging in or re-authenticating sensitive actions are allowed
+    pub static ref REAUTH_MAX_AGE_SECONDS: i64 = load_env_or_default("REAUTH_MAX_AGE_SECONDS", 60 * 10); // 10 minutes
     pub static ref SESSION_ID_GEN_RETRIES: u32 = load_env_or_default("SESSION_ID_GEN_RETRIES", 5);
     pub static ref SESSION_EXPIRE_BUFFER_DAYS: i64 = load_env_or_default("EXPIRED_BUFFER_DAYS", 1);
+    // 0 disables caching of sessions fetched from the repository
+    pub static ref SESSION_CACHE_CAPACITY: usize = load_env_or_default("SESSION_CACHE_CAPACITY", 10000);
+    pub static ref SESSION_CACHE_TTL_SECONDS: u64 = load_env_or_default("SESSION_CACHE_TTL_SECONDS", 30);
+    pub static ref SESSION_CACHE_NEGATIVE_TTL_SECONDS: u64 = load_env_or_default("SESSION_CACHE_NEGATIVE_TTL_SECONDS", 5);
+    pub static ref SESSION_PURGE_INTERVAL_SECONDS: u64 = load_env_or_default("SESSION_PURGE_INTERVAL_SECONDS", 60 * 60); // 1 hour
+    pub static ref SESSION_PURGE_JITTER_SECONDS: u64 = load_env_or_default("SESSION_PURGE_JITTER_SECONDS", 60 * 5); // 5 minutes
+    pub static ref METRICS_REPORT_INTERVAL_SECONDS: u64 = load_env_or_default("METRICS_REPORT_INTERVAL_SECONDS", 60); // 0 disables the report
+    // how logouts reach the session caches of other instances, postgres uses LISTEN/NOTIFY on the PG* database
+    pub static ref REVOCATION_BACKEND: RevocationBackend = load_env_or_default("REVOCATION_BACKEND", RevocationBackend::None);
+    pub static ref REVOCATION_CHANNEL_CAPACITY: usize = load_env_or_default("REVOCATION_CHANNEL_CAPACITY", 1024);
+    // none, user-agent, subnet or both
+    pub static ref SESSION_BINDING: SessionBindingMode = load_env_or_default("SESSION_BINDING", SessionBindingMode::None);
+    // reject or flag
+    pub static ref SESSION_BINDING_ENFORCEMENT: BindingEnforcement = load_env_or_default("SESSION_BINDING_ENFORCEMENT", BindingEnforcement::Reject);
+    pub static ref SESSION_BINDING_IPV4_PREFIX: u8 = load_env_or_default("SESSION_BINDING_IPV4_PREFIX", 24);
+    pub static ref SESSION_BINDING_IPV6_PREFIX: u8 = load_env_or_default("SESSION_BINDING_IPV6_PREFIX", 64);
+    // header set by the reverse proxy with the client address, e.g. X-Forwarded-For, empty uses the peer address
+    pub static ref CLIENT_IP_HEADER: String = load_env_or_default("CLIENT_IP_HEADER", String::new()).to_lowercase();
     pub static ref IS_COOKIE_SECURE: bool = load_env_or_default("IS_COOKIE_SECURE", false);
+    pub static ref SESSION_COOKIE_SAME_SITE: SameSitePolicy = load_env_or_default("SESSION_COOKIE_SAME_SITE", SameSitePolicy::Lax);
+    // empty keeps the cookie on the exact host
+    pub static ref SESSION_COOKIE_DOMAIN: String = load_env_or_default("SESSION_COOKIE_DOMAIN", String::new());
+    pub static ref SESSION_COOKIE_PATH: String = load_env_or_default("SESSION_COOKIE_PATH", String::from("/"));
+    // false issues a cookie that is dropped when the browser closes
+    pub static ref SESSION_COOKIE_PERSISTENT: bool = load_env_or_default("SESSION_COOKIE_PERSISTENT", true);
+    pub static ref SESSION_COOKIE: CookieConfig = CookieConfig {
+        name: SESSION_COOKIE_NAME.clone(),
+        same_site: *SESSION_COOKIE_SAME_SITE,
+        domain: SESSION_COOKIE_DOMAIN.clone(),
+        path: SESSION_COOKIE_PATH.clone(),
+        secure: *IS_COOKIE_SECURE,
+        http_only: true,
+        persistent: *SESSION_COOKIE_PERSISTENT
+    };
+    pub static ref SESSION_COOKIE_PROTECTION: CookieProtection = load_env_or_default("SESSION_COOKIE_PROTECTION", CookieProtection::None);
+    // comma separated base64 keys of at least 64 bytes, the first one seals new cookies
+    pub static ref SESSION_COOKIE_KEYS: String = load_env_or_default("SESSION_COOKIE_KEYS", String::new());
+    pub static ref SESSION_COOKIE_SEAL: CookieSeal = CookieSeal::parse(*SESSION_COOKIE_PROTECTION, SESSION_COOKIE_KEYS.as_str()).unwrap();
+    pub static ref CSRF_PROTECTION: bool = load_env_or_default("CSRF_PROTECTION", true);
+    pub static ref CSRF_COOKIE_NAME: String = load_env_or_default("CSRF_COOKIE_NAME", String::from("CSRF-TOKEN"));
+    pub static ref CSRF_HEADER: String = load_env_or_default("CSRF_HEADER", String::from("X-CSRF-Token")).to_lowercase();
+    // comma separated origins, e.g. https://agartex.com, empty disables the Origin/Referer check
+    pub static ref CSRF_TRUSTED_ORIGINS: String = load_env_or_default("CSRF_TRUSTED_ORIGINS", String::new());
+    pub static ref CSRF_EXEMPT_BEARER: bool = load_env_or_default("CSRF_EXEMPT_BEARER", true);
+    pub static ref CSRF_COOKIE: CookieConfig = CookieConfig {
+        name: CSRF_COOKIE_NAME.clone(),
+        http_only: false,
+        ..SESSION_COOKIE.clone()
+    };
+    pub static ref SESSION_TOKEN_SOURCES: TokenSources = load_env_or_default("SESSION_TOKEN_SOURCES", TokenSources::from_str("cookie,bearer").unwrap());
     pub static ref USER_ID_HEADER: String = load_env_or_default("USER_ID_HEADER", String::from("X-User-Id")).to_lowercase();
     pub static ref USER_HEADER_NAME: HeaderName = HeaderName::from_static(USER_ID_HEADER.as_str());
+    pub static ref TOKEN_SCOPES_HEADER: String = load_env_or_default("TOKEN_SCOPES_HEADER", String::from("X-Token-Scopes")).to_lowercase();
+    pub static ref TOKEN_SCOPES_HEADER_NAME: HeaderName = HeaderName::from_static(TOKEN_SCOPES_HEADER.as_str());
+}
+
+lazy_static! {
+    // comma separated kid=path list of Ed25519 PKCS#8 PEM files, the first one signs new tokens
+    pub static ref JWT_SIGNING_KEYS: String = load_env_or_default("JWT_SIGNING_KEYS", String::new());
+    pub static ref ACCESS_TOKEN_LENGTH_SECONDS: i64 = load_env_or_default("ACCESS_TOKEN_LENGTH_SECONDS", 60 * 5); // 5 minutes
+    pub static ref REFRESH_TOKEN_LENGTH_SECONDS: i64 = load_env_or_default("REFRESH_TOKEN_LENGTH_SECONDS", 60 * 60 * 24 * 30); // 30 days
 
-    pub static ref PASSWORD_REGEX: Regex = Regex::new(format!("^[A-Za-z0-9{}]*$", PASSWORD_SPECIAL_CHARS).as_str()).unwrap();
+    // HIBP style list of SHA-1 hashes sorted by hash, one per line optionally followed by :count, empty disables breach screening
+    pub static ref BREACHED_PASSWORDS_PATH: String = load_env_or_default("BREACHED_PASSWORDS_PATH", String::new());
+    pub static ref PASSWORD_MIN_LENGTH: usize = load_env_or_default("PASSWORD_MIN_LENGTH", 8);
+    pub static ref PASSWORD_MAX_LENGTH: usize = load_env_or_default("PASSWORD_MAX_LENGTH", PASSWORD_MAX_BYTES);
+    // comma separated classes out of lowercase, uppercase, digit and special, none required by default
+    pub static ref PASSWORD_REQUIRED_CLASSES: String = load_env_or_default("PASSWORD_REQUIRED_CLASSES", String::new());
+    // symbols accepted besides letters and digits, empty accepts any
+    pub static ref PASSWORD_SPECIAL_CHARS: String = load_env_or_default("PASSWORD_SPECIAL_CHARS", String::new());
+    // 0 to 4, passwords estimated below it are rejected as weak
+    pub static ref PASSWORD_MIN_SCORE: u8 = load_env_or_default("PASSWORD_MIN_SCORE", 3);
+    pub static ref PASSWORD_POLICY: PasswordPolicy = PasswordPolicy::from_env().unwrap();
 }
@@ -0,0 +1,73 @@
+use std::fmt::Debug;
+
+use axum::{Extension, Json, http::StatusCode, extract::Path};
+use tracing::{error, info, warn};
+
+use crate::{domain::{personal_tokens::{PersonalToken, PersonalTokenRequest, CreatedPersonalToken}, sessions::ClientInfo}, service::{sessions::SessionService, personal_tokens::{PersonalTokenService, PersonalTokenCreationError, PersonalTokenListError, PersonalTokenRevokeError}}, validation::ValidatedJson, control::sessions::authenticate, extract::{SessionToken, RecentAuth}};
+
+/// Personal tokens outlive the session, so minting one requires a recent login or re-authentication.
+#[tracing::instrument(skip_all, fields(name = request.name))]
+pub async fn post_personal_tokens<S: SessionService + Clone + Debug + Send + Sync + 'static, P: PersonalTokenService + Debug>(
+    Extension(token_service): Extension<P>,
+    RecentAuth(user, _): RecentAuth<S>,
+    ValidatedJson(request): ValidatedJson<PersonalTokenRequest>
+) -> Result<(StatusCode, Json<CreatedPersonalToken>), StatusCode> {
+    info!("Received personal token creation attempt");
+
+    match token_service.create(user.id, request).await {
+        Ok(created) => Ok((StatusCode::CREATED, Json(created))),
+        Err(PersonalTokenCreationError::Unknown) => {
+            error!("Unable to create personal token");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn get_personal_tokens<S: SessionService + Debug, P: PersonalTokenService + Debug>(
+    Extension(session_service): Extension<S>,
+    Extension(token_service): Extension<P>,
+    SessionToken(session_id): SessionToken,
+    client: ClientInfo
+) -> Result<Json<Vec<PersonalToken>>, StatusCode> {
+    info!("Received personal token listing attempt");
+    let user = authenticate(&session_service, &session_id, &client).await?;
+
+    match token_service.list(user.id).await {
+        Ok(tokens) => Ok(Json(tokens)),
+        Err(PersonalTokenListError::Unknown) => {
+            error!("Unable to list personal tokens");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[tracing::instrument(skip_all, fields(id = id))]
+pub async fn delete_personal_tokens<S: SessionService + Debug, P: PersonalTokenService + Debug>(
+    Extension(session_service): Extension<S>,
+    Extension(token_service): Extension<P>,
+    SessionToken(session_id): SessionToken,
+    client: ClientInfo,
+    Path(id): Path<i32>
+) -> StatusCode {
+    info!("Received personal token revocation attempt");
+    let user = match authenticate(&session_service, &session_id, &client).await {
+        Ok(user) => user,
+        Err(code) => return code
+    };
+
+    match token_service.revoke(user.id, id).await {
+        Ok(()) => StatusCode::NO_CONTENT,
+        Err(PersonalTokenRevokeError::Missing) => {
+            warn!("No such personal token");
+            StatusCode::NOT_FOUND
+        },
+        Err(PersonalTokenRevokeError::Unknown) => {
+            error!("Unable to revoke personal token");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
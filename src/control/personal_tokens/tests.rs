@@ -0,0 +1,117 @@
+use std::{marker::PhantomData, sync::Arc};
+
+use mockall::predicate;
+
+use crate::{service::{sessions::MockSessionService, personal_tokens::MockPersonalTokenService}, domain::{users::User, sessions::ClientInfo}, constants::SESSION_ID_LENGTH};
+
+use super::*;
+
+fn mock_session_id() -> String {
+    "1".repeat(SESSION_ID_LENGTH)
+}
+
+fn mock_user() -> User {
+    User {
+        id: 1,
+        email: String::from("email"),
+        password_hash: String::from("password")
+    }
+}
+
+fn mock_request() -> PersonalTokenRequest {
+    PersonalTokenRequest {
+        name: String::from("ci"),
+        scopes: vec![String::from("documents:compile")],
+        expires_in_days: None
+    }
+}
+
+fn mock_details() -> PersonalToken {
+    PersonalToken {
+        id: 2,
+        user_id: 1,
+        name: String::from("ci"),
+        scopes: vec![String::from("documents:compile")],
+        expires: None
+    }
+}
+
+fn mock_session_service() -> MockSessionService {
+    let mut session_service = MockSessionService::new();
+
+    session_service
+        .expect_verify()
+        .with(predicate::eq(mock_session_id()), predicate::always())
+        .times(1)
+        .returning(|_, _| Ok(mock_user()));
+
+    session_service
+}
+
+#[tokio::test]
+async fn post_personal_tokens_normal() {
+    let mut token_service = MockPersonalTokenService::new();
+
+    token_service
+        .expect_create()
+        .with(predicate::eq(1), predicate::eq(mock_request()))
+        .times(1)
+        .returning(|_, _| Ok(CreatedPersonalToken { token: String::from("agp_token"), details: mock_details() }));
+
+    let (status, Json(created)) = post_personal_tokens(Extension(token_service), RecentAuth::<Arc<MockSessionService>>(mock_user(), PhantomData), ValidatedJson(mock_request())).await.unwrap();
+    assert_eq!(StatusCode::CREATED, status);
+    assert_eq!("agp_token", created.token);
+}
+
+#[tokio::test]
+async fn post_personal_tokens_unknown_error() {
+    let mut token_service = MockPersonalTokenService::new();
+
+    token_service
+        .expect_create()
+        .times(1)
+        .returning(|_, _| Err(PersonalTokenCreationError::Unknown));
+
+    let err = post_personal_tokens(Extension(token_service), RecentAuth::<Arc<MockSessionService>>(mock_user(), PhantomData), ValidatedJson(mock_request())).await.unwrap_err();
+    assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, err);
+}
+
+#[tokio::test]
+async fn get_personal_tokens_normal() {
+    let mut token_service = MockPersonalTokenService::new();
+
+    token_service
+        .expect_list()
+        .with(predicate::eq(1))
+        .times(1)
+        .returning(|_| Ok(vec![mock_details()]));
+
+    let Json(tokens) = get_personal_tokens(Extension(mock_session_service()), Extension(token_service), SessionToken(mock_session_id()), ClientInfo::default()).await.unwrap();
+    assert_eq!(vec![mock_details()], tokens);
+}
+
+#[tokio::test]
+async fn delete_personal_tokens_normal() {
+    let mut token_service = MockPersonalTokenService::new();
+
+    token_service
+        .expect_revoke()
+        .with(predicate::eq(1), predicate::eq(2))
+        .times(1)
+        .returning(|_, _| Ok(()));
+
+    assert_eq!(StatusCode::NO_CONTENT, delete_personal_tokens(Extension(mock_session_service()), Extension(token_service), SessionToken(mock_session_id()), ClientInfo::default(), Path(2)).await);
+}
+
+#[tokio::test]
+async fn delete_personal_tokens_missing() {
+    let mut token_service = MockPersonalTokenService::new();
+
+    token_service
+        .expect_revoke()
+        .with(predicate::eq(1), predicate::eq(2))
+        .times(1)
+        .returning(|_, _| Err(PersonalTokenRevokeError::Missing));
+
+    assert_eq!(StatusCode::NOT_FOUND, delete_personal_tokens(Extension(mock_session_service()), Extension(token_service), SessionToken(mock_session_id()), ClientInfo::default(), Path(2)).await);
+}
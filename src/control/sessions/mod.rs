@@ -1,21 +1,23 @@
 use std::fmt::Debug;
 
-use axum::{Extension, Json, http::StatusCode, TypedHeader};
-use axum_extra::extract::{CookieJar, cookie::Cookie};
+use axum::{Extension, Json, http::StatusCode, TypedHeader, extract::Query};
+use axum_extra::extract::CookieJar;
+use chrono::Utc;
 use cookie::time::{OffsetDateTime, Duration};
 use tracing::{error, info, warn};
-use validator::Validate;
 
-use crate::{domain::{users::{Credentials, PubUserData}, sessions::SessionId}, service::sessions::{SessionService, LoginError, SessionVerifyError, LogoutError}, constants::{SESSION_COOKIE_NAME, SESSION_EXPIRE_BUFFER_DAYS, IS_COOKIE_SECURE}, extract::XUserId};
+use crate::{domain::{users::{Credentials, User}, sessions::{LoginOptions, PubSessionData, SessionTransport, SessionData, ClientInfo, Reauthentication}}, service::{sessions::{SessionService, LoginError, SessionVerifyError, LogoutError, ReauthenticateError, RotateError}, personal_tokens::{PersonalTokenService, PersonalTokenVerifyError}}, constants::{SESSION_COOKIE, SESSION_COOKIE_NAME, SESSION_COOKIE_SEAL, CSRF_COOKIE, SESSION_EXPIRE_BUFFER_DAYS, SESSION_LENGTH_SECONDS}, extract::{XUserId, XTokenScopes, Credential, SessionToken}, csrf::{generate_csrf_token, csrf_cookie}};
 
 #[tracing::instrument(skip_all, fields(email = credentials.email))]
 pub async fn post_sessions<T: SessionService + Debug>(
     Extension(service): Extension<T>,
+    Query(options): Query<LoginOptions>,
     jar: CookieJar,
+    client: ClientInfo,
     Json(credentials): Json<Credentials>
-) -> Result<(StatusCode, CookieJar, Json<PubUserData>), StatusCode> {
+) -> Result<(StatusCode, CookieJar, Json<PubSessionData>), StatusCode> {
     info!("Received login attempt");
-    let session = match service.login(credentials).await {
+    let session = match service.login(credentials, &client).await {
         Err(LoginError::NoUser) =>  {
             warn!("Bad credentials provided");
             return Err(StatusCode::UNAUTHORIZED);
@@ -29,81 +31,182 @@ pub async fn post_sessions<T: SessionService + Debug>(
 
     info!("Extracted session {:?}", session);
 
-    let cookie = Cookie::build(SESSION_COOKIE_NAME.as_str(), session.id)
-        .expires(OffsetDateTime::from_unix_timestamp(session.expires).unwrap())
-        .http_only(true)
-        .secure(*IS_COOKIE_SECURE)
-        // .same_site(cookie::SameSite::Strict)
-        .finish();
+    let mut session_data = PubSessionData {
+        user_id: session.user_id,
+        token: None,
+        expires: None,
+        csrf_token: None
+    };
+
+    if options.transport != SessionTransport::Cookie {
+        session_data.token = Some(session.id.clone());
+        session_data.expires = Some(session.expires);
+    }
+
+    if options.transport == SessionTransport::Body {
+        return Ok((StatusCode::CREATED, jar, Json(session_data)));
+    }
+
+    let cookie = SESSION_COOKIE_SEAL.seal(SESSION_COOKIE.build(session.id, session.expires));
+
+    let csrf_token = generate_csrf_token();
+    let jar = jar.add(cookie).add(csrf_cookie(csrf_token.clone(), session.expires));
+    session_data.csrf_token = Some(csrf_token);
 
-    let user_data = PubUserData {
+    Ok((StatusCode::CREATED, jar, Json(session_data)))
+}
+
+/// Hands a rotated session back the way the old one arrived, a cookie is replaced in place
+/// while bearer clients receive the new token in the body.
+pub fn reissue(jar: CookieJar, session: SessionData) -> (CookieJar, PubSessionData) {
+    let mut session_data = PubSessionData {
         user_id: session.user_id,
+        token: None,
+        expires: None,
+        csrf_token: None
     };
 
-    Ok((StatusCode::CREATED, jar.add(cookie), Json(user_data)))
+    if jar.get(SESSION_COOKIE_NAME.as_str()).is_none() {
+        session_data.token = Some(session.id);
+        session_data.expires = Some(session.expires);
+        return (jar, session_data);
+    }
+
+    let cookie = SESSION_COOKIE_SEAL.seal(SESSION_COOKIE.build(session.id, session.expires));
+    (jar.add(cookie), session_data)
 }
 
+/// Resolves the user the given session belongs to.
+pub async fn authenticate<T: SessionService>(service: &T, session_id: &str, client: &ClientInfo) -> Result<User, StatusCode> {
+    match service.verify(session_id, client).await {
+        Err(SessionVerifyError::Missing) =>  {
+            warn!("Provided session is not valid: {}", session_id);
+            Err(StatusCode::UNAUTHORIZED)
+        },
+        Err(SessionVerifyError::BindingMismatch) => {
+            warn!("Provided session is bound to another client");
+            Err(StatusCode::UNAUTHORIZED)
+        },
+        Err(SessionVerifyError::StaleAuthentication) => {
+            warn!("Provided session needs to be re-authenticated");
+            Err(StatusCode::FORBIDDEN)
+        },
+        Err(SessionVerifyError::Unknown) => {
+            error!("Unexpected error during session verification attempt");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        },
+        Ok(user) => Ok(user)
+    }
+}
+
+/// Sessions started before CSRF protection was enabled have no `CSRF-TOKEN` cookie, they get one
+/// on their next verification so that the frontend can keep sending unsafe requests. Only browsers
+/// need it, so nothing is set unless the verified session came from the session cookie.
+fn ensure_csrf_cookie(jar: CookieJar, session_id: &str) -> CookieJar {
+    let from_cookie = jar
+        .get(SESSION_COOKIE_NAME.as_str())
+        .and_then(|cookie| SESSION_COOKIE_SEAL.open(cookie))
+        .is_some_and(|cookie| cookie.value() == session_id);
+    let has_csrf_cookie = jar.get(CSRF_COOKIE.name.as_str()).is_some_and(|cookie| !cookie.value().is_empty());
+    if !from_cookie || has_csrf_cookie {
+        return jar;
+    }
+
+    info!("Issuing a CSRF token to a session that has none");
+    // the session's expiry is unknown here, but no session outlives the session length from now
+    jar.add(csrf_cookie(generate_csrf_token(), Utc::now().timestamp() + *SESSION_LENGTH_SECONDS))
+}
+
+/// Headers identifying the owner of a verified credential, plus the cookies to update.
+type VerifiedCredential = (CookieJar, Option<TypedHeader<XTokenScopes>>, TypedHeader<XUserId>);
+
 #[tracing::instrument(skip_all)]
-pub async fn get_sessions<T: SessionService + Debug>(
+pub async fn get_sessions<T: SessionService + Debug, P: PersonalTokenService + Debug>(
     Extension(service): Extension<T>,
+    Extension(token_service): Extension<P>,
+    credential: Credential,
+    client: ClientInfo,
     jar: CookieJar
-) -> Result<TypedHeader<XUserId>, StatusCode> {
+) -> Result<VerifiedCredential, StatusCode> {
     info!("Received session verification attempt");
-    let session_id = match jar.get(SESSION_COOKIE_NAME.as_str()) {
-        Some(cookie) => cookie.value(),
-        None => {
-            warn!("No session provided!");
-            return Err(StatusCode::UNAUTHORIZED);
+    let session_id = match credential {
+        Credential::Session(session_id) => session_id,
+        Credential::PersonalToken(token) => {
+            let token = match token_service.verify(&token).await {
+                Err(PersonalTokenVerifyError::Missing) => {
+                    warn!("Provided personal token is not valid");
+                    return Err(StatusCode::UNAUTHORIZED);
+                },
+                Err(PersonalTokenVerifyError::Unknown) => {
+                    error!("Unexpected error during personal token verification attempt");
+                    return Err(StatusCode::INTERNAL_SERVER_ERROR);
+                },
+                Ok(token) => token
+            };
+
+            info!("Successfully verified personal token {}", token.id);
+            return Ok((jar, Some(TypedHeader(XTokenScopes(token.scopes))), TypedHeader(XUserId(token.user_id))));
         }
     };
 
-    if let Err(errs) = SessionId(String::from(session_id)).validate() {
-        warn!("Session ID: {}, Validation errors: {}", session_id, errs);
-        return Err(StatusCode::UNPROCESSABLE_ENTITY)
-    }
+    let user = authenticate(&service, &session_id, &client).await?;
 
-    let user = match service.verify(session_id).await {
-        Err(SessionVerifyError::Missing) =>  {
-            warn!("Provided session is not valid: {}", session_id);
+    info!("Successfully verified session");
+    Ok((ensure_csrf_cookie(jar, &session_id), None, TypedHeader(XUserId(user.id))))
+}
+
+/// Confirms the password of the logged in user, sensitive actions guarded by `RecentAuth` are allowed afterwards.
+/// Being a step-up, it also moves the session to a new id the same way a password change does.
+#[tracing::instrument(skip_all)]
+pub async fn post_reauthenticate<T: SessionService + Debug>(
+    Extension(service): Extension<T>,
+    SessionToken(session_id): SessionToken,
+    client: ClientInfo,
+    jar: CookieJar,
+    Json(reauthentication): Json<Reauthentication>
+) -> Result<(CookieJar, Json<PubSessionData>), StatusCode> {
+    info!("Received re-authentication attempt");
+    match service.reauthenticate(&session_id, &reauthentication.password, &client).await {
+        Ok(()) => (),
+        Err(ReauthenticateError::Missing) => {
+            warn!("Provided session is not valid");
             return Err(StatusCode::UNAUTHORIZED);
         },
-        Err(SessionVerifyError::Unknown) => {
-            error!("Unexpected error during session verification attempt");
+        Err(ReauthenticateError::WrongPassword) => {
+            warn!("Wrong password provided");
+            return Err(StatusCode::FORBIDDEN);
+        },
+        Err(ReauthenticateError::Unknown) => {
+            error!("Unexpected error during re-authentication attempt");
             return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let session = match service.rotate(&session_id).await {
+        Ok(session) => session,
+        Err(RotateError::Missing) => {
+            warn!("Session ended during the re-authentication");
+            return Err(StatusCode::UNAUTHORIZED);
         },
-        Ok(session) => session
+        Err(RotateError::Unknown) => {
+            error!("Re-authenticated but the session could not be rotated");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
     };
 
-    info!("Successfully verified session");
-    Ok(TypedHeader(XUserId(user.id)))
+    let (jar, session_data) = reissue(jar, session);
+    Ok((jar, Json(session_data)))
 }
 
 #[tracing::instrument(skip_all)]
 pub async fn delete_sessions<T: SessionService + Debug>(
     Extension(service): Extension<T>,
+    SessionToken(session_id): SessionToken,
     jar: CookieJar
 ) -> Result<CookieJar, StatusCode> {
     info!("Received logout attempt");
-    let session_id = match jar.get(SESSION_COOKIE_NAME.as_str()) {
-        Some(cookie) => cookie.value(),
-        None => {
-            warn!("No session provided!");
-            return Err(StatusCode::UNAUTHORIZED);
-        }
-    };
-
-    if let Err(errs) = SessionId(String::from(session_id)).validate() {
-        warn!("Session ID: {}, Validation errors: {}", session_id, errs);
-        return Err(StatusCode::UNPROCESSABLE_ENTITY);
-    }
-
-    let cookie = match service.logout(session_id).await {
-        Ok(()) => {
-            let expiration = OffsetDateTime::now_utc().saturating_sub(Duration::days(*SESSION_EXPIRE_BUFFER_DAYS));
-            Cookie::build(SESSION_COOKIE_NAME.as_str(), "")
-                .expires(expiration)
-                .finish()
-        },
+    let expiration = match service.logout(&session_id).await {
+        Ok(()) => OffsetDateTime::now_utc().saturating_sub(Duration::days(*SESSION_EXPIRE_BUFFER_DAYS)),
         Err(LogoutError::Unknown) => {
             error!("Unable to process logout attempt");
             return Err(StatusCode::INTERNAL_SERVER_ERROR);
@@ -111,8 +214,14 @@ pub async fn delete_sessions<T: SessionService + Debug>(
     };
 
     info!("Successfully logged out session {}", session_id);
-    Ok(jar.add(cookie))
+    if jar.get(SESSION_COOKIE_NAME.as_str()).is_none() {
+        // bearer clients have no cookie to clear
+        return Ok(jar);
+    }
+    Ok(jar.add(SESSION_COOKIE.removal(expiration)).add(CSRF_COOKIE.removal(expiration)))
 }
 
 #[cfg(test)]
+// newer clippy prefers repeat_n, the test helpers predate it
+#[allow(clippy::manual_repeat_n)]
 mod tests;
@@ -1,7 +1,10 @@
+use std::sync::Arc;
+
+use axum_extra::extract::cookie::Cookie;
 use chrono::Utc;
 use mockall::predicate;
 
-use crate::{service::sessions::MockSessionService, domain::{sessions::SessionData, users::User}, constants::SESSION_ID_LENGTH};
+use crate::{service::{sessions::{MockSessionService, HashSessionService}, personal_tokens::MockPersonalTokenService, hash::MockHashService}, repository::{memory::{MemoryStore, MemoryUserRepository, MemorySessionRepository}, users::UserRepository, sessions::SessionRepository}, domain::{sessions::{SessionData, SessionBinding}, users::{User, UserData}, personal_tokens::PersonalToken}, constants::{SESSION_ID_LENGTH, PERSONAL_TOKEN_PREFIX, CSRF_TOKEN_LENGTH, SESSION_ROTATION_GRACE_SECONDS}};
 
 use super::*;
 
@@ -36,7 +39,23 @@ fn mock_session_data() -> SessionData {
     SessionData {
         id: mock_session_id(),
         user_id: 1,
-        expires: Utc::now().timestamp()
+        expires: Utc::now().timestamp(),
+        authenticated_at: 0,
+        binding: SessionBinding::default()
+    }
+}
+
+fn mock_personal_token() -> String {
+    String::from(PERSONAL_TOKEN_PREFIX) + "token"
+}
+
+fn mock_personal_token_details() -> PersonalToken {
+    PersonalToken {
+        id: 2,
+        user_id: 1,
+        name: String::from("ci"),
+        scopes: vec![String::from("documents:compile")],
+        expires: None
     }
 }
 
@@ -53,19 +72,71 @@ async fn post_sessions_normal() {
 
     session_service
         .expect_login()
-        .with(predicate::eq(mock_credentials()))
+        .with(predicate::eq(mock_credentials()), predicate::always())
         .times(1)
-        .return_once(|_| Ok(session_data_cpy));
+        .return_once(|_, _| Ok(session_data_cpy));
 
-    let (status, jar, Json(user)) = post_sessions(Extension(session_service), CookieJar::new(), Json(mock_credentials())).await.unwrap();
+    let (status, jar, Json(user)) = post_sessions(Extension(session_service), Query(LoginOptions::default()), CookieJar::new(), ClientInfo::default(), Json(mock_credentials())).await.unwrap();
     assert_eq!(StatusCode::CREATED, status);
 
     let cookie = jar.get(SESSION_COOKIE_NAME.as_str()).unwrap();
     assert_eq!(session_data.id, cookie.value());
     assert_eq!(session_data.expires, cookie.expires().unwrap().datetime().unwrap().unix_timestamp());
     assert!(cookie.http_only().unwrap());
-    assert_eq!(*IS_COOKIE_SECURE, cookie.secure().unwrap());
+    assert_eq!(SESSION_COOKIE.secure, cookie.secure().unwrap());
+    assert_eq!(Some(SESSION_COOKIE.same_site.into()), cookie.same_site());
+    assert_eq!(Some(SESSION_COOKIE.path.as_str()), cookie.path());
     assert_eq!(session_data.user_id, user.user_id);
+    assert_eq!(None, user.token);
+
+    let csrf_cookie = jar.get(CSRF_COOKIE.name.as_str()).unwrap();
+    assert_eq!(user.csrf_token.as_deref(), Some(csrf_cookie.value()));
+    assert!(!csrf_cookie.http_only().unwrap());
+}
+
+#[tokio::test]
+async fn post_sessions_body_transport() {
+    let mut session_service = MockSessionService::new();
+
+    let session_data = mock_session_data();
+    let session_data_cpy = session_data.clone();
+
+    session_service
+        .expect_login()
+        .with(predicate::eq(mock_credentials()), predicate::always())
+        .times(1)
+        .return_once(|_, _| Ok(session_data_cpy));
+
+    let options = LoginOptions { transport: SessionTransport::Body };
+    let (status, jar, Json(body)) = post_sessions(Extension(session_service), Query(options), CookieJar::new(), ClientInfo::default(), Json(mock_credentials())).await.unwrap();
+    assert_eq!(StatusCode::CREATED, status);
+
+    assert!(jar.get(SESSION_COOKIE_NAME.as_str()).is_none());
+    assert!(jar.get(CSRF_COOKIE.name.as_str()).is_none());
+    assert_eq!(None, body.csrf_token);
+    assert_eq!(Some(session_data.id), body.token);
+    assert_eq!(Some(session_data.expires), body.expires);
+    assert_eq!(session_data.user_id, body.user_id);
+}
+
+#[tokio::test]
+async fn post_sessions_both_transport() {
+    let mut session_service = MockSessionService::new();
+
+    let session_data = mock_session_data();
+    let session_data_cpy = session_data.clone();
+
+    session_service
+        .expect_login()
+        .with(predicate::eq(mock_credentials()), predicate::always())
+        .times(1)
+        .return_once(|_, _| Ok(session_data_cpy));
+
+    let options = LoginOptions { transport: SessionTransport::Both };
+    let (_, jar, Json(body)) = post_sessions(Extension(session_service), Query(options), CookieJar::new(), ClientInfo::default(), Json(mock_credentials())).await.unwrap();
+
+    assert_eq!(session_data.id, jar.get(SESSION_COOKIE_NAME.as_str()).unwrap().value());
+    assert_eq!(Some(session_data.id), body.token);
 }
 
 #[tokio::test]
@@ -74,11 +145,11 @@ async fn post_sessions_no_user_error() {
 
     session_service
         .expect_login()
-        .with(predicate::eq(mock_credentials()))
+        .with(predicate::eq(mock_credentials()), predicate::always())
         .times(1)
-        .returning(|_| Err(LoginError::NoUser));
+        .returning(|_, _| Err(LoginError::NoUser));
 
-    assert_eq!(StatusCode::UNAUTHORIZED, post_sessions(Extension(session_service), CookieJar::new(), Json(mock_credentials())).await.err().unwrap())
+    assert_eq!(StatusCode::UNAUTHORIZED, post_sessions(Extension(session_service), Query(LoginOptions::default()), CookieJar::new(), ClientInfo::default(), Json(mock_credentials())).await.err().unwrap())
 }
 
 #[tokio::test]
@@ -87,11 +158,11 @@ async fn post_sessions_unknown_error() {
 
     session_service
         .expect_login()
-        .with(predicate::eq(mock_credentials()))
+        .with(predicate::eq(mock_credentials()), predicate::always())
         .times(1)
-        .returning(|_| Err(LoginError::Unknown));
+        .returning(|_, _| Err(LoginError::Unknown));
 
-    assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, post_sessions(Extension(session_service), CookieJar::new(), Json(mock_credentials())).await.err().unwrap())
+    assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, post_sessions(Extension(session_service), Query(LoginOptions::default()), CookieJar::new(), ClientInfo::default(), Json(mock_credentials())).await.err().unwrap())
 }
 
 #[tokio::test]
@@ -100,12 +171,127 @@ async fn get_sessions_normal() {
 
     session_service
         .expect_verify()
-        .with(predicate::eq(mock_session_id()))
+        .with(predicate::eq(mock_session_id()), predicate::always())
         .times(1)
-        .returning(|_| Ok(mock_user()));
+        .returning(|_, _| Ok(mock_user()));
 
-    let TypedHeader(XUserId(user_id)) = get_sessions(Extension(session_service), mock_cookie_jar()).await.unwrap();
+    let (_, scopes, TypedHeader(XUserId(user_id))) = get_sessions(Extension(session_service), Extension(MockPersonalTokenService::new()), Credential::Session(mock_session_id()), ClientInfo::default(), CookieJar::new()).await.unwrap();
     assert_eq!(mock_user().id, user_id);
+    assert!(scopes.is_none());
+}
+
+#[tokio::test]
+async fn get_sessions_issues_missing_csrf_cookie() {
+    let mut session_service = MockSessionService::new();
+
+    session_service
+        .expect_verify()
+        .times(1)
+        .returning(|_, _| Ok(mock_user()));
+
+    let (jar, _, _) = get_sessions(Extension(session_service), Extension(MockPersonalTokenService::new()), Credential::Session(mock_session_id()), ClientInfo::default(), mock_cookie_jar()).await.unwrap();
+    let cookie = jar.get(CSRF_COOKIE.name.as_str()).unwrap();
+
+    assert_eq!(CSRF_TOKEN_LENGTH, cookie.value().len());
+    assert!(cookie.expires().unwrap().datetime().unwrap() > OffsetDateTime::now_utc());
+}
+
+#[tokio::test]
+async fn get_sessions_keeps_csrf_cookie() {
+    let mut session_service = MockSessionService::new();
+
+    session_service
+        .expect_verify()
+        .times(1)
+        .returning(|_, _| Ok(mock_user()));
+
+    let jar = mock_cookie_jar().add(Cookie::new(CSRF_COOKIE.name.clone(), "token"));
+    let (jar, _, _) = get_sessions(Extension(session_service), Extension(MockPersonalTokenService::new()), Credential::Session(mock_session_id()), ClientInfo::default(), jar).await.unwrap();
+
+    assert_eq!("token", jar.get(CSRF_COOKIE.name.as_str()).unwrap().value());
+}
+
+#[tokio::test]
+async fn get_sessions_bearer_no_csrf_cookie() {
+    let mut session_service = MockSessionService::new();
+
+    session_service
+        .expect_verify()
+        .times(1)
+        .returning(|_, _| Ok(mock_user()));
+
+    let (jar, _, _) = get_sessions(Extension(session_service), Extension(MockPersonalTokenService::new()), Credential::Session(mock_session_id()), ClientInfo::default(), CookieJar::new()).await.unwrap();
+
+    assert!(jar.get(CSRF_COOKIE.name.as_str()).is_none());
+}
+
+#[tokio::test]
+async fn get_sessions_bearer_with_cookie_no_csrf_cookie() {
+    let mut session_service = MockSessionService::new();
+
+    session_service
+        .expect_verify()
+        .times(1)
+        .returning(|_, _| Ok(mock_user()));
+
+    // the bearer was picked over a session cookie for another session
+    let jar = CookieJar::new().add(Cookie::new(SESSION_COOKIE_NAME.as_str(), "2".repeat(SESSION_ID_LENGTH)));
+    let (jar, _, _) = get_sessions(Extension(session_service), Extension(MockPersonalTokenService::new()), Credential::Session(mock_session_id()), ClientInfo::default(), jar).await.unwrap();
+
+    assert!(jar.get(CSRF_COOKIE.name.as_str()).is_none());
+}
+
+#[tokio::test]
+async fn get_sessions_personal_token_no_csrf_cookie() {
+    let mut token_service = MockPersonalTokenService::new();
+
+    token_service
+        .expect_verify()
+        .times(1)
+        .returning(|_| Ok(mock_personal_token_details()));
+
+    let (jar, _, _) = get_sessions(Extension(MockSessionService::new()), Extension(token_service), Credential::PersonalToken(mock_personal_token()), ClientInfo::default(), mock_cookie_jar()).await.unwrap();
+
+    assert!(jar.get(CSRF_COOKIE.name.as_str()).is_none());
+}
+
+#[tokio::test]
+async fn get_sessions_personal_token() {
+    let mut session_service = MockSessionService::new();
+    let mut token_service = MockPersonalTokenService::new();
+
+    session_service
+        .expect_verify()
+        .never();
+
+    token_service
+        .expect_verify()
+        .with(predicate::eq(mock_personal_token()))
+        .times(1)
+        .returning(|_| Ok(mock_personal_token_details()));
+
+    let (_, scopes, TypedHeader(XUserId(user_id))) = get_sessions(Extension(session_service), Extension(token_service), Credential::PersonalToken(mock_personal_token()), ClientInfo::default(), CookieJar::new()).await.unwrap();
+    assert_eq!(mock_personal_token_details().user_id, user_id);
+    assert_eq!(mock_personal_token_details().scopes, scopes.unwrap().0.0);
+}
+
+#[tokio::test]
+async fn get_sessions_personal_token_missing_error() {
+    let mut session_service = MockSessionService::new();
+    let mut token_service = MockPersonalTokenService::new();
+
+    session_service
+        .expect_verify()
+        .never();
+
+    token_service
+        .expect_verify()
+        .with(predicate::eq(mock_personal_token()))
+        .times(1)
+        .returning(|_| Err(PersonalTokenVerifyError::Missing));
+
+    let res = get_sessions(Extension(session_service), Extension(token_service), Credential::PersonalToken(mock_personal_token()), ClientInfo::default(), CookieJar::new()).await.err().unwrap();
+    assert_eq!(StatusCode::UNAUTHORIZED, res);
 }
 
 #[tokio::test]
@@ -114,11 +300,26 @@ async fn get_sessions_missing_error() {
 
     session_service
         .expect_verify()
-        .with(predicate::eq(mock_session_id()))
+        .with(predicate::eq(mock_session_id()), predicate::always())
+        .times(1)
+        .returning(|_, _| Err(SessionVerifyError::Missing));
+
+    let res = get_sessions(Extension(session_service), Extension(MockPersonalTokenService::new()), Credential::Session(mock_session_id()), ClientInfo::default(), CookieJar::new()).await.err().unwrap();
+    assert_eq!(StatusCode::UNAUTHORIZED, res);
+}
+
+#[tokio::test]
+async fn get_sessions_binding_mismatch_error() {
+    let mut session_service = MockSessionService::new();
+    let client = ClientInfo { user_agent: Some(String::from("curl/8.0.1")), ip: None };
+
+    session_service
+        .expect_verify()
+        .with(predicate::eq(mock_session_id()), predicate::eq(client.clone()))
         .times(1)
-        .returning(|_| Err(SessionVerifyError::Missing));
+        .returning(|_, _| Err(SessionVerifyError::BindingMismatch));
 
-    let res = get_sessions(Extension(session_service), mock_cookie_jar()).await.err().unwrap();
+    let res = get_sessions(Extension(session_service), Extension(MockPersonalTokenService::new()), Credential::Session(mock_session_id()), client, CookieJar::new()).await.err().unwrap();
     assert_eq!(StatusCode::UNAUTHORIZED, res);
 }
 
@@ -128,11 +329,11 @@ async fn get_sessions_unknown_error() {
 
     session_service
         .expect_verify()
-        .with(predicate::eq(mock_session_id()))
+        .with(predicate::eq(mock_session_id()), predicate::always())
         .times(1)
-        .returning(|_| Err(SessionVerifyError::Unknown));
+        .returning(|_, _| Err(SessionVerifyError::Unknown));
 
-    let res = get_sessions(Extension(session_service), mock_cookie_jar()).await.err().unwrap();
+    let res = get_sessions(Extension(session_service), Extension(MockPersonalTokenService::new()), Credential::Session(mock_session_id()), ClientInfo::default(), CookieJar::new()).await.err().unwrap();
     assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, res);
 }
 
@@ -146,11 +347,27 @@ async fn delete_sessions_normal() {
         .times(1)
         .returning(|_| Ok(()));
 
-    let jar = delete_sessions(Extension(session_service), mock_cookie_jar()).await.unwrap();
+    let jar = delete_sessions(Extension(session_service), SessionToken(mock_session_id()), mock_cookie_jar()).await.unwrap();
     let cookie = jar.get(&SESSION_COOKIE_NAME).unwrap();
 
     assert_eq!("", cookie.value());
     assert!(cookie.expires().unwrap().datetime().unwrap() < OffsetDateTime::now_utc());
+    assert_eq!("", jar.get(CSRF_COOKIE.name.as_str()).unwrap().value());
+}
+
+#[tokio::test]
+async fn delete_sessions_bearer() {
+    let mut session_service = MockSessionService::new();
+
+    session_service
+        .expect_logout()
+        .with(predicate::eq(mock_session_id()))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let jar = delete_sessions(Extension(session_service), SessionToken(mock_session_id()), CookieJar::new()).await.unwrap();
+
+    assert!(jar.get(&SESSION_COOKIE_NAME).is_none());
 }
 
 #[tokio::test]
@@ -163,7 +380,112 @@ async fn delete_sessions_unknown_error() {
         .times(1)
         .returning(|_| Err(LogoutError::Unknown));
 
-    let err = delete_sessions(Extension(session_service), mock_cookie_jar()).await.unwrap_err();
+    let err = delete_sessions(Extension(session_service), SessionToken(mock_session_id()), mock_cookie_jar()).await.unwrap_err();
 
     assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, err);
 }
+
+fn mock_reauthentication() -> Reauthentication {
+    Reauthentication { password: mock_password() }
+}
+
+fn mock_rotated_session() -> SessionData {
+    SessionData {
+        id: String::from("rotated"),
+        user_id: 1,
+        expires: 1000,
+        authenticated_at: 900,
+        binding: SessionBinding::default()
+    }
+}
+
+#[tokio::test]
+async fn post_reauthenticate_normal() {
+    let mut session_service = MockSessionService::new();
+
+    session_service
+        .expect_reauthenticate()
+        .with(predicate::eq(mock_session_id()), predicate::eq(mock_password()), predicate::always())
+        .times(1)
+        .returning(|_, _, _| Ok(()));
+    session_service
+        .expect_rotate()
+        .with(predicate::eq(mock_session_id()))
+        .times(1)
+        .returning(|_| Ok(mock_rotated_session()));
+
+    let (jar, Json(session_data)) = post_reauthenticate(Extension(session_service), SessionToken(mock_session_id()), ClientInfo::default(), mock_cookie_jar(), Json(mock_reauthentication())).await.unwrap();
+
+    assert_eq!("rotated", jar.get(&SESSION_COOKIE_NAME).unwrap().value());
+    assert_eq!(None, session_data.token);
+}
+
+#[tokio::test]
+async fn post_reauthenticate_bearer_returns_token() {
+    let mut session_service = MockSessionService::new();
+
+    session_service
+        .expect_reauthenticate()
+        .times(1)
+        .returning(|_, _, _| Ok(()));
+    session_service
+        .expect_rotate()
+        .times(1)
+        .returning(|_| Ok(mock_rotated_session()));
+
+    let (jar, Json(session_data)) = post_reauthenticate(Extension(session_service), SessionToken(mock_session_id()), ClientInfo::default(), CookieJar::new(), Json(mock_reauthentication())).await.unwrap();
+
+    assert!(jar.get(&SESSION_COOKIE_NAME).is_none());
+    assert_eq!(Some(String::from("rotated")), session_data.token);
+    assert_eq!(Some(1000), session_data.expires);
+}
+
+#[tokio::test]
+async fn post_reauthenticate_shortens_old_session() {
+    let store = Arc::new(MemoryStore::new());
+    let users = MemoryUserRepository::new(store.clone());
+    users.insert(UserData { email: mock_email(), password_hash: String::from("hash") }).await.ok().unwrap();
+    let sessions = MemorySessionRepository::new(store);
+    let expires = Utc::now().timestamp() + 3600;
+    sessions.insert(&SessionData { id: mock_session_id(), user_id: 1, expires, authenticated_at: 0, binding: SessionBinding::default() }).await.ok().unwrap();
+
+    let mut hash_service = MockHashService::new();
+    hash_service
+        .expect_verify()
+        .returning(|_, _| Ok(true));
+    let service = HashSessionService::new(sessions.clone(), users, hash_service, 1);
+
+    let (_, Json(session_data)) = post_reauthenticate(Extension(service), SessionToken(mock_session_id()), ClientInfo::default(), CookieJar::new(), Json(mock_reauthentication())).await.unwrap();
+
+    let new_id = session_data.token.unwrap();
+    assert_ne!(mock_session_id(), new_id);
+    assert_eq!(Some(expires), session_data.expires);
+    assert!(sessions.get(&new_id).await.ok().unwrap().authenticated_at > 0);
+    assert!(sessions.get(&mock_session_id()).await.ok().unwrap().expires <= Utc::now().timestamp() + *SESSION_ROTATION_GRACE_SECONDS);
+}
+
+#[tokio::test]
+async fn post_reauthenticate_wrong_password() {
+    let mut session_service = MockSessionService::new();
+
+    session_service
+        .expect_reauthenticate()
+        .times(1)
+        .returning(|_, _, _| Err(ReauthenticateError::WrongPassword));
+
+    let res = post_reauthenticate(Extension(session_service), SessionToken(mock_session_id()), ClientInfo::default(), mock_cookie_jar(), Json(mock_reauthentication())).await.unwrap_err();
+    assert_eq!(StatusCode::FORBIDDEN, res);
+}
+
+#[tokio::test]
+async fn post_reauthenticate_missing() {
+    let mut session_service = MockSessionService::new();
+
+    session_service
+        .expect_reauthenticate()
+        .times(1)
+        .returning(|_, _, _| Err(ReauthenticateError::Missing));
+
+    let res = post_reauthenticate(Extension(session_service), SessionToken(mock_session_id()), ClientInfo::default(), mock_cookie_jar(), Json(mock_reauthentication())).await.unwrap_err();
+    assert_eq!(StatusCode::UNAUTHORIZED, res);
+}
@@ -0,0 +1,97 @@
+use std::fmt::Debug;
+
+use axum::{Extension, Json, http::StatusCode};
+use jsonwebtoken::jwk::JwkSet;
+use tracing::{error, info, warn};
+
+use crate::{domain::{tokens::{AccessToken, RefreshRequest}, sessions::ClientInfo}, service::{sessions::{SessionService, SessionVerifyError}, tokens::{TokenService, TokenIssueError}, refresh_tokens::{RefreshTokenService, RefreshTokenIssueError, RefreshError}}, validation::ValidatedJson, control::sessions::authenticate, extract::SessionToken};
+
+#[tracing::instrument(skip_all)]
+pub async fn post_tokens<S: SessionService + Debug, T: TokenService + Debug, R: RefreshTokenService + Debug>(
+    Extension(session_service): Extension<S>,
+    Extension(token_service): Extension<T>,
+    Extension(refresh_service): Extension<R>,
+    SessionToken(session_id): SessionToken,
+    client: ClientInfo
+) -> Result<(StatusCode, Json<AccessToken>), StatusCode> {
+    info!("Received access token request");
+    let user = authenticate(&session_service, &session_id, &client).await?;
+
+    let mut token = match token_service.issue(user.id, &session_id) {
+        Ok(token) => token,
+        Err(TokenIssueError::Unknown) => {
+            error!("Unable to issue access token");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    match refresh_service.issue(user.id, &session_id).await {
+        Ok(refresh_token) => token.refresh_token = Some(refresh_token.id),
+        Err(RefreshTokenIssueError::Unknown) => {
+            error!("Unable to issue refresh token");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    Ok((StatusCode::CREATED, Json(token)))
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn post_refresh<S: SessionService + Debug, T: TokenService + Debug, R: RefreshTokenService + Debug>(
+    Extension(session_service): Extension<S>,
+    Extension(token_service): Extension<T>,
+    Extension(refresh_service): Extension<R>,
+    client: ClientInfo,
+    ValidatedJson(request): ValidatedJson<RefreshRequest>
+) -> Result<(StatusCode, Json<AccessToken>), StatusCode> {
+    info!("Received refresh attempt");
+    let refresh_token = match refresh_service.refresh(&request.refresh_token).await {
+        Ok(refresh_token) => refresh_token,
+        Err(RefreshError::Missing) => {
+            warn!("Provided refresh token is not valid");
+            return Err(StatusCode::UNAUTHORIZED);
+        },
+        Err(RefreshError::Reused) => {
+            warn!("Provided refresh token was already used, its family has been revoked");
+            return Err(StatusCode::UNAUTHORIZED);
+        },
+        Err(RefreshError::Unknown) => {
+            error!("Unexpected error during refresh attempt");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    match session_service.verify(&refresh_token.session_id, &client).await {
+        Ok(user) if user.id == refresh_token.user_id => (),
+        Ok(_) | Err(SessionVerifyError::Missing | SessionVerifyError::BindingMismatch | SessionVerifyError::StaleAuthentication) => {
+            warn!("Session bound to refresh token is no longer valid");
+            return Err(StatusCode::UNAUTHORIZED);
+        },
+        Err(SessionVerifyError::Unknown) => {
+            error!("Unexpected error during session verification attempt");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let mut token = match token_service.issue(refresh_token.user_id, &refresh_token.session_id) {
+        Ok(token) => token,
+        Err(TokenIssueError::Unknown) => {
+            error!("Unable to issue access token");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    token.refresh_token = Some(refresh_token.id);
+
+    info!("Successfully refreshed access token");
+    Ok((StatusCode::CREATED, Json(token)))
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn get_jwks<T: TokenService + Debug>(
+    Extension(token_service): Extension<T>
+) -> Json<JwkSet> {
+    Json(token_service.jwks())
+}
+
+#[cfg(test)]
+mod tests;
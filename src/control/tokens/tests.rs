@@ -0,0 +1,269 @@
+use mockall::predicate;
+
+use crate::{service::{sessions::MockSessionService, tokens::MockTokenService, refresh_tokens::MockRefreshTokenService}, domain::{users::User, tokens::RefreshTokenData, sessions::ClientInfo}, constants::{SESSION_ID_LENGTH, REFRESH_TOKEN_LENGTH}};
+
+use super::*;
+
+fn mock_session_id() -> String {
+    "1".repeat(SESSION_ID_LENGTH)
+}
+
+fn mock_user() -> User {
+    User {
+        id: 1,
+        email: String::from("email"),
+        password_hash: String::from("password")
+    }
+}
+
+fn mock_access_token() -> AccessToken {
+    AccessToken {
+        access_token: String::from("token"),
+        token_type: String::from("Bearer"),
+        expires_in: 300,
+        refresh_token: None
+    }
+}
+
+fn mock_refresh_token_id() -> String {
+    "2".repeat(REFRESH_TOKEN_LENGTH)
+}
+
+fn mock_rotated_refresh_token_id() -> String {
+    "3".repeat(REFRESH_TOKEN_LENGTH)
+}
+
+fn mock_refresh_token_data(id: String) -> RefreshTokenData {
+    RefreshTokenData {
+        id,
+        family_id: String::from("family_id"),
+        user_id: 1,
+        session_id: mock_session_id(),
+        expires: 0
+    }
+}
+
+fn mock_refresh_request() -> RefreshRequest {
+    RefreshRequest {
+        refresh_token: mock_refresh_token_id()
+    }
+}
+
+#[tokio::test]
+async fn post_tokens_normal() {
+    let mut session_service = MockSessionService::new();
+    let mut token_service = MockTokenService::new();
+    let mut refresh_service = MockRefreshTokenService::new();
+
+    session_service
+        .expect_verify()
+        .with(predicate::eq(mock_session_id()), predicate::always())
+        .times(1)
+        .returning(|_, _| Ok(mock_user()));
+
+    token_service
+        .expect_issue()
+        .with(predicate::eq(1), predicate::eq(mock_session_id()))
+        .times(1)
+        .returning(|_, _| Ok(mock_access_token()));
+
+    refresh_service
+        .expect_issue()
+        .with(predicate::eq(1), predicate::eq(mock_session_id()))
+        .times(1)
+        .returning(|_, _| Ok(mock_refresh_token_data(mock_refresh_token_id())));
+
+    let (status, Json(token)) = post_tokens(Extension(session_service), Extension(token_service), Extension(refresh_service), SessionToken(mock_session_id()), ClientInfo::default()).await.unwrap();
+    assert_eq!(StatusCode::CREATED, status);
+    assert_eq!(mock_access_token().access_token, token.access_token);
+    assert_eq!(Some(mock_refresh_token_id()), token.refresh_token);
+}
+
+#[tokio::test]
+async fn post_tokens_missing_session() {
+    let mut session_service = MockSessionService::new();
+    let mut token_service = MockTokenService::new();
+    let mut refresh_service = MockRefreshTokenService::new();
+
+    session_service
+        .expect_verify()
+        .with(predicate::eq(mock_session_id()), predicate::always())
+        .times(1)
+        .returning(|_, _| Err(SessionVerifyError::Missing));
+
+    token_service
+        .expect_issue()
+        .never();
+
+    refresh_service
+        .expect_issue()
+        .never();
+
+    let err = post_tokens(Extension(session_service), Extension(token_service), Extension(refresh_service), SessionToken(mock_session_id()), ClientInfo::default()).await.unwrap_err();
+    assert_eq!(StatusCode::UNAUTHORIZED, err);
+}
+
+#[tokio::test]
+async fn post_tokens_issue_error() {
+    let mut session_service = MockSessionService::new();
+    let mut token_service = MockTokenService::new();
+    let mut refresh_service = MockRefreshTokenService::new();
+
+    session_service
+        .expect_verify()
+        .with(predicate::eq(mock_session_id()), predicate::always())
+        .times(1)
+        .returning(|_, _| Ok(mock_user()));
+
+    token_service
+        .expect_issue()
+        .times(1)
+        .returning(|_, _| Err(TokenIssueError::Unknown));
+
+    refresh_service
+        .expect_issue()
+        .never();
+
+    let err = post_tokens(Extension(session_service), Extension(token_service), Extension(refresh_service), SessionToken(mock_session_id()), ClientInfo::default()).await.unwrap_err();
+    assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, err);
+}
+
+#[tokio::test]
+async fn post_tokens_refresh_issue_error() {
+    let mut session_service = MockSessionService::new();
+    let mut token_service = MockTokenService::new();
+    let mut refresh_service = MockRefreshTokenService::new();
+
+    session_service
+        .expect_verify()
+        .with(predicate::eq(mock_session_id()), predicate::always())
+        .times(1)
+        .returning(|_, _| Ok(mock_user()));
+
+    token_service
+        .expect_issue()
+        .times(1)
+        .returning(|_, _| Ok(mock_access_token()));
+
+    refresh_service
+        .expect_issue()
+        .times(1)
+        .returning(|_, _| Err(RefreshTokenIssueError::Unknown));
+
+    let err = post_tokens(Extension(session_service), Extension(token_service), Extension(refresh_service), SessionToken(mock_session_id()), ClientInfo::default()).await.unwrap_err();
+    assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, err);
+}
+
+#[tokio::test]
+async fn post_refresh_normal() {
+    let mut session_service = MockSessionService::new();
+    let mut token_service = MockTokenService::new();
+    let mut refresh_service = MockRefreshTokenService::new();
+
+    refresh_service
+        .expect_refresh()
+        .with(predicate::eq(mock_refresh_token_id()))
+        .times(1)
+        .returning(|_| Ok(mock_refresh_token_data(mock_rotated_refresh_token_id())));
+
+    session_service
+        .expect_verify()
+        .with(predicate::eq(mock_session_id()), predicate::always())
+        .times(1)
+        .returning(|_, _| Ok(mock_user()));
+
+    token_service
+        .expect_issue()
+        .with(predicate::eq(1), predicate::eq(mock_session_id()))
+        .times(1)
+        .returning(|_, _| Ok(mock_access_token()));
+
+    let (status, Json(token)) = post_refresh(Extension(session_service), Extension(token_service), Extension(refresh_service), ClientInfo::default(), ValidatedJson(mock_refresh_request())).await.unwrap();
+    assert_eq!(StatusCode::CREATED, status);
+    assert_eq!(Some(mock_rotated_refresh_token_id()), token.refresh_token);
+}
+
+#[tokio::test]
+async fn post_refresh_reused() {
+    let mut session_service = MockSessionService::new();
+    let mut token_service = MockTokenService::new();
+    let mut refresh_service = MockRefreshTokenService::new();
+
+    refresh_service
+        .expect_refresh()
+        .with(predicate::eq(mock_refresh_token_id()))
+        .times(1)
+        .returning(|_| Err(RefreshError::Reused));
+
+    session_service
+        .expect_verify()
+        .never();
+
+    token_service
+        .expect_issue()
+        .never();
+
+    let err = post_refresh(Extension(session_service), Extension(token_service), Extension(refresh_service), ClientInfo::default(), ValidatedJson(mock_refresh_request())).await.unwrap_err();
+    assert_eq!(StatusCode::UNAUTHORIZED, err);
+}
+
+#[tokio::test]
+async fn post_refresh_session_missing() {
+    let mut session_service = MockSessionService::new();
+    let mut token_service = MockTokenService::new();
+    let mut refresh_service = MockRefreshTokenService::new();
+
+    refresh_service
+        .expect_refresh()
+        .times(1)
+        .returning(|_| Ok(mock_refresh_token_data(mock_rotated_refresh_token_id())));
+
+    session_service
+        .expect_verify()
+        .with(predicate::eq(mock_session_id()), predicate::always())
+        .times(1)
+        .returning(|_, _| Err(SessionVerifyError::Missing));
+
+    token_service
+        .expect_issue()
+        .never();
+
+    let err = post_refresh(Extension(session_service), Extension(token_service), Extension(refresh_service), ClientInfo::default(), ValidatedJson(mock_refresh_request())).await.unwrap_err();
+    assert_eq!(StatusCode::UNAUTHORIZED, err);
+}
+
+#[tokio::test]
+async fn post_refresh_unknown_error() {
+    let mut session_service = MockSessionService::new();
+    let mut token_service = MockTokenService::new();
+    let mut refresh_service = MockRefreshTokenService::new();
+
+    refresh_service
+        .expect_refresh()
+        .times(1)
+        .returning(|_| Err(RefreshError::Unknown));
+
+    session_service
+        .expect_verify()
+        .never();
+
+    token_service
+        .expect_issue()
+        .never();
+
+    let err = post_refresh(Extension(session_service), Extension(token_service), Extension(refresh_service), ClientInfo::default(), ValidatedJson(mock_refresh_request())).await.unwrap_err();
+    assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, err);
+}
+
+#[tokio::test]
+async fn get_jwks_normal() {
+    let mut token_service = MockTokenService::new();
+
+    token_service
+        .expect_jwks()
+        .times(1)
+        .returning(|| JwkSet { keys: vec![] });
+
+    let Json(jwks) = get_jwks(Extension(token_service)).await;
+    assert!(jwks.keys.is_empty());
+}
@@ -1,23 +1,82 @@
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 
-use axum::{Extension, http::StatusCode};
-use tracing::info;
+use axum::{Extension, Json, http::StatusCode, response::{IntoResponse, Response}};
+use axum_extra::extract::CookieJar;
+use tracing::{error, info, warn};
 
-use crate::{service::{users::{UserService, UserCreationError}}, validation::ValidatedJson, domain::users::Credentials};
+use crate::{service::{users::{UserService, UserCreationError, PasswordChangeError}, sessions::{SessionService, RotateError}}, validation::ValidatedJsonRejection, domain::{users::{Credentials, PasswordChange}, sessions::PubSessionData}, extract::{SessionToken, RecentAuth}, control::sessions::reissue, passwords::{PasswordPolicy, BreachedPasswords}, constants::PASSWORD_POLICY};
 
 #[tracing::instrument(skip_all, fields(email = credentials.email))]
 pub async fn post_users<T: UserService + Debug>(
     Extension(service): Extension<T>,
-    ValidatedJson(credentials): ValidatedJson<Credentials>
-) -> StatusCode {
+    Extension(breached): Extension<Arc<BreachedPasswords>>,
+    Json(credentials): Json<Credentials>
+) -> Result<StatusCode, Response> {
     info!("Received registration attempt");
 
+    if let Err(errs) = credentials.validate_registration(&breached).await {
+        warn!("Registration rejected");
+        return Err(ValidatedJsonRejection::ValidationRejection(errs).into_response());
+    }
+
     match service.register(credentials).await {
-        Ok(()) => StatusCode::CREATED,
-        Err(UserCreationError::DuplicateEmail) => StatusCode::CONFLICT,
-        Err(UserCreationError::Unknown) => StatusCode::INTERNAL_SERVER_ERROR
+        Ok(()) => Ok(StatusCode::CREATED),
+        Err(UserCreationError::DuplicateEmail) => Err(StatusCode::CONFLICT.into_response()),
+        Err(UserCreationError::Unknown) => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
     }
 }
 
+/// Lets the frontend show the rules while the user types, the same ones are enforced on registration and password change.
+pub async fn get_password_policy() -> Json<PasswordPolicy> {
+    Json(PASSWORD_POLICY.clone())
+}
+
+/// Changing the password moves the session to a new id, so an id captured before the change stops working.
+/// Requires a recent authentication like other sensitive actions, on top of the current password.
+/// Roles and second factors don't exist yet, their changes should rotate the session the same way.
+#[tracing::instrument(skip_all)]
+pub async fn put_password<U: UserService + Debug, S: SessionService + Clone + Debug + Send + Sync + 'static>(
+    Extension(users_service): Extension<U>,
+    Extension(sessions_service): Extension<S>,
+    Extension(breached): Extension<Arc<BreachedPasswords>>,
+    RecentAuth(user, _): RecentAuth<S>,
+    SessionToken(session_id): SessionToken,
+    jar: CookieJar,
+    Json(change): Json<PasswordChange>
+) -> Result<(CookieJar, Json<PubSessionData>), Response> {
+    info!("Received password change attempt");
+
+    if let Err(errs) = change.validate_for(&user, &breached).await {
+        warn!("New password rejected");
+        return Err(ValidatedJsonRejection::ValidationRejection(errs).into_response());
+    }
+
+    match users_service.change_password(&user, change).await {
+        Ok(()) => (),
+        Err(PasswordChangeError::WrongPassword) => {
+            warn!("Wrong current password provided");
+            return Err(StatusCode::FORBIDDEN.into_response());
+        },
+        Err(PasswordChangeError::Unknown) => return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+    };
+    // other sessions of the user stay valid, but caches still hold the old password hash
+    sessions_service.refresh_user(user.id).await;
+
+    let session = match sessions_service.rotate(&session_id).await {
+        Ok(session) => session,
+        Err(RotateError::Missing) => {
+            warn!("Session ended during the password change");
+            return Err(StatusCode::UNAUTHORIZED.into_response());
+        },
+        Err(RotateError::Unknown) => {
+            error!("Password changed but the session could not be rotated");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        }
+    };
+
+    let (jar, session_data) = reissue(jar, session);
+    Ok((jar, Json(session_data)))
+}
+
 #[cfg(test)]
 mod tests;
@@ -1,23 +1,30 @@
+use std::{marker::PhantomData, sync::Arc};
+
 use axum::Extension;
+use axum_extra::extract::cookie::Cookie;
 use http::StatusCode;
 use mockall::predicate;
 
-use crate::{service::users::MockUserService, domain::users::Credentials, validation::ValidatedJson};
+use crate::{service::{users::MockUserService, sessions::MockSessionService}, domain::{users::{Credentials, User}, sessions::{SessionData, SessionBinding}}, constants::SESSION_COOKIE_NAME};
 
 use super::*;
 
 fn mock_email() -> String {
-    String::from("email")
+    String::from("email@email.com")
 }
 
 fn mock_password() -> String {
     String::from("password")
 }
 
+fn mock_breached_passwords() -> Extension<Arc<BreachedPasswords>> {
+    Extension(Arc::new(BreachedPasswords::default()))
+}
+
 fn mock_credentials() -> Credentials {
     Credentials {
         email: mock_email(),
-        password: mock_password()
+        password: String::from("quiet lantern orbits marmalade")
     }
 }
 
@@ -31,7 +38,24 @@ async fn post_users_normal() {
         .times(1)
         .returning(|_| Ok(()));
 
-    assert_eq!(StatusCode::CREATED, post_users(Extension(user_service), ValidatedJson(mock_credentials())).await)
+    assert_eq!(StatusCode::CREATED, post_users(Extension(user_service), mock_breached_passwords(), Json(mock_credentials())).await.unwrap())
+}
+
+#[tokio::test]
+async fn post_users_weak_password() {
+    let mut user_service = MockUserService::new();
+
+    user_service
+        .expect_register()
+        .never();
+
+    let credentials = Credentials {
+        email: mock_email(),
+        password: mock_password()
+    };
+    let res = post_users(Extension(user_service), mock_breached_passwords(), Json(credentials)).await.unwrap_err();
+
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
 }
 
 #[tokio::test]
@@ -44,7 +68,7 @@ async fn post_users_duplicate_error() {
         .times(1)
         .returning(|_| Err(UserCreationError::DuplicateEmail));
 
-    assert_eq!(StatusCode::CONFLICT, post_users(Extension(user_service), ValidatedJson(mock_credentials())).await)
+    assert_eq!(StatusCode::CONFLICT, post_users(Extension(user_service), mock_breached_passwords(), Json(mock_credentials())).await.unwrap_err().status())
 }
 
 #[tokio::test]
@@ -57,5 +81,152 @@ async fn post_users_service_unknown_error() {
         .times(1)
         .returning(|_| Err(UserCreationError::Unknown));
 
-    assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, post_users(Extension(user_service), ValidatedJson(mock_credentials())).await)
+    assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, post_users(Extension(user_service), mock_breached_passwords(), Json(mock_credentials())).await.unwrap_err().status())
+}
+
+fn mock_session_id() -> String {
+    String::from("old_session")
+}
+
+fn mock_user() -> User {
+    User {
+        id: 1,
+        email: mock_email(),
+        password_hash: String::from("hash")
+    }
+}
+
+fn mock_password_change() -> PasswordChange {
+    PasswordChange {
+        current_password: mock_password(),
+        new_password: String::from("quiet lantern orbits marmalade")
+    }
+}
+
+fn mock_rotated_session() -> SessionData {
+    SessionData {
+        id: String::from("new_session"),
+        user_id: 1,
+        expires: 1000,
+        authenticated_at: 0,
+        binding: SessionBinding::default()
+    }
+}
+
+fn mock_session_service(rotations: usize) -> Arc<MockSessionService> {
+    let mut session_service = MockSessionService::new();
+
+    session_service
+        .expect_rotate()
+        .with(predicate::eq(mock_session_id()))
+        .times(rotations)
+        .returning(|_| Ok(mock_rotated_session()));
+
+    session_service
+        .expect_refresh_user()
+        .with(predicate::eq(mock_user().id))
+        .times(rotations)
+        .returning(|_| ());
+
+    Arc::new(session_service)
+}
+
+fn mock_recent_auth() -> RecentAuth<Arc<MockSessionService>> {
+    RecentAuth(mock_user(), PhantomData)
+}
+
+#[tokio::test]
+async fn put_password_reissues_cookie() {
+    let mut user_service = MockUserService::new();
+
+    user_service
+        .expect_change_password()
+        .with(predicate::eq(mock_user()), predicate::eq(mock_password_change()))
+        .times(1)
+        .returning(|_, _| Ok(()));
+
+    let jar = CookieJar::new().add(Cookie::new(SESSION_COOKIE_NAME.as_str(), mock_session_id()));
+    let (jar, Json(session_data)) = put_password(
+        Extension(user_service),
+        Extension(mock_session_service(1)),
+        mock_breached_passwords(),
+        mock_recent_auth(),
+        SessionToken(mock_session_id()),
+        jar,
+        Json(mock_password_change())
+    ).await.unwrap();
+
+    assert_eq!("new_session", jar.get(&SESSION_COOKIE_NAME).unwrap().value());
+    assert_eq!(None, session_data.token);
+}
+
+#[tokio::test]
+async fn put_password_bearer_returns_token() {
+    let mut user_service = MockUserService::new();
+
+    user_service
+        .expect_change_password()
+        .times(1)
+        .returning(|_, _| Ok(()));
+
+    let (jar, Json(session_data)) = put_password(
+        Extension(user_service),
+        Extension(mock_session_service(1)),
+        mock_breached_passwords(),
+        mock_recent_auth(),
+        SessionToken(mock_session_id()),
+        CookieJar::new(),
+        Json(mock_password_change())
+    ).await.unwrap();
+
+    assert!(jar.get(&SESSION_COOKIE_NAME).is_none());
+    assert_eq!(Some(String::from("new_session")), session_data.token);
+    assert_eq!(Some(1000), session_data.expires);
+}
+
+#[tokio::test]
+async fn put_password_wrong_password() {
+    let mut user_service = MockUserService::new();
+
+    user_service
+        .expect_change_password()
+        .times(1)
+        .returning(|_, _| Err(PasswordChangeError::WrongPassword));
+
+    let res = put_password(
+        Extension(user_service),
+        Extension(mock_session_service(0)),
+        mock_breached_passwords(),
+        mock_recent_auth(),
+        SessionToken(mock_session_id()),
+        CookieJar::new(),
+        Json(mock_password_change())
+    ).await.err().unwrap();
+
+    assert_eq!(StatusCode::FORBIDDEN, res.status());
+}
+
+#[tokio::test]
+async fn put_password_weak_password() {
+    let mut user_service = MockUserService::new();
+
+    user_service
+        .expect_change_password()
+        .never();
+
+    let change = PasswordChange {
+        current_password: mock_password(),
+        new_password: String::from("Password1!")
+    };
+    let res = put_password(
+        Extension(user_service),
+        Extension(mock_session_service(0)),
+        mock_breached_passwords(),
+        mock_recent_auth(),
+        SessionToken(mock_session_id()),
+        CookieJar::new(),
+        Json(change)
+    ).await.err().unwrap();
+
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
 }
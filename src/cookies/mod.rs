@@ -0,0 +1,128 @@
+mod seal;
+
+use std::{fmt, str::FromStr};
+
+use axum_extra::extract::cookie::{Cookie, SameSite};
+use cookie::time::OffsetDateTime;
+
+pub use seal::{CookieProtection, CookieSeal};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SameSitePolicy {
+    Strict,
+    Lax,
+    None
+}
+
+impl FromStr for SameSitePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "strict" => Ok(Self::Strict),
+            "lax" => Ok(Self::Lax),
+            "none" => Ok(Self::None),
+            other => Err(format!("Unknown SameSite policy: {}", other))
+        }
+    }
+}
+
+impl From<SameSitePolicy> for SameSite {
+    fn from(policy: SameSitePolicy) -> Self {
+        match policy {
+            SameSitePolicy::Strict => SameSite::Strict,
+            SameSitePolicy::Lax => SameSite::Lax,
+            SameSitePolicy::None => SameSite::None
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CookieConfigError {
+    /// `__Secure-` and `__Host-` cookies, as well as `SameSite=None` ones, are dropped by browsers unless `Secure`.
+    InsecurePrefix,
+    InsecureSameSiteNone,
+    /// `__Host-` cookies can't set a domain and must be scoped to `/`.
+    HostPrefixDomain,
+    HostPrefixPath
+}
+
+impl fmt::Display for CookieConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InsecurePrefix => write!(f, "cookies prefixed with __Secure- or __Host- must be secure"),
+            Self::InsecureSameSiteNone => write!(f, "cookies with SameSite=None must be secure"),
+            Self::HostPrefixDomain => write!(f, "cookies prefixed with __Host- can't set a domain"),
+            Self::HostPrefixPath => write!(f, "cookies prefixed with __Host- must use the path /")
+        }
+    }
+}
+
+impl std::error::Error for CookieConfigError {}
+
+/// Attributes shared by the cookie that is set on login and the one that clears it on logout,
+/// browsers only replace a cookie with the same name, domain and path.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CookieConfig {
+    pub name: String,
+    pub same_site: SameSitePolicy,
+    /// Empty keeps the cookie on the exact host, e.g. `agartex.com` shares it with every subdomain.
+    pub domain: String,
+    pub path: String,
+    pub secure: bool,
+    pub http_only: bool,
+    /// Persistent cookies outlive the browser session until the session expires.
+    pub persistent: bool
+}
+
+impl CookieConfig {
+    pub fn validate(&self) -> Result<(), CookieConfigError> {
+        let is_host = self.name.starts_with("__Host-");
+        if (is_host || self.name.starts_with("__Secure-")) && !self.secure {
+            return Err(CookieConfigError::InsecurePrefix);
+        }
+        if is_host && !self.domain.is_empty() {
+            return Err(CookieConfigError::HostPrefixDomain);
+        }
+        if is_host && self.path != "/" {
+            return Err(CookieConfigError::HostPrefixPath);
+        }
+        if self.same_site == SameSitePolicy::None && !self.secure {
+            return Err(CookieConfigError::InsecureSameSiteNone);
+        }
+
+        Ok(())
+    }
+
+    fn builder(&self, value: String) -> cookie::CookieBuilder<'static> {
+        let builder = Cookie::build(self.name.clone(), value)
+            .path(self.path.clone())
+            .same_site(self.same_site.into())
+            .secure(self.secure)
+            .http_only(self.http_only);
+
+        match self.domain.as_str() {
+            "" => builder,
+            domain => builder.domain(String::from(domain))
+        }
+    }
+
+    pub fn build(&self, value: String, expires: i64) -> Cookie<'static> {
+        let builder = self.builder(value);
+        if !self.persistent {
+            return builder.finish();
+        }
+        builder
+            .expires(OffsetDateTime::from_unix_timestamp(expires).unwrap())
+            .finish()
+    }
+
+    pub fn removal(&self, expiration: OffsetDateTime) -> Cookie<'static> {
+        self.builder(String::new())
+            .expires(expiration)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,104 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::bail;
+use axum_extra::extract::cookie::Cookie;
+use cookie::Key;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CookieProtection {
+    None,
+    /// HMAC-SHA256, the value stays readable but can't be altered.
+    Signed,
+    /// AES-256-GCM, the value is opaque and can't be altered.
+    Private
+}
+
+impl FromStr for CookieProtection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "signed" => Ok(Self::Signed),
+            "private" => Ok(Self::Private),
+            other => Err(format!("Unknown cookie protection: {}", other))
+        }
+    }
+}
+
+/// Signs or encrypts cookie values with the first key and accepts values sealed with any of them,
+/// so a new key can be put in front while cookies issued with the old ones stay valid.
+#[derive(Clone)]
+pub struct CookieSeal {
+    protection: CookieProtection,
+    keys: Vec<Key>
+}
+
+impl fmt::Debug for CookieSeal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CookieSeal")
+            .field("protection", &self.protection)
+            .field("keys", &self.keys.len())
+            .finish()
+    }
+}
+
+impl CookieSeal {
+    pub fn new(protection: CookieProtection, keys: Vec<Key>) -> anyhow::Result<Self> {
+        if protection != CookieProtection::None && keys.is_empty() {
+            bail!("{:?} cookies need at least one key", protection);
+        }
+        Ok(Self { protection, keys })
+    }
+
+    /// Parses a comma separated list of base64 encoded keys of at least 64 bytes each.
+    pub fn parse(protection: CookieProtection, keys: &str) -> anyhow::Result<Self> {
+        let keys = keys
+            .split(',')
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(|key| Ok(Key::try_from(STANDARD.decode(key)?.as_slice())?))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Self::new(protection, keys)
+    }
+
+    pub fn seal(&self, cookie: Cookie<'static>) -> Cookie<'static> {
+        let Some(key) = self.keys.first() else {
+            return cookie;
+        };
+
+        let name = cookie.name().to_owned();
+        let mut jar = cookie::CookieJar::new();
+        match self.protection {
+            CookieProtection::None => return cookie,
+            CookieProtection::Signed => jar.signed_mut(key).add(cookie),
+            CookieProtection::Private => jar.private_mut(key).add(cookie)
+        }
+        jar.get(&name).unwrap().clone()
+    }
+
+    /// Returns the cookie with its original value, or `None` when no key verifies it.
+    pub fn open(&self, cookie: &Cookie<'_>) -> Option<Cookie<'static>> {
+        let cookie = cookie.clone().into_owned();
+        if self.protection == CookieProtection::None {
+            return Some(cookie);
+        }
+
+        let jar = cookie::CookieJar::new();
+        let opened = self.keys
+            .iter()
+            .find_map(|key| match self.protection {
+                CookieProtection::Signed => jar.signed(key).verify(cookie.clone()),
+                CookieProtection::Private => jar.private(key).decrypt(cookie.clone()),
+                CookieProtection::None => None
+            });
+
+        if opened.is_none() {
+            warn!("Cookie {} failed verification", cookie.name());
+        }
+        opened
+    }
+}
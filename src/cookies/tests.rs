@@ -0,0 +1,152 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use cookie::Key;
+
+use super::*;
+
+fn mock_config() -> CookieConfig {
+    CookieConfig {
+        name: String::from("RSESSID"),
+        same_site: SameSitePolicy::Lax,
+        domain: String::new(),
+        path: String::from("/"),
+        secure: true,
+        http_only: true,
+        persistent: true
+    }
+}
+
+#[test]
+fn same_site_from_str() {
+    assert_eq!(Ok(SameSitePolicy::Strict), SameSitePolicy::from_str("Strict"));
+    assert_eq!(Ok(SameSitePolicy::None), SameSitePolicy::from_str("none"));
+    assert!(SameSitePolicy::from_str("always").is_err());
+}
+
+#[test]
+fn validate_normal() {
+    assert_eq!(Ok(()), mock_config().validate());
+    assert_eq!(Ok(()), CookieConfig { secure: false, ..mock_config() }.validate());
+}
+
+#[test]
+fn validate_secure_prefix() {
+    let config = CookieConfig { name: String::from("__Secure-RSESSID"), domain: String::from("agartex.com"), ..mock_config() };
+    assert_eq!(Ok(()), config.validate());
+    assert_eq!(Err(CookieConfigError::InsecurePrefix), CookieConfig { secure: false, ..config }.validate());
+}
+
+#[test]
+fn validate_host_prefix() {
+    let config = CookieConfig { name: String::from("__Host-RSESSID"), ..mock_config() };
+    assert_eq!(Ok(()), config.validate());
+    assert_eq!(Err(CookieConfigError::InsecurePrefix), CookieConfig { secure: false, ..config.clone() }.validate());
+    assert_eq!(Err(CookieConfigError::HostPrefixDomain), CookieConfig { domain: String::from("agartex.com"), ..config.clone() }.validate());
+    assert_eq!(Err(CookieConfigError::HostPrefixPath), CookieConfig { path: String::from("/sessions"), ..config }.validate());
+}
+
+#[test]
+fn validate_same_site_none() {
+    let config = CookieConfig { same_site: SameSitePolicy::None, ..mock_config() };
+    assert_eq!(Ok(()), config.validate());
+    assert_eq!(Err(CookieConfigError::InsecureSameSiteNone), CookieConfig { secure: false, ..config }.validate());
+}
+
+#[test]
+fn build_persistent() {
+    let config = CookieConfig { domain: String::from("agartex.com"), same_site: SameSitePolicy::Strict, ..mock_config() };
+    let cookie = config.build(String::from("value"), 1000);
+
+    assert_eq!("value", cookie.value());
+    assert_eq!(Some("agartex.com"), cookie.domain());
+    assert_eq!(Some("/"), cookie.path());
+    assert_eq!(Some(SameSite::Strict), cookie.same_site());
+    assert_eq!(Some(true), cookie.secure());
+    assert_eq!(Some(true), cookie.http_only());
+    assert_eq!(1000, cookie.expires().unwrap().datetime().unwrap().unix_timestamp());
+}
+
+#[test]
+fn build_session() {
+    let cookie = CookieConfig { persistent: false, ..mock_config() }.build(String::from("value"), 1000);
+
+    assert_eq!(None, cookie.expires());
+    assert_eq!(None, cookie.domain());
+}
+
+#[test]
+fn removal_keeps_attributes() {
+    let config = CookieConfig { domain: String::from("agartex.com"), path: String::from("/auth"), persistent: false, ..mock_config() };
+    let cookie = config.removal(OffsetDateTime::UNIX_EPOCH);
+
+    assert_eq!("", cookie.value());
+    assert_eq!(Some("agartex.com"), cookie.domain());
+    assert_eq!(Some("/auth"), cookie.path());
+    assert_eq!(Some(true), cookie.secure());
+    assert_eq!(0, cookie.expires().unwrap().datetime().unwrap().unix_timestamp());
+}
+
+fn mock_key() -> Key {
+    Key::generate()
+}
+
+fn mock_session_cookie() -> Cookie<'static> {
+    mock_config().build(String::from("1").repeat(64), 1000)
+}
+
+#[test]
+fn seal_parse() {
+    let encoded = STANDARD.encode(mock_key().master());
+    let seal = CookieSeal::parse(CookieProtection::Signed, &format!("{}, {}", encoded, encoded)).unwrap();
+    assert!(format!("{:?}", seal).contains("keys: 2"));
+
+    assert!(CookieSeal::parse(CookieProtection::Signed, "").is_err());
+    assert!(CookieSeal::parse(CookieProtection::Private, &STANDARD.encode([0; 16])).is_err());
+    assert!(CookieSeal::parse(CookieProtection::None, "").is_ok());
+}
+
+#[test]
+fn seal_none() {
+    let seal = CookieSeal::new(CookieProtection::None, Vec::new()).unwrap();
+    let sealed = seal.seal(mock_session_cookie());
+
+    assert_eq!(mock_session_cookie().value(), sealed.value());
+    assert_eq!(Some(mock_session_cookie()), seal.open(&sealed));
+}
+
+#[test]
+fn seal_signed() {
+    let seal = CookieSeal::new(CookieProtection::Signed, vec![mock_key()]).unwrap();
+    let sealed = seal.seal(mock_session_cookie());
+
+    assert!(sealed.value().ends_with(mock_session_cookie().value()));
+    assert_eq!(Some(true), sealed.http_only());
+    assert_eq!(mock_session_cookie().value(), seal.open(&sealed).unwrap().value());
+
+    let tampered = Cookie::new(sealed.name().to_owned(), sealed.value().replace('1', "2"));
+    assert_eq!(None, seal.open(&tampered));
+    assert_eq!(None, seal.open(&mock_session_cookie()));
+}
+
+#[test]
+fn seal_private() {
+    let seal = CookieSeal::new(CookieProtection::Private, vec![mock_key()]).unwrap();
+    let sealed = seal.seal(mock_session_cookie());
+
+    assert!(!sealed.value().contains(mock_session_cookie().value()));
+    assert_eq!(mock_session_cookie().value(), seal.open(&sealed).unwrap().value());
+    assert_eq!(None, seal.open(&Cookie::new(sealed.name().to_owned(), "garbage")));
+}
+
+#[test]
+fn seal_rotation() {
+    let (old_key, new_key) = (mock_key(), mock_key());
+    let old_seal = CookieSeal::new(CookieProtection::Private, vec![old_key.clone()]).unwrap();
+    let seal = CookieSeal::new(CookieProtection::Private, vec![new_key.clone(), old_key]).unwrap();
+
+    let sealed = old_seal.seal(mock_session_cookie());
+    assert_eq!(mock_session_cookie().value(), seal.open(&sealed).unwrap().value());
+
+    let sealed = seal.seal(mock_session_cookie());
+    assert_eq!(None, old_seal.open(&sealed));
+    assert!(CookieSeal::new(CookieProtection::Private, vec![new_key]).unwrap().open(&sealed).is_some());
+}
@@ -0,0 +1,95 @@
+use std::{str::FromStr, time::Duration};
+
+use http::{HeaderName, HeaderValue, Method, header};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::constants::{CORS_ALLOWED_ORIGINS, CORS_MAX_AGE_SECONDS, CSRF_HEADER, USER_ID_HEADER, TOKEN_SCOPES_HEADER};
+
+/// Origin allowed to call the service from a browser, either exact or any subdomain of a domain.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OriginPattern {
+    /// e.g. `https://agartex.com`
+    Exact(String),
+    /// e.g. `https://*.agartex.com`, which doesn't match `https://agartex.com` itself.
+    Subdomain { scheme: String, suffix: String }
+}
+
+impl FromStr for OriginPattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let pattern = s.trim().trim_end_matches('/').to_lowercase();
+        let Some((scheme, host)) = pattern.split_once("://") else {
+            return Err(format!("Origin must include a scheme: {}", s));
+        };
+
+        match host.strip_prefix('*') {
+            Some(suffix) if suffix.starts_with('.') && !suffix.contains('*') => Ok(Self::Subdomain {
+                scheme: String::from(scheme),
+                suffix: String::from(suffix)
+            }),
+            Some(_) => Err(format!("Wildcards are only allowed as the leftmost label: {}", s)),
+            None if host.contains('*') => Err(format!("Wildcards are only allowed as the leftmost label: {}", s)),
+            None => Ok(Self::Exact(pattern))
+        }
+    }
+}
+
+impl OriginPattern {
+    pub fn matches(&self, origin: &str) -> bool {
+        let origin = origin.to_lowercase();
+        match self {
+            Self::Exact(exact) => *exact == origin,
+            Self::Subdomain { scheme, suffix } => origin
+                .strip_prefix(scheme.as_str())
+                .and_then(|rest| rest.strip_prefix("://"))
+                .and_then(|host| host.strip_suffix(suffix.as_str()))
+                .is_some_and(|subdomain| !subdomain.is_empty() && subdomain.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.'))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CorsConfig {
+    pub allowed_origins: Vec<OriginPattern>,
+    /// How long browsers may cache the result of a preflight request.
+    pub max_age: Duration
+}
+
+impl CorsConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let allowed_origins = CORS_ALLOWED_ORIGINS
+            .split(',')
+            .filter(|origin| !origin.trim().is_empty())
+            .map(OriginPattern::from_str)
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            allowed_origins,
+            max_age: Duration::from_secs(*CORS_MAX_AGE_SECONDS)
+        })
+    }
+
+    pub fn is_allowed(&self, origin: &HeaderValue) -> bool {
+        origin
+            .to_str()
+            .is_ok_and(|origin| self.allowed_origins.iter().any(|pattern| pattern.matches(origin)))
+    }
+
+    /// Origins are echoed back one by one since credentialed requests can't use `*`.
+    pub fn layer(self) -> CorsLayer {
+        let header_name = |name: &str| HeaderName::from_str(name).unwrap();
+        let max_age = self.max_age;
+
+        CorsLayer::new()
+            .allow_origin(AllowOrigin::predicate(move |origin, _| self.is_allowed(origin)))
+            .allow_credentials(true)
+            .allow_methods([Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE])
+            .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION, header_name(&CSRF_HEADER), header_name(&USER_ID_HEADER)])
+            .expose_headers([header_name(&USER_ID_HEADER), header_name(&TOKEN_SCOPES_HEADER)])
+            .max_age(max_age)
+    }
+}
+
+#[cfg(test)]
+mod tests;
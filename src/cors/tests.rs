@@ -0,0 +1,47 @@
+use super::*;
+
+fn mock_config() -> CorsConfig {
+    CorsConfig {
+        allowed_origins: vec![
+            OriginPattern::from_str("https://agartex.com").unwrap(),
+            OriginPattern::from_str("https://*.agartex.com").unwrap()
+        ],
+        max_age: Duration::from_secs(600)
+    }
+}
+
+#[test]
+fn origin_pattern_from_str() {
+    assert_eq!(Ok(OriginPattern::Exact(String::from("https://agartex.com"))), OriginPattern::from_str(" https://AgarTeX.com/ "));
+    assert_eq!(
+        Ok(OriginPattern::Subdomain { scheme: String::from("https"), suffix: String::from(".agartex.com") }),
+        OriginPattern::from_str("https://*.agartex.com")
+    );
+    assert!(OriginPattern::from_str("agartex.com").is_err());
+    assert!(OriginPattern::from_str("https://*agartex.com").is_err());
+    assert!(OriginPattern::from_str("https://app.*.agartex.com").is_err());
+}
+
+#[test]
+fn origin_pattern_matches() {
+    let subdomain = OriginPattern::from_str("https://*.agartex.com").unwrap();
+    assert!(subdomain.matches("https://app.agartex.com"));
+    assert!(subdomain.matches("https://eu.app.agartex.com"));
+    assert!(!subdomain.matches("https://agartex.com"));
+    assert!(!subdomain.matches("http://app.agartex.com"));
+    assert!(!subdomain.matches("https://evil-agartex.com"));
+    assert!(!subdomain.matches("https://evil.com/.agartex.com"));
+
+    let exact = OriginPattern::from_str("http://localhost:3000").unwrap();
+    assert!(exact.matches("http://localhost:3000"));
+    assert!(!exact.matches("http://localhost:3001"));
+}
+
+#[test]
+fn is_allowed() {
+    let config = mock_config();
+    assert!(config.is_allowed(&HeaderValue::from_static("https://agartex.com")));
+    assert!(config.is_allowed(&HeaderValue::from_static("https://app.agartex.com")));
+    assert!(!config.is_allowed(&HeaderValue::from_static("https://evil.com")));
+    assert!(!config.is_allowed(&HeaderValue::from_static("null")));
+}
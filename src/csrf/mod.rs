@@ -0,0 +1,134 @@
+use std::sync::Arc;
+
+use axum::{extract::State, middleware::Next, response::Response};
+use axum_extra::extract::{CookieJar, cookie::Cookie};
+use http::{HeaderMap, Method, Request, StatusCode, Uri, header};
+use tracing::warn;
+
+use crate::{constants::{CSRF_COOKIE, CSRF_COOKIE_NAME, CSRF_HEADER, CSRF_TOKEN_LENGTH, CSRF_TRUSTED_ORIGINS, CSRF_EXEMPT_BEARER, SESSION_COOKIE_NAME, SESSION_TOKEN_SOURCES}, extract::TokenSource, service::random_alphanumeric};
+
+/// Double-submit token issued next to the session cookie, scripts of the frontend echo it back in `CSRF_HEADER`.
+pub fn generate_csrf_token() -> String {
+    random_alphanumeric(CSRF_TOKEN_LENGTH)
+}
+
+/// Unlike the session cookie this one is readable by scripts, it grants nothing on its own.
+pub fn csrf_cookie(token: String, expires: i64) -> Cookie<'static> {
+    CSRF_COOKIE.build(token, expires)
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CsrfConfig {
+    /// Origins such as `https://agartex.com` allowed to send unsafe requests, empty skips the Origin/Referer check.
+    pub trusted_origins: Vec<String>,
+    /// Requests authenticated with an Authorization header can't be forged by a browser and skip every check.
+    pub exempt_bearer: bool
+}
+
+impl CsrfConfig {
+    pub fn from_env() -> Self {
+        Self {
+            trusted_origins: CSRF_TRUSTED_ORIGINS
+                .split(',')
+                .map(normalize_origin)
+                .filter(|origin| !origin.is_empty())
+                .collect(),
+            exempt_bearer: *CSRF_EXEMPT_BEARER
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CsrfError {
+    UntrustedOrigin(String),
+    MissingToken,
+    TokenMismatch
+}
+
+fn normalize_origin(origin: &str) -> String {
+    origin.trim().trim_end_matches('/').to_lowercase()
+}
+
+fn is_safe(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE)
+}
+
+/// Whether the credential picked by `extract::Credential` comes from the Authorization header.
+fn is_bearer_authenticated(headers: &HeaderMap, jar: &CookieJar) -> bool {
+    let has_bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("Bearer "));
+
+    SESSION_TOKEN_SOURCES.0
+        .iter()
+        .find(|source| match source {
+            TokenSource::Cookie => jar.get(SESSION_COOKIE_NAME.as_str()).is_some(),
+            TokenSource::Bearer => has_bearer
+        })
+        == Some(&TokenSource::Bearer)
+}
+
+/// Origin of the request, falling back to the Referer as some browsers omit Origin on same-origin requests.
+fn request_origin(headers: &HeaderMap) -> Option<String> {
+    if let Some(origin) = headers.get(header::ORIGIN) {
+        return Some(normalize_origin(origin.to_str().unwrap_or_default()));
+    }
+
+    let referer = headers.get(header::REFERER)?.to_str().ok()?.parse::<Uri>().ok();
+    match referer.as_ref().and_then(|uri| Some((uri.scheme_str()?, uri.authority()?))) {
+        Some((scheme, authority)) => Some(normalize_origin(&format!("{}://{}", scheme, authority))),
+        None => Some(String::new())
+    }
+}
+
+pub fn check(config: &CsrfConfig, method: &Method, headers: &HeaderMap) -> Result<(), CsrfError> {
+    if is_safe(method) {
+        return Ok(());
+    }
+
+    let jar = CookieJar::from_headers(headers);
+    if config.exempt_bearer && is_bearer_authenticated(headers, &jar) {
+        return Ok(());
+    }
+
+    if !config.trusted_origins.is_empty() {
+        // requests stripped of both headers by privacy settings still have to pass the token check
+        if let Some(origin) = request_origin(headers) {
+            if !config.trusted_origins.contains(&origin) {
+                return Err(CsrfError::UntrustedOrigin(origin));
+            }
+        }
+    }
+
+    // without the session cookie there is no ambient authority to abuse
+    if jar.get(SESSION_COOKIE_NAME.as_str()).is_none() {
+        return Ok(());
+    }
+
+    let cookie = jar.get(CSRF_COOKIE_NAME.as_str()).map(Cookie::value);
+    let header = headers.get(CSRF_HEADER.as_str()).map(|value| value.as_bytes());
+    match (cookie, header) {
+        (Some(cookie), Some(header)) if !cookie.is_empty() => {
+            ring::constant_time::verify_slices_are_equal(cookie.as_bytes(), header).map_err(|_| CsrfError::TokenMismatch)
+        },
+        _ => Err(CsrfError::MissingToken)
+    }
+}
+
+/// Rejects unsafe cookie-authenticated requests that fail `check` with 403.
+pub async fn csrf_protection<B>(
+    State(config): State<Arc<CsrfConfig>>,
+    request: Request<B>,
+    next: Next<B>
+) -> Result<Response, StatusCode> {
+    if let Err(err) = check(&config, request.method(), request.headers()) {
+        warn!("Rejected possible cross-site request to {} {}: {:?}", request.method(), request.uri(), err);
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests;
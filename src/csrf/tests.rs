@@ -0,0 +1,106 @@
+use http::HeaderValue;
+
+use crate::constants::SESSION_ID_LENGTH;
+
+use super::*;
+
+fn mock_session_id() -> String {
+    "1".repeat(SESSION_ID_LENGTH)
+}
+
+fn mock_config() -> CsrfConfig {
+    CsrfConfig {
+        trusted_origins: vec![String::from("https://agartex.com")],
+        exempt_bearer: true
+    }
+}
+
+fn mock_headers(csrf_cookie: Option<&str>, csrf_header: Option<&str>) -> HeaderMap {
+    let mut cookies = vec![format!("{}={}", SESSION_COOKIE_NAME.as_str(), mock_session_id())];
+    if let Some(token) = csrf_cookie {
+        cookies.push(format!("{}={}", CSRF_COOKIE_NAME.as_str(), token));
+    }
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::COOKIE, HeaderValue::from_str(&cookies.join("; ")).unwrap());
+    if let Some(token) = csrf_header {
+        headers.insert(CSRF_HEADER.as_str(), HeaderValue::from_str(token).unwrap());
+    }
+    headers
+}
+
+#[test]
+fn generate_csrf_token_length() {
+    let token = generate_csrf_token();
+    assert_eq!(CSRF_TOKEN_LENGTH, token.len());
+    assert!(token.chars().all(|c| c.is_ascii_alphanumeric()));
+}
+
+#[test]
+fn check_matching_token() {
+    assert_eq!(Ok(()), check(&mock_config(), &Method::DELETE, &mock_headers(Some("token"), Some("token"))));
+}
+
+#[test]
+fn check_missing_token() {
+    assert_eq!(Err(CsrfError::MissingToken), check(&mock_config(), &Method::DELETE, &mock_headers(Some("token"), None)));
+    assert_eq!(Err(CsrfError::MissingToken), check(&mock_config(), &Method::DELETE, &mock_headers(None, Some("token"))));
+}
+
+#[test]
+fn check_token_mismatch() {
+    assert_eq!(Err(CsrfError::TokenMismatch), check(&mock_config(), &Method::POST, &mock_headers(Some("token"), Some("other"))));
+}
+
+#[test]
+fn check_safe_method() {
+    assert_eq!(Ok(()), check(&mock_config(), &Method::GET, &mock_headers(None, None)));
+}
+
+#[test]
+fn check_without_session_cookie() {
+    assert_eq!(Ok(()), check(&mock_config(), &Method::POST, &HeaderMap::new()));
+}
+
+#[test]
+fn check_trusted_origin() {
+    let mut headers = mock_headers(Some("token"), Some("token"));
+    headers.insert(header::ORIGIN, HeaderValue::from_static("https://AgarTeX.com"));
+    assert_eq!(Ok(()), check(&mock_config(), &Method::DELETE, &headers));
+}
+
+#[test]
+fn check_untrusted_origin() {
+    let mut headers = mock_headers(Some("token"), Some("token"));
+    headers.insert(header::ORIGIN, HeaderValue::from_static("https://evil.com"));
+    assert_eq!(Err(CsrfError::UntrustedOrigin(String::from("https://evil.com"))), check(&mock_config(), &Method::DELETE, &headers));
+}
+
+#[test]
+fn check_untrusted_referer() {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::REFERER, HeaderValue::from_static("https://evil.com/login?next=/"));
+    assert_eq!(Err(CsrfError::UntrustedOrigin(String::from("https://evil.com"))), check(&mock_config(), &Method::POST, &headers));
+
+    headers.insert(header::REFERER, HeaderValue::from_static("https://agartex.com/login"));
+    assert_eq!(Ok(()), check(&mock_config(), &Method::POST, &headers));
+}
+
+#[test]
+fn check_bearer_exempt() {
+    let mut headers = HeaderMap::new();
+    headers.insert(header::AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {}", mock_session_id())).unwrap());
+    headers.insert(header::ORIGIN, HeaderValue::from_static("https://evil.com"));
+    assert_eq!(Ok(()), check(&mock_config(), &Method::DELETE, &headers));
+
+    let config = CsrfConfig { exempt_bearer: false, ..mock_config() };
+    assert!(check(&config, &Method::DELETE, &headers).is_err());
+}
+
+#[test]
+fn check_bearer_behind_cookie() {
+    // the cookie takes precedence by default, so the bearer header must not lift the checks
+    let mut headers = mock_headers(None, None);
+    headers.insert(header::AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {}", mock_session_id())).unwrap());
+    assert_eq!(Err(CsrfError::MissingToken), check(&mock_config(), &Method::DELETE, &headers));
+}
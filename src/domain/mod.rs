@@ -1,2 +1,4 @@
 pub mod users;
 pub mod sessions;
+pub mod tokens;
+pub mod personal_tokens;
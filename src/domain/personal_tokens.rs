@@ -0,0 +1,47 @@
+use serde::{Serialize, Deserialize};
+use validator::{Validate, ValidationError};
+
+use crate::constants::PERSONAL_TOKEN_SCOPES;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PersonalToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub expires: Option<i64>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PersonalTokenData {
+    pub user_id: i32,
+    pub name: String,
+    pub token_hash: String,
+    pub scopes: Vec<String>,
+    pub expires: Option<i64>
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Validate)]
+pub struct PersonalTokenRequest {
+    #[validate(length(min = 1, max = 64))]
+    pub name: String,
+    #[validate(length(min = 1), custom = "known_scopes")]
+    pub scopes: Vec<String>,
+    #[validate(range(min = 1, max = 365))]
+    pub expires_in_days: Option<i64>
+}
+
+fn known_scopes(scopes: &[String]) -> Result<(), ValidationError> {
+    if !scopes.iter().all(|scope| PERSONAL_TOKEN_SCOPES.contains(&scope.as_str())) {
+        return Err(ValidationError::new("unknown_scope"));
+    }
+    Ok(())
+}
+
+/// Returned only once, right after creation, as the plaintext token is never stored.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CreatedPersonalToken {
+    pub token: String,
+    #[serde(flatten)]
+    pub details: PersonalToken
+}
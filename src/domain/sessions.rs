@@ -1,3 +1,5 @@
+use std::net::IpAddr;
+
 use serde::{Serialize, Deserialize};
 use validator::{Validate, validate_length, ValidationErrors, ValidationError};
 
@@ -9,15 +11,75 @@ use super::users::User;
 pub struct Session {
     pub id: String,
     pub user: User,
-    pub expires: i64
+    pub expires: i64,
+    /// When the user last proved who they are, at login or through re-authentication.
+    #[serde(default)]
+    pub authenticated_at: i64,
+    #[serde(default, flatten)]
+    pub binding: SessionBinding
 }
 
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SessionData {
     pub id: String,
     pub user_id: i32,
-    pub expires: i64
+    pub expires: i64,
+    #[serde(default)]
+    pub authenticated_at: i64,
+    #[serde(default, flatten)]
+    pub binding: SessionBinding
+}
+
+/// Client properties recorded at login that later requests with the session have to match,
+/// `None` when the binding policy ignores them.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct SessionBinding {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_agent_family: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip_subnet: Option<String>
+}
+
+/// The client a request comes from, extracted from the request headers and peer address.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip: Option<IpAddr>
+}
+
+/// Returned on login, `token` and `expires` are only present when the session is returned in the body,
+/// `csrf_token` only when it is set in a cookie.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PubSessionData {
+    pub user_id: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub csrf_token: Option<String>
+}
+
+/// Body of `POST /sessions/reauthenticate`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Reauthentication {
+    pub password: String
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionTransport {
+    #[default]
+    Cookie,
+    Body,
+    Both
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+pub struct LoginOptions {
+    #[serde(default)]
+    pub transport: SessionTransport
 }
 
 pub struct SessionId(pub String);
@@ -39,3 +101,14 @@ impl Validate for SessionId {
         }
     }
 }
+
+/// Tells other instances to forget a revoked session, or every session of a user.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Revocation {
+    Session { id: String },
+    User { user_id: i32 },
+    /// Raised locally when revocations may have been missed, never sent between instances.
+    #[serde(skip)]
+    All
+}
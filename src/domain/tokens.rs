@@ -0,0 +1,64 @@
+use serde::{Serialize, Deserialize};
+use validator::{Validate, validate_length, ValidationErrors, ValidationError};
+
+use crate::constants::REFRESH_TOKEN_LENGTH;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccessClaims {
+    pub sub: String,
+    /// `session_handle` of the session the token was issued for, never the session ID itself.
+    pub sid: String,
+    pub iat: i64,
+    pub exp: i64
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AccessToken {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RefreshToken {
+    pub id: String,
+    pub family_id: String,
+    pub user_id: i32,
+    pub session_id: String,
+    pub expires: i64,
+    pub used: bool
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RefreshTokenData {
+    pub id: String,
+    pub family_id: String,
+    pub user_id: i32,
+    pub session_id: String,
+    pub expires: i64
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String
+}
+
+impl Validate for RefreshRequest {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errs = ValidationErrors::new();
+        if !validate_length(&self.refresh_token, None, None, Some(REFRESH_TOKEN_LENGTH as u64)) {
+            errs.add("refresh_token", ValidationError::new("length"));
+        }
+        if !self.refresh_token.chars().all(|c| c.is_ascii_alphanumeric()) {
+            errs.add("refresh_token", ValidationError::new("charset"));
+        }
+
+        if errs.is_empty() {
+            Ok(())
+        } else {
+            Err(errs)
+        }
+    }
+}
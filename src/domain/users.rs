@@ -1,56 +1,60 @@
 use serde::{Deserialize, Serialize};
-use validator::{Validate, ValidationError};
+use validator::{ValidationError, ValidationErrors, validate_email};
 
-use crate::constants::{PASSWORD_SPECIAL_CHARS, PASSWORD_REGEX};
+use crate::passwords::{BreachedPasswords, validate_password, email_inputs};
 
-#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct User {
     pub id: i32,
     pub email: String,
     pub password_hash: String
 }
 
-#[derive(Debug, Deserialize, Validate, PartialEq)]
+#[derive(Debug, Deserialize, PartialEq)]
 pub struct Credentials {
-    #[validate(email)]
     pub email: String,
-    #[validate(
-        length(min = 8, max = 32), 
-        regex = "PASSWORD_REGEX", 
-        custom = "contains_uppercase",
-        custom = "contains_lowercase",
-        custom = "contains_digit",
-        custom = "contains_special"
-    )]
     pub password: String
 }
 
-fn contains_uppercase(password: &str) -> Result<(), ValidationError> {
-    if !password.chars().any(|c| c.is_ascii_uppercase()) {
-        return Err(ValidationError::new("lowercase_character"))
-    }
-    Ok(())
-}
+impl Credentials {
+    /// Checked by hand rather than on extraction, screening the password reads the breached password list.
+    pub async fn validate_registration(&self, breached: &BreachedPasswords) -> Result<(), ValidationErrors> {
+        let mut errs = ValidationErrors::new();
+        if !validate_email(&self.email) {
+            errs.add("email", ValidationError::new("email"));
+        }
+        for err in validate_password(breached, &self.password, &email_inputs(&self.email)).await {
+            errs.add("password", err);
+        }
 
-fn contains_lowercase(password: &str) -> Result<(), ValidationError> {
-    if !password.chars().any(|c| c.is_ascii_lowercase()) {
-        return Err(ValidationError::new("uppercase_character"))
+        if errs.is_empty() {
+            Ok(())
+        } else {
+            Err(errs)
+        }
     }
-    Ok(())
 }
 
-fn contains_digit(password: &str) -> Result<(), ValidationError> {
-    if !password.chars().any(|c| c.is_ascii_digit()) {
-        return Err(ValidationError::new("digit_character"))
-    } 
-    Ok(())
+/// Not validated on extraction, the new password is checked against the email of the logged in user.
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct PasswordChange {
+    pub current_password: String,
+    pub new_password: String
 }
 
-fn contains_special(password: &str) -> Result<(), ValidationError> {
-    if !password.chars().any(|c| PASSWORD_SPECIAL_CHARS.contains(c)) {
-        return Err(ValidationError::new("special_character"))
+impl PasswordChange {
+    pub async fn validate_for(&self, user: &User, breached: &BreachedPasswords) -> Result<(), ValidationErrors> {
+        let mut errs = ValidationErrors::new();
+        for err in validate_password(breached, &self.new_password, &email_inputs(&user.email)).await {
+            errs.add("new_password", err);
+        }
+
+        if errs.is_empty() {
+            Ok(())
+        } else {
+            Err(errs)
+        }
     }
-    Ok(())
 }
 
 #[derive(Debug, Serialize, PartialEq)]
@@ -58,8 +62,3 @@ pub struct UserData {
     pub email: String,
     pub password_hash: String
 }
-
-#[derive(Debug, Serialize, PartialEq)]
-pub struct PubUserData {
-    pub user_id: i32,
-}
@@ -0,0 +1,277 @@
+use std::{convert::Infallible, marker::PhantomData, net::{IpAddr, SocketAddr}, str::FromStr};
+
+use axum::{async_trait, extract::{FromRequestParts, ConnectInfo}, headers::{Header, Error, Authorization, authorization::Bearer}, response::{IntoResponse, Response}, Extension, Json, TypedHeader};
+use axum_extra::extract::CookieJar;
+use http::{HeaderName, HeaderValue, StatusCode, request::Parts, header};
+use serde_json::json;
+use tracing::{error, warn};
+use validator::Validate;
+
+use crate::{constants::{USER_HEADER_NAME, TOKEN_SCOPES_HEADER_NAME, SESSION_COOKIE_NAME, SESSION_COOKIE_SEAL, SESSION_TOKEN_SOURCES, CLIENT_IP_HEADER, REAUTH_MAX_AGE_SECONDS}, domain::{sessions::{SessionId, ClientInfo}, users::User}, service::{personal_tokens::is_personal_token, sessions::{SessionService, SessionVerifyError}}};
+
+pub struct XUserId(pub i32);
+
+impl Header for XUserId {
+    fn name() -> &'static HeaderName {
+        &USER_HEADER_NAME
+    }
+
+    fn decode<'i, I>(values: &mut I) -> Result<Self, Error>
+    where
+        I: Iterator<Item = &'i HeaderValue>,
+    {
+        if let Ok(s) = values.next().ok_or_else(Error::invalid)?.to_str() {
+            if let Ok(num) = s.parse::<i32>() {
+                return Ok(XUserId(num));
+            }
+        }
+        Err(Error::invalid())
+    }
+
+    fn encode<E>(&self, values: &mut E)
+    where
+        E: Extend<HeaderValue>,
+    {
+        let value = HeaderValue::from(self.0);
+
+        values.extend(std::iter::once(value));
+    }
+}
+
+pub struct XTokenScopes(pub Vec<String>);
+
+impl Header for XTokenScopes {
+    fn name() -> &'static HeaderName {
+        &TOKEN_SCOPES_HEADER_NAME
+    }
+
+    fn decode<'i, I>(values: &mut I) -> Result<Self, Error>
+    where
+        I: Iterator<Item = &'i HeaderValue>,
+    {
+        if let Ok(s) = values.next().ok_or_else(Error::invalid)?.to_str() {
+            return Ok(XTokenScopes(s.split(',').filter(|scope| !scope.is_empty()).map(String::from).collect()));
+        }
+        Err(Error::invalid())
+    }
+
+    fn encode<E>(&self, values: &mut E)
+    where
+        E: Extend<HeaderValue>,
+    {
+        if let Ok(value) = HeaderValue::from_str(&self.0.join(",")) {
+            values.extend(std::iter::once(value));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenSource {
+    Cookie,
+    Bearer
+}
+
+/// Order in which token sources are consulted, the first one present wins.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenSources(pub Vec<TokenSource>);
+
+impl FromStr for TokenSources {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(str::trim)
+            .map(|source| match source {
+                "cookie" => Ok(TokenSource::Cookie),
+                "bearer" => Ok(TokenSource::Bearer),
+                other => Err(format!("Unknown token source: {}", other))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(TokenSources)
+    }
+}
+
+/// Credential presented by the client, either in the session cookie or in the Authorization header.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Credential {
+    Session(String),
+    PersonalToken(String)
+}
+
+impl Credential {
+    pub async fn from_sources(parts: &mut Parts, sources: &[TokenSource]) -> Result<Self, StatusCode> {
+        let mut token = None;
+        for source in sources {
+            token = match source {
+                // tampered cookies are dropped here, before any repository is asked about them
+                TokenSource::Cookie => CookieJar::from_headers(&parts.headers)
+                    .get(SESSION_COOKIE_NAME.as_str())
+                    .and_then(|cookie| SESSION_COOKIE_SEAL.open(cookie))
+                    .map(|cookie| String::from(cookie.value())),
+                TokenSource::Bearer => TypedHeader::<Authorization<Bearer>>::from_request_parts(parts, &())
+                    .await
+                    .ok()
+                    .map(|TypedHeader(Authorization(bearer))| String::from(bearer.token()))
+            };
+            if token.is_some() {
+                break;
+            }
+        }
+
+        let token = match token {
+            Some(token) => token,
+            None => {
+                warn!("No session provided!");
+                return Err(StatusCode::UNAUTHORIZED);
+            }
+        };
+
+        if is_personal_token(&token) {
+            return Ok(Self::PersonalToken(token));
+        }
+
+        if let Err(errs) = SessionId(token.clone()).validate() {
+            warn!("Session ID: {}, Validation errors: {}", token, errs);
+            return Err(StatusCode::UNPROCESSABLE_ENTITY);
+        }
+
+        Ok(Self::Session(token))
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Credential
+where
+    S: Send + Sync
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Self::from_sources(parts, &SESSION_TOKEN_SOURCES.0).await
+    }
+}
+
+/// Validated session ID, for endpoints that do not accept personal tokens.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionToken(pub String);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for SessionToken
+where
+    S: Send + Sync
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        match Credential::from_request_parts(parts, state).await? {
+            Credential::Session(id) => Ok(Self(id)),
+            Credential::PersonalToken(_) => {
+                warn!("Personal token provided where a session is required");
+                Err(StatusCode::UNAUTHORIZED)
+            }
+        }
+    }
+}
+
+impl ClientInfo {
+    /// Behind a reverse proxy the peer is the proxy, so the client address is taken from the last entry
+    /// of `ip_header`, the one appended by the proxy itself.
+    pub fn from_parts(parts: &Parts, ip_header: &str) -> Self {
+        let user_agent = parts.headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from);
+
+        let ip = match ip_header {
+            "" => parts.extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip()),
+            ip_header => parts.headers
+                .get_all(ip_header)
+                .iter()
+                .next_back()
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.rsplit(',').next())
+                .and_then(|ip| IpAddr::from_str(ip.trim()).ok())
+        };
+
+        Self { user_agent, ip }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    S: Send + Sync
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_parts(parts, CLIENT_IP_HEADER.as_str()))
+    }
+}
+
+/// Turns away requests whose session isn't allowed to perform sensitive actions.
+#[derive(Debug, PartialEq)]
+pub enum RecentAuthRejection {
+    Status(StatusCode),
+    /// The session is valid but the user has to confirm their password at `POST /sessions/reauthenticate` first.
+    ReauthenticationRequired { max_age: i64 }
+}
+
+impl IntoResponse for RecentAuthRejection {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Status(code) => code.into_response(),
+            Self::ReauthenticationRequired { max_age } => (
+                StatusCode::FORBIDDEN,
+                Json(json!({ "error": "reauthentication_required", "max_age": max_age }))
+            ).into_response()
+        }
+    }
+}
+
+/// Guard for sensitive handlers, resolves the user of a session that logged in or re-authenticated
+/// within the last `REAUTH_MAX_AGE_SECONDS`. `S` is the session service mounted as an extension.
+pub struct RecentAuth<S>(pub User, pub PhantomData<S>);
+
+#[async_trait]
+impl<S, T> FromRequestParts<T> for RecentAuth<S>
+where
+    S: SessionService + Clone + Send + Sync + 'static,
+    T: Send + Sync
+{
+    type Rejection = RecentAuthRejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &T) -> Result<Self, Self::Rejection> {
+        let Extension(service) = Extension::<S>::from_request_parts(parts, state)
+            .await
+            .map_err(|err| {
+                error!(%err);
+                RecentAuthRejection::Status(StatusCode::INTERNAL_SERVER_ERROR)
+            })?;
+        let SessionToken(session_id) = SessionToken::from_request_parts(parts, state)
+            .await
+            .map_err(RecentAuthRejection::Status)?;
+        let client = ClientInfo::from_parts(parts, CLIENT_IP_HEADER.as_str());
+
+        match service.verify_recent(&session_id, &client, *REAUTH_MAX_AGE_SECONDS).await {
+            Ok(user) => Ok(Self(user, PhantomData)),
+            Err(SessionVerifyError::StaleAuthentication) => {
+                warn!("Session needs to be re-authenticated for a sensitive action");
+                Err(RecentAuthRejection::ReauthenticationRequired { max_age: *REAUTH_MAX_AGE_SECONDS })
+            },
+            Err(SessionVerifyError::Missing | SessionVerifyError::BindingMismatch) => {
+                warn!("Provided session is not valid");
+                Err(RecentAuthRejection::Status(StatusCode::UNAUTHORIZED))
+            },
+            Err(SessionVerifyError::Unknown) => {
+                error!("Unexpected error during session verification attempt");
+                Err(RecentAuthRejection::Status(StatusCode::INTERNAL_SERVER_ERROR))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
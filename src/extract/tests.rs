@@ -0,0 +1,192 @@
+use std::sync::Arc;
+
+use http::{Request, header};
+use mockall::predicate;
+
+use crate::{constants::{SESSION_ID_LENGTH, PERSONAL_TOKEN_PREFIX}, service::sessions::MockSessionService};
+
+use super::*;
+
+fn mock_cookie_session_id() -> String {
+    "1".repeat(SESSION_ID_LENGTH)
+}
+
+fn mock_bearer_session_id() -> String {
+    "2".repeat(SESSION_ID_LENGTH)
+}
+
+fn mock_personal_token() -> String {
+    String::from(PERSONAL_TOKEN_PREFIX) + "token"
+}
+
+fn mock_parts(cookie: Option<&str>, bearer: Option<&str>) -> Parts {
+    let mut builder = Request::builder();
+    if let Some(cookie) = cookie {
+        builder = builder.header(header::COOKIE, format!("{}={}", SESSION_COOKIE_NAME.as_str(), cookie));
+    }
+    if let Some(bearer) = bearer {
+        builder = builder.header(header::AUTHORIZATION, format!("Bearer {}", bearer));
+    }
+    builder.body(()).unwrap().into_parts().0
+}
+
+fn cookie_first() -> Vec<TokenSource> {
+    vec![TokenSource::Cookie, TokenSource::Bearer]
+}
+
+fn bearer_first() -> Vec<TokenSource> {
+    vec![TokenSource::Bearer, TokenSource::Cookie]
+}
+
+#[test]
+fn token_sources_from_str() {
+    assert_eq!(Ok(TokenSources(bearer_first())), TokenSources::from_str("bearer, cookie"));
+    assert_eq!(Ok(TokenSources(vec![TokenSource::Cookie])), TokenSources::from_str("cookie"));
+    assert!(TokenSources::from_str("header").is_err());
+}
+
+#[tokio::test]
+async fn credential_cookie() {
+    let mut parts = mock_parts(Some(&mock_cookie_session_id()), None);
+    assert_eq!(Ok(Credential::Session(mock_cookie_session_id())), Credential::from_sources(&mut parts, &bearer_first()).await);
+}
+
+#[tokio::test]
+async fn credential_bearer() {
+    let mut parts = mock_parts(None, Some(&mock_bearer_session_id()));
+    assert_eq!(Ok(Credential::Session(mock_bearer_session_id())), Credential::from_sources(&mut parts, &cookie_first()).await);
+}
+
+#[tokio::test]
+async fn credential_cookie_precedence() {
+    let mut parts = mock_parts(Some(&mock_cookie_session_id()), Some(&mock_bearer_session_id()));
+    assert_eq!(Ok(Credential::Session(mock_cookie_session_id())), Credential::from_sources(&mut parts, &cookie_first()).await);
+}
+
+#[tokio::test]
+async fn credential_bearer_precedence() {
+    let mut parts = mock_parts(Some(&mock_cookie_session_id()), Some(&mock_bearer_session_id()));
+    assert_eq!(Ok(Credential::Session(mock_bearer_session_id())), Credential::from_sources(&mut parts, &bearer_first()).await);
+}
+
+#[tokio::test]
+async fn credential_disabled_source() {
+    let mut parts = mock_parts(None, Some(&mock_bearer_session_id()));
+    assert_eq!(Err(StatusCode::UNAUTHORIZED), Credential::from_sources(&mut parts, &[TokenSource::Cookie]).await);
+}
+
+#[tokio::test]
+async fn credential_personal_token() {
+    let mut parts = mock_parts(Some(&mock_cookie_session_id()), Some(&mock_personal_token()));
+    assert_eq!(Ok(Credential::PersonalToken(mock_personal_token())), Credential::from_sources(&mut parts, &bearer_first()).await);
+}
+
+#[tokio::test]
+async fn credential_missing() {
+    let mut parts = mock_parts(None, None);
+    assert_eq!(Err(StatusCode::UNAUTHORIZED), Credential::from_sources(&mut parts, &cookie_first()).await);
+}
+
+#[tokio::test]
+async fn credential_invalid_session_id() {
+    let mut parts = mock_parts(None, Some("short"));
+    assert_eq!(Err(StatusCode::UNPROCESSABLE_ENTITY), Credential::from_sources(&mut parts, &cookie_first()).await);
+}
+
+#[tokio::test]
+async fn session_token_rejects_personal_token() {
+    let mut parts = mock_parts(None, Some(&mock_personal_token()));
+    assert_eq!(Err(StatusCode::UNAUTHORIZED), SessionToken::from_request_parts(&mut parts, &()).await);
+}
+
+#[test]
+fn client_info_peer_address() {
+    let mut parts = Request::builder()
+        .header(header::USER_AGENT, "curl/8.0.1")
+        .header("x-forwarded-for", "198.51.100.1")
+        .body(())
+        .unwrap()
+        .into_parts()
+        .0;
+    parts.extensions.insert(ConnectInfo(SocketAddr::from(([203, 0, 113, 7], 4000))));
+
+    let client = ClientInfo::from_parts(&parts, "");
+    assert_eq!(Some(String::from("curl/8.0.1")), client.user_agent);
+    assert_eq!(Some(IpAddr::from([203, 0, 113, 7])), client.ip);
+}
+
+#[test]
+fn client_info_ip_header() {
+    let parts = Request::builder()
+        .header("x-forwarded-for", "198.51.100.1, 203.0.113.7")
+        .body(())
+        .unwrap()
+        .into_parts()
+        .0;
+
+    let client = ClientInfo::from_parts(&parts, "x-forwarded-for");
+    assert_eq!(None, client.user_agent);
+    assert_eq!(Some(IpAddr::from([203, 0, 113, 7])), client.ip);
+    assert_eq!(None, ClientInfo::from_parts(&parts, "x-real-ip").ip);
+}
+
+fn mock_user() -> User {
+    User {
+        id: 1,
+        email: String::from("email"),
+        password_hash: String::from("hash")
+    }
+}
+
+async fn recent_auth(verify: Result<User, SessionVerifyError>) -> Result<User, RecentAuthRejection> {
+    let mut service = MockSessionService::new();
+    service
+        .expect_verify_recent()
+        .with(predicate::eq(mock_bearer_session_id()), predicate::always(), predicate::eq(*REAUTH_MAX_AGE_SECONDS))
+        .times(1)
+        .return_once(move |_, _, _| verify);
+
+    let mut parts = mock_parts(None, Some(&mock_bearer_session_id()));
+    parts.extensions.insert(Arc::new(service));
+
+    RecentAuth::<Arc<MockSessionService>>::from_request_parts(&mut parts, &())
+        .await
+        .map(|RecentAuth(user, _)| user)
+}
+
+#[tokio::test]
+async fn recent_auth_normal() {
+    assert_eq!(Ok(mock_user()), recent_auth(Ok(mock_user())).await);
+}
+
+#[tokio::test]
+async fn recent_auth_stale() {
+    assert_eq!(
+        Err(RecentAuthRejection::ReauthenticationRequired { max_age: *REAUTH_MAX_AGE_SECONDS }),
+        recent_auth(Err(SessionVerifyError::StaleAuthentication)).await
+    );
+}
+
+#[tokio::test]
+async fn recent_auth_missing_session() {
+    assert_eq!(Err(RecentAuthRejection::Status(StatusCode::UNAUTHORIZED)), recent_auth(Err(SessionVerifyError::Missing)).await);
+}
+
+#[tokio::test]
+async fn recent_auth_no_credential() {
+    let mut parts = mock_parts(None, None);
+    parts.extensions.insert(Arc::new(MockSessionService::new()));
+
+    let res = RecentAuth::<Arc<MockSessionService>>::from_request_parts(&mut parts, &()).await;
+    assert!(matches!(res, Err(RecentAuthRejection::Status(StatusCode::UNAUTHORIZED))));
+}
+
+#[tokio::test]
+async fn recent_auth_rejection_body() {
+    let res = RecentAuthRejection::ReauthenticationRequired { max_age: 600 }.into_response();
+    assert_eq!(StatusCode::FORBIDDEN, res.status());
+
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json!({ "error": "reauthentication_required", "max_age": 600 }), body);
+}
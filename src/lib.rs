@@ -0,0 +1,64 @@
+//! Authentication service for AgarTeX.
+//!
+//! The binary serves `routing::main_router`, other applications can mount the same routes
+//! with their own services through `routing::RouterBuilder`.
+
+pub mod constants;
+pub mod control;
+pub mod cookies;
+pub mod cors;
+pub mod csrf;
+pub mod domain;
+pub mod extract;
+pub mod passwords;
+pub mod repository;
+pub mod routing;
+pub mod service;
+pub mod tasks;
+pub mod validation;
+
+use std::net::SocketAddr;
+
+use tracing::info;
+
+use constants::SERVER_URL;
+
+pub use routing::{RouterBuilder, AppState, main_router};
+
+async fn shutdown_signal() {
+    let interrupt = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            },
+            Err(_) => std::future::pending::<()>().await
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => (),
+        _ = terminate => ()
+    }
+    info!("Shutting down");
+}
+
+#[tracing::instrument]
+pub async fn run() -> anyhow::Result<()> {
+    let (router, tasks) = main_router().await?;
+
+    info!("Running server!");
+    axum::Server::try_bind(&SERVER_URL)?
+        .serve(router.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown_signal())
+        .await?;
+
+    tasks.shutdown().await;
+    Ok(())
+}
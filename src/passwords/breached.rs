@@ -0,0 +1,195 @@
+use std::{fmt::{self, Debug}, fs::File, io::{self, BufRead, BufReader, Read}, os::unix::fs::FileExt, sync::Arc};
+
+use anyhow::{bail, Context};
+use ring::digest::{digest, SHA1_FOR_LEGACY_USE_ONLY};
+use tracing::{error, info};
+
+const SHA1_LENGTH: usize = 20;
+/// Hashes are grouped by their first 5 hex digits, like the ranges of the Have I Been Pwned API.
+const PREFIX_BITS: u32 = 20;
+const PREFIX_COUNT: usize = 1 << PREFIX_BITS;
+
+/// Positioned reads, so that concurrent lookups don't have to share a cursor.
+pub trait Source: Send + Sync + 'static {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize>;
+
+    fn read_exact_at(&self, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+        while !buf.is_empty() {
+            match self.read_at(buf, offset)? {
+                0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                read => {
+                    buf = &mut buf[read..];
+                    offset += read as u64;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Source for File {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        FileExt::read_at(self, buf, offset)
+    }
+}
+
+impl Source for Vec<u8> {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        let start = usize::try_from(offset).unwrap_or(usize::MAX).min(self.len());
+        let read = buf.len().min(self.len() - start);
+        buf[..read].copy_from_slice(&self[start..start + read]);
+        Ok(read)
+    }
+}
+
+/// Reads a `Source` front to back, for indexing.
+struct SourceReader<'a, S> {
+    source: &'a S,
+    offset: u64
+}
+
+impl<S: Source> Read for SourceReader<'_, S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.source.read_at(buf, self.offset)?;
+        self.offset += read as u64;
+        Ok(read)
+    }
+}
+
+/// SHA-1 hashes of passwords known from breaches, checked locally so that screening needs no network access.
+/// The list stays on disk, only the offset where every prefix starts is kept in memory, 8 MB whatever its size.
+#[derive(Default)]
+pub struct BreachedPasswords {
+    source: Option<Arc<dyn Source>>,
+    /// Hashes starting with prefix `p` are stored between `offsets[p]` and `offsets[p + 1]`.
+    offsets: Vec<u64>,
+    len: usize
+}
+
+impl BreachedPasswords {
+    /// Indexes one hex encoded SHA-1 per line, optionally followed by `:count` as in the Have I Been Pwned
+    /// downloads. Lines have to be sorted by hash, so the list must be downloaded ordered by hash rather than count.
+    pub fn index<S: Source>(source: S) -> anyhow::Result<Self> {
+        let mut reader = BufReader::new(SourceReader { source: &source, offset: 0 });
+        let mut offsets = Vec::with_capacity(PREFIX_COUNT + 1);
+        let mut previous: Option<[u8; SHA1_LENGTH]> = None;
+        let mut len = 0;
+        let mut offset = 0;
+        let mut line = String::new();
+
+        for number in 1.. {
+            line.clear();
+            let read = reader.read_line(&mut line)?;
+            if read == 0 {
+                break;
+            }
+            let start = offset;
+            offset += read as u64;
+
+            let hash = line.split(':').next().unwrap_or_default().trim();
+            if hash.is_empty() {
+                continue;
+            }
+            let Some(hash) = decode_sha1(hash) else {
+                bail!("Invalid SHA-1 on line {}: {}", number, hash);
+            };
+
+            match previous {
+                Some(previous) if hash < previous => bail!("Breached password list is not sorted by hash on line {}", number),
+                Some(previous) if hash == previous => continue,
+                _ => ()
+            }
+            previous = Some(hash);
+            len += 1;
+
+            // prefixes without any hash start where the next one does
+            while offsets.len() <= prefix(&hash) {
+                offsets.push(start);
+            }
+        }
+        offsets.resize(PREFIX_COUNT + 1, offset);
+
+        Ok(Self { source: Some(Arc::new(source)), offsets, len })
+    }
+
+    /// An empty path disables screening. The file must not change while the server runs.
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        if path.is_empty() {
+            return Ok(Self::default());
+        }
+
+        let file = File::open(path).with_context(|| format!("Unable to open breached password list {}", path))?;
+        let breached = Self::index(file).with_context(|| format!("Unable to index breached password list {}", path))?;
+        info!("Indexed {} breached password hashes from {}", breached.len(), path);
+        Ok(breached)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Reads the range of the password's prefix, a few dozen kilobytes for the full Have I Been Pwned list,
+    /// on the blocking pool. Passwords are let through if the list can't be read, as screening is best effort.
+    pub async fn contains(&self, password: &str) -> bool {
+        let Some(source) = &self.source else {
+            return false;
+        };
+
+        let hash = digest(&SHA1_FOR_LEGACY_USE_ONLY, password.as_bytes());
+        let hash = hash.as_ref();
+        let prefix = prefix(hash);
+        let (start, end) = (self.offsets[prefix], self.offsets[prefix + 1]);
+        if start == end {
+            return false;
+        }
+
+        let source = source.clone();
+        let res = tokio::task::spawn_blocking(move || {
+            let mut range = vec![0; (end - start) as usize];
+            source.read_exact_at(&mut range, start).map(|_| range)
+        }).await;
+        let range = match res {
+            Ok(Ok(range)) => range,
+            Ok(Err(err)) => {
+                error!("Unable to read the breached password list: {}", err);
+                return false;
+            },
+            Err(err) => {
+                error!(%err);
+                return false;
+            }
+        };
+
+        range
+            .split(|byte| *byte == b'\n')
+            .filter_map(|line| std::str::from_utf8(line).ok())
+            .filter_map(|line| decode_sha1(line.split(':').next().unwrap_or_default().trim()))
+            .any(|candidate| candidate == hash)
+    }
+}
+
+impl Debug for BreachedPasswords {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BreachedPasswords").field("len", &self.len).finish_non_exhaustive()
+    }
+}
+
+fn prefix(hash: &[u8]) -> usize {
+    (usize::from(hash[0]) << 12) | (usize::from(hash[1]) << 4) | (usize::from(hash[2]) >> 4)
+}
+
+fn decode_sha1(hex: &str) -> Option<[u8; SHA1_LENGTH]> {
+    if hex.len() != SHA1_LENGTH * 2 || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut hash = [0; SHA1_LENGTH];
+    for (i, byte) in hash.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(hash)
+}
@@ -0,0 +1,34 @@
+mod breached;
+mod policy;
+mod strength;
+
+use unicode_normalization::UnicodeNormalization;
+use validator::ValidationError;
+
+use crate::constants::PASSWORD_POLICY;
+
+pub use breached::BreachedPasswords;
+pub use policy::{PasswordPolicy, CharacterClass};
+pub use strength::{PasswordStrength, PasswordFeedback, estimate_strength, email_inputs};
+
+/// NFKC form of a password, so that the same passphrase typed on another keyboard or system
+/// hashes the same. ASCII passwords are left unchanged.
+pub fn normalize(password: &str) -> String {
+    password.nfkc().collect()
+}
+
+/// Checks a new password against the configured policy and the given breached password list, `user_inputs`
+/// such as the email make passwords built from them weak. Returns every failed check, an empty list accepts the password.
+pub async fn validate_password(breached: &BreachedPasswords, password: &str, user_inputs: &[&str]) -> Vec<ValidationError> {
+    let password = normalize(password);
+    let mut errs = PASSWORD_POLICY.validate(&password, user_inputs);
+
+    if breached.contains(&password).await {
+        errs.push(ValidationError::new("breached_password"));
+    }
+
+    errs
+}
+
+#[cfg(test)]
+mod tests;
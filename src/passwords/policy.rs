@@ -0,0 +1,152 @@
+use std::str::FromStr;
+
+use serde::Serialize;
+use validator::ValidationError;
+
+use crate::constants::{PASSWORD_MIN_LENGTH, PASSWORD_MAX_LENGTH, PASSWORD_MAX_BYTES, PASSWORD_REQUIRED_CLASSES, PASSWORD_SPECIAL_CHARS, PASSWORD_MIN_SCORE};
+
+use super::estimate_strength;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CharacterClass {
+    Lowercase,
+    Uppercase,
+    Digit,
+    Special
+}
+
+impl FromStr for CharacterClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "lowercase" => Ok(Self::Lowercase),
+            "uppercase" => Ok(Self::Uppercase),
+            "digit" => Ok(Self::Digit),
+            "special" => Ok(Self::Special),
+            _ => Err(format!("Unknown character class: {}", s))
+        }
+    }
+}
+
+impl CharacterClass {
+    fn code(&self) -> &'static str {
+        match self {
+            Self::Lowercase => "lowercase_character",
+            Self::Uppercase => "uppercase_character",
+            Self::Digit => "digit_character",
+            Self::Special => "special_character"
+        }
+    }
+}
+
+/// Rules for new passwords, at registration and password change. Password reset doesn't exist yet,
+/// it should apply the same policy. Serialized as is for the frontend to render hints.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PasswordPolicy {
+    /// In characters, passwords over `PASSWORD_MAX_BYTES` bytes are rejected regardless since bcrypt ignores the rest.
+    pub min_length: usize,
+    pub max_length: usize,
+    pub required_classes: Vec<CharacterClass>,
+    /// Symbols accepted besides letters and digits, `None` accepts any and counts every one as special.
+    pub special_chars: Option<String>,
+    /// 0 to 4, passwords estimated below it are rejected as weak.
+    pub min_score: u8
+}
+
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        Self {
+            min_length: 8,
+            max_length: PASSWORD_MAX_BYTES,
+            required_classes: Vec::new(),
+            special_chars: None,
+            min_score: 3
+        }
+    }
+}
+
+impl PasswordPolicy {
+    pub fn from_env() -> Result<Self, String> {
+        let required_classes = PASSWORD_REQUIRED_CLASSES
+            .split(',')
+            .filter(|class| !class.trim().is_empty())
+            .map(CharacterClass::from_str)
+            .collect::<Result<_, _>>()?;
+        let special_chars = match PASSWORD_SPECIAL_CHARS.as_str() {
+            "" => None,
+            chars => Some(String::from(chars))
+        };
+
+        let policy = Self {
+            min_length: *PASSWORD_MIN_LENGTH,
+            max_length: *PASSWORD_MAX_LENGTH,
+            required_classes,
+            special_chars,
+            min_score: *PASSWORD_MIN_SCORE
+        };
+
+        if policy.min_length == 0 || policy.min_length > policy.max_length {
+            return Err(format!("Invalid password length bounds: {}..{}", policy.min_length, policy.max_length));
+        }
+        if policy.min_score > 4 {
+            return Err(format!("Password score threshold must be between 0 and 4: {}", policy.min_score));
+        }
+        Ok(policy)
+    }
+
+    fn is_special(&self, c: char) -> bool {
+        match &self.special_chars {
+            Some(chars) => chars.contains(c),
+            None => !c.is_alphanumeric()
+        }
+    }
+
+    fn has_class(&self, password: &str, class: CharacterClass) -> bool {
+        password.chars().any(|c| match class {
+            CharacterClass::Lowercase => c.is_lowercase(),
+            CharacterClass::Uppercase => c.is_uppercase(),
+            CharacterClass::Digit => c.is_numeric(),
+            CharacterClass::Special => self.is_special(c)
+        })
+    }
+
+    /// Returns every rule the already normalized `password` breaks, `user_inputs` such as the email
+    /// make passwords built from them weak.
+    pub fn validate(&self, password: &str, user_inputs: &[&str]) -> Vec<ValidationError> {
+        let mut errs = Vec::new();
+
+        let length = password.chars().count();
+        if length < self.min_length || length > self.max_length || password.len() > PASSWORD_MAX_BYTES {
+            let mut err = ValidationError::new("length");
+            err.add_param("min".into(), &self.min_length);
+            err.add_param("max".into(), &self.max_length);
+            err.add_param("max_bytes".into(), &PASSWORD_MAX_BYTES);
+            errs.push(err);
+        }
+
+        for class in &self.required_classes {
+            if !self.has_class(password, *class) {
+                errs.push(ValidationError::new(class.code()));
+            }
+        }
+
+        if self.special_chars.is_some() && !password.chars().all(|c| c.is_alphanumeric() || self.is_special(c)) {
+            let mut err = ValidationError::new("invalid_character");
+            err.add_param("special_chars".into(), &self.special_chars);
+            errs.push(err);
+        }
+
+        let strength = estimate_strength(password, user_inputs);
+        if strength.score < self.min_score {
+            let mut err = ValidationError::new("weak_password");
+            err.add_param("score".into(), &strength.score);
+            err.add_param("min_score".into(), &self.min_score);
+            err.add_param("feedback".into(), &strength.feedback);
+            errs.push(err);
+        }
+
+        errs
+    }
+}
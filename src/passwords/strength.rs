@@ -0,0 +1,51 @@
+use serde::Serialize;
+use zxcvbn::zxcvbn;
+
+/// How hard a password is to guess, `score` goes from 0 (too guessable) to 4 (very unguessable).
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PasswordStrength {
+    pub score: u8,
+    pub feedback: PasswordFeedback
+}
+
+/// Hints shown to the user next to a weak password.
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct PasswordFeedback {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
+    pub suggestions: Vec<String>
+}
+
+/// Estimates the guesses an attacker needs, penalising dictionary words, keyboard patterns, repeats and
+/// sequences. `user_inputs`, e.g. the email, count as dictionary words.
+pub fn estimate_strength(password: &str, user_inputs: &[&str]) -> PasswordStrength {
+    let Ok(entropy) = zxcvbn(password, user_inputs) else {
+        // only fails for an empty password
+        return PasswordStrength { score: 0, feedback: PasswordFeedback::default() };
+    };
+
+    let feedback = entropy
+        .feedback()
+        .as_ref()
+        .map(|feedback| PasswordFeedback {
+            warning: feedback.warning().map(|warning| warning.to_string()),
+            suggestions: feedback.suggestions().iter().map(ToString::to_string).collect()
+        })
+        .unwrap_or_default();
+
+    PasswordStrength { score: entropy.score(), feedback }
+}
+
+/// The parts of an email a password might be built from, e.g. `jan.kowalski@agartex.com`
+/// gives the whole address, `jan`, `kowalski` and `agartex`.
+pub fn email_inputs(email: &str) -> Vec<&str> {
+    let (local, domain) = email.split_once('@').unwrap_or((email, ""));
+    let domain = domain.split('.').next().unwrap_or_default();
+
+    std::iter::once(email)
+        .chain(std::iter::once(local))
+        .chain(local.split(['.', '_', '-', '+']))
+        .chain(std::iter::once(domain))
+        .filter(|input| input.len() > 1)
+        .collect()
+}
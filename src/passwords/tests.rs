@@ -0,0 +1,218 @@
+use std::str::FromStr;
+
+use super::*;
+
+// SHA-1 of "Password1!" and "password"
+const PASSWORD1_SHA1: &str = "32CA9FC1A0F5B6330E3F4C8C1BBECDE9BEDB9573";
+const PASSWORD_SHA1: &str = "5BAA61E4C9B93F3F0682250B6CF8331B7EE68FD8";
+
+fn mock_list() -> String {
+    format!("{}:12\n\n{}:24230577\n", PASSWORD1_SHA1.to_lowercase(), PASSWORD_SHA1)
+}
+
+fn fixture() -> BreachedPasswords {
+    BreachedPasswords::load(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/breached_passwords.txt")).unwrap()
+}
+
+fn index(list: String) -> anyhow::Result<BreachedPasswords> {
+    BreachedPasswords::index(list.into_bytes())
+}
+
+#[tokio::test]
+async fn index_and_contains() {
+    let breached = index(mock_list()).unwrap();
+
+    assert_eq!(2, breached.len());
+    assert!(breached.contains("password").await);
+    assert!(breached.contains("Password1!").await);
+    assert!(!breached.contains("correct horse battery staple").await);
+}
+
+#[tokio::test]
+async fn index_without_counts() {
+    let breached = index(format!("{}\n{}\n", PASSWORD_SHA1, PASSWORD_SHA1)).unwrap();
+
+    assert_eq!(1, breached.len());
+    assert!(breached.contains("password").await);
+}
+
+#[tokio::test]
+async fn index_shared_prefix() {
+    // same first 5 hex digits as "password", the whole range is searched
+    let neighbours = ["5BAA600000000000000000000000000000000000", "5BAA6FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF"];
+    let breached = index(format!("{}:1\r\n{}:3\r\n{}:2\r\n", neighbours[0], PASSWORD_SHA1, neighbours[1])).unwrap();
+
+    assert_eq!(3, breached.len());
+    assert!(breached.contains("password").await);
+    assert!(!breached.contains("Password1!").await);
+
+    let breached = index(format!("{}\n{}\n", neighbours[0], neighbours[1])).unwrap();
+    assert!(!breached.contains("password").await);
+}
+
+#[test]
+fn index_invalid_line() {
+    let err = index(format!("{}\nnot-a-hash:3\n", PASSWORD_SHA1)).unwrap_err();
+    assert!(err.to_string().contains("line 2"));
+}
+
+#[test]
+fn index_unsorted() {
+    let err = index(format!("{}\n{}\n", PASSWORD_SHA1, PASSWORD1_SHA1)).unwrap_err();
+    assert!(err.to_string().contains("not sorted"));
+}
+
+#[tokio::test]
+async fn load_disabled() {
+    let breached = BreachedPasswords::load("").unwrap();
+
+    assert!(breached.is_empty());
+    assert!(!breached.contains("password").await);
+}
+
+#[tokio::test]
+async fn load_file() {
+    let breached = fixture();
+
+    assert_eq!(4, breached.len());
+    assert!(breached.contains("123456").await);
+}
+
+#[test]
+fn email_inputs_parts() {
+    assert_eq!(
+        vec!["jan.kowalski@agartex.com", "jan.kowalski", "jan", "kowalski", "agartex"],
+        email_inputs("jan.kowalski@agartex.com")
+    );
+}
+
+#[test]
+fn email_inputs_skips_single_chars() {
+    assert_eq!(vec!["a_b@c.io", "a_b"], email_inputs("a_b@c.io"));
+}
+
+#[test]
+fn estimate_strength_weak() {
+    let strength = estimate_strength("Password1!", &[]);
+
+    assert!(strength.score < 3);
+    assert!(strength.feedback.warning.is_some() || !strength.feedback.suggestions.is_empty());
+}
+
+#[test]
+fn estimate_strength_passphrase() {
+    assert!(estimate_strength("quiet lantern orbits marmalade", &[]).score >= 3);
+}
+
+#[test]
+fn estimate_strength_empty() {
+    assert_eq!(0, estimate_strength("", &[]).score);
+}
+
+#[test]
+fn normalize_compatibility_forms() {
+    assert_eq!("password", normalize("ｐａｓｓｗｏｒｄ"));
+    assert_eq!("Ångström", normalize("A\u{30a}ngstro\u{308}m"));
+}
+
+#[tokio::test]
+async fn validate_password_passphrase() {
+    assert!(validate_password(&fixture(), "quiet lantern orbits marmalade", &[]).await.is_empty());
+}
+
+#[tokio::test]
+async fn validate_password_unicode_passphrase() {
+    assert!(validate_password(&fixture(), "żółta łódź pływa po jeziorze", &[]).await.is_empty());
+}
+
+#[tokio::test]
+async fn validate_password_too_short() {
+    let errs = validate_password(&fixture(), "x7#", &[]).await;
+    assert!(errs.iter().any(|err| err.code == "length"));
+}
+
+#[tokio::test]
+async fn validate_password_too_long() {
+    let password = "quiet lantern orbits marmalade ".repeat(3);
+    let errs = validate_password(&fixture(), &password, &[]).await;
+
+    assert!(password.len() > 72);
+    assert!(errs.iter().any(|err| err.code == "length"));
+}
+
+#[tokio::test]
+async fn validate_password_weak() {
+    let errs = validate_password(&fixture(), "Password1!", &[]).await;
+    let err = errs.iter().find(|err| err.code == "weak_password").unwrap();
+
+    assert!(err.params.contains_key("score"));
+    assert!(err.params.contains_key("feedback"));
+}
+
+#[tokio::test]
+async fn validate_password_breached() {
+    // strong by estimate, but published in a comic and found in breaches since
+    let errs = validate_password(&fixture(), "correct horse battery staple", &[]).await;
+
+    assert_eq!(vec!["breached_password"], errs.iter().map(|err| err.code.as_ref()).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn validate_password_built_from_email() {
+    let email = "zbigniew.wojtaszczyk@agartex.com";
+    let password = "Wojtaszczyk2023";
+
+    assert!(validate_password(&fixture(), password, &[]).await.is_empty());
+    assert!(validate_password(&fixture(), password, &email_inputs(email)).await.iter().any(|err| err.code == "weak_password"));
+}
+
+#[test]
+fn character_class_from_str() {
+    assert_eq!(Ok(CharacterClass::Uppercase), CharacterClass::from_str(" Uppercase"));
+    assert_eq!(Ok(CharacterClass::Special), CharacterClass::from_str("special"));
+    assert!(CharacterClass::from_str("emoji").is_err());
+}
+
+#[test]
+fn policy_required_classes() {
+    let policy = PasswordPolicy {
+        required_classes: vec![CharacterClass::Lowercase, CharacterClass::Uppercase, CharacterClass::Digit, CharacterClass::Special],
+        ..PasswordPolicy::default()
+    };
+
+    let errs = policy.validate("quiet lantern orbits marmalade", &[]);
+    let codes: Vec<_> = errs.iter().map(|err| err.code.as_ref()).collect();
+    assert_eq!(vec!["uppercase_character", "digit_character"], codes);
+
+    assert!(policy.validate("Quiet lantern orbits 42 marmalade", &[]).is_empty());
+}
+
+#[test]
+fn policy_special_chars() {
+    let policy = PasswordPolicy {
+        required_classes: vec![CharacterClass::Special],
+        special_chars: Some(String::from("!@#$%^&*")),
+        ..PasswordPolicy::default()
+    };
+
+    let errs = policy.validate("quiet lantern orbits marmalade", &[]);
+    let codes: Vec<_> = errs.iter().map(|err| err.code.as_ref()).collect();
+    assert_eq!(vec!["special_character", "invalid_character"], codes);
+
+    assert!(policy.validate("quietlantern&orbitsmarmalade", &[]).is_empty());
+}
+
+#[test]
+fn policy_length_bounds() {
+    let policy = PasswordPolicy { min_length: 12, max_length: 20, min_score: 0, ..PasswordPolicy::default() };
+
+    assert!(policy.validate("short pass", &[]).iter().any(|err| err.code == "length"));
+    assert!(policy.validate("a passphrase far too long", &[]).iter().any(|err| err.code == "length"));
+    assert!(policy.validate("just long enough", &[]).is_empty());
+}
+
+#[test]
+fn policy_min_score() {
+    let policy = PasswordPolicy { min_score: 0, ..PasswordPolicy::default() };
+    assert!(policy.validate("Password1!", &[]).is_empty());
+}
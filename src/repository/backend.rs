@@ -0,0 +1,227 @@
+use std::str::FromStr;
+
+use axum::async_trait;
+
+use crate::domain::{sessions::{Session, SessionData}, users::{User, UserData}, personal_tokens::{PersonalToken, PersonalTokenData}, tokens::{RefreshToken, RefreshTokenData}};
+
+use super::{users::{UserRepository, UserGetError, UserInsertError, UserUpdateError, HttpUserRepository}, sessions::{SessionRepository, SessionGetError, SessionDeleteError, SessionInsertError, SessionUpdateError, HttpSessionRepository}, personal_tokens::{PersonalTokenRepository, PersonalTokenInsertError, PersonalTokenGetError, PersonalTokenDeleteError, HttpPersonalTokenRepository}, refresh_tokens::{RefreshTokenRepository, RefreshTokenInsertError, RefreshTokenGetError, RefreshTokenUseError, RefreshTokenRevokeError, HttpRefreshTokenRepository}, postgres::{PgUserRepository, PgSessionRepository, PgPersonalTokenRepository, PgRefreshTokenRepository}, memory::{MemoryUserRepository, MemorySessionRepository, MemoryPersonalTokenRepository, MemoryRefreshTokenRepository}, sqlite::{SqliteUserRepository, SqliteSessionRepository, SqlitePersonalTokenRepository, SqliteRefreshTokenRepository}};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RepositoryBackend {
+    Http,
+    Postgres,
+    Sqlite,
+    Memory
+}
+
+impl FromStr for RepositoryBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "http" => Ok(Self::Http),
+            "postgres" => Ok(Self::Postgres),
+            "sqlite" => Ok(Self::Sqlite),
+            "memory" => Ok(Self::Memory),
+            other => Err(format!("Unknown repository backend: {}", other))
+        }
+    }
+}
+
+/// Lets the backend be chosen at startup while the services stay monomorphic.
+#[derive(Debug, Clone)]
+pub enum AnyUserRepository {
+    Http(HttpUserRepository),
+    Postgres(PgUserRepository),
+    Sqlite(SqliteUserRepository),
+    Memory(MemoryUserRepository)
+}
+
+#[async_trait]
+impl UserRepository for AnyUserRepository {
+    async fn get_by_email(&self, email: &str) -> Result<User, UserGetError> {
+        match self {
+            Self::Http(repository) => repository.get_by_email(email).await,
+            Self::Postgres(repository) => repository.get_by_email(email).await,
+            Self::Sqlite(repository) => repository.get_by_email(email).await,
+            Self::Memory(repository) => repository.get_by_email(email).await
+        }
+    }
+
+    async fn insert(&self, user_data: UserData) -> Result<(), UserInsertError> {
+        match self {
+            Self::Http(repository) => repository.insert(user_data).await,
+            Self::Postgres(repository) => repository.insert(user_data).await,
+            Self::Sqlite(repository) => repository.insert(user_data).await,
+            Self::Memory(repository) => repository.insert(user_data).await
+        }
+    }
+
+    async fn update_password(&self, id: i32, password_hash: &str) -> Result<(), UserUpdateError> {
+        match self {
+            Self::Http(repository) => repository.update_password(id, password_hash).await,
+            Self::Postgres(repository) => repository.update_password(id, password_hash).await,
+            Self::Sqlite(repository) => repository.update_password(id, password_hash).await,
+            Self::Memory(repository) => repository.update_password(id, password_hash).await
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum AnySessionRepository {
+    Http(HttpSessionRepository),
+    Postgres(PgSessionRepository),
+    Sqlite(SqliteSessionRepository),
+    Memory(MemorySessionRepository)
+}
+
+#[async_trait]
+impl SessionRepository for AnySessionRepository {
+    async fn insert(&self, session_data: &SessionData) -> Result<(), SessionInsertError> {
+        match self {
+            Self::Http(repository) => repository.insert(session_data).await,
+            Self::Postgres(repository) => repository.insert(session_data).await,
+            Self::Sqlite(repository) => repository.insert(session_data).await,
+            Self::Memory(repository) => repository.insert(session_data).await
+        }
+    }
+
+    async fn get(&self, id: &str) -> Result<Session, SessionGetError> {
+        match self {
+            Self::Http(repository) => repository.get(id).await,
+            Self::Postgres(repository) => repository.get(id).await,
+            Self::Sqlite(repository) => repository.get(id).await,
+            Self::Memory(repository) => repository.get(id).await
+        }
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), SessionDeleteError> {
+        match self {
+            Self::Http(repository) => repository.delete(id).await,
+            Self::Postgres(repository) => repository.delete(id).await,
+            Self::Sqlite(repository) => repository.delete(id).await,
+            Self::Memory(repository) => repository.delete(id).await
+        }
+    }
+
+    async fn shorten(&self, id: &str, expires: i64) -> Result<(), SessionUpdateError> {
+        match self {
+            Self::Http(repository) => repository.shorten(id, expires).await,
+            Self::Postgres(repository) => repository.shorten(id, expires).await,
+            Self::Sqlite(repository) => repository.shorten(id, expires).await,
+            Self::Memory(repository) => repository.shorten(id, expires).await
+        }
+    }
+
+    async fn mark_authenticated(&self, id: &str, authenticated_at: i64) -> Result<(), SessionUpdateError> {
+        match self {
+            Self::Http(repository) => repository.mark_authenticated(id, authenticated_at).await,
+            Self::Postgres(repository) => repository.mark_authenticated(id, authenticated_at).await,
+            Self::Sqlite(repository) => repository.mark_authenticated(id, authenticated_at).await,
+            Self::Memory(repository) => repository.mark_authenticated(id, authenticated_at).await
+        }
+    }
+
+    async fn delete_expired(&self, before: i64) -> Result<u64, SessionDeleteError> {
+        match self {
+            Self::Http(repository) => repository.delete_expired(before).await,
+            Self::Postgres(repository) => repository.delete_expired(before).await,
+            Self::Sqlite(repository) => repository.delete_expired(before).await,
+            Self::Memory(repository) => repository.delete_expired(before).await
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum AnyPersonalTokenRepository {
+    Http(HttpPersonalTokenRepository),
+    Postgres(PgPersonalTokenRepository),
+    Sqlite(SqlitePersonalTokenRepository),
+    Memory(MemoryPersonalTokenRepository)
+}
+
+#[async_trait]
+impl PersonalTokenRepository for AnyPersonalTokenRepository {
+    async fn insert(&self, token_data: &PersonalTokenData) -> Result<PersonalToken, PersonalTokenInsertError> {
+        match self {
+            Self::Http(repository) => repository.insert(token_data).await,
+            Self::Postgres(repository) => repository.insert(token_data).await,
+            Self::Sqlite(repository) => repository.insert(token_data).await,
+            Self::Memory(repository) => repository.insert(token_data).await
+        }
+    }
+
+    async fn get_by_hash(&self, token_hash: &str) -> Result<PersonalToken, PersonalTokenGetError> {
+        match self {
+            Self::Http(repository) => repository.get_by_hash(token_hash).await,
+            Self::Postgres(repository) => repository.get_by_hash(token_hash).await,
+            Self::Sqlite(repository) => repository.get_by_hash(token_hash).await,
+            Self::Memory(repository) => repository.get_by_hash(token_hash).await
+        }
+    }
+
+    async fn list(&self, user_id: i32) -> Result<Vec<PersonalToken>, PersonalTokenGetError> {
+        match self {
+            Self::Http(repository) => repository.list(user_id).await,
+            Self::Postgres(repository) => repository.list(user_id).await,
+            Self::Sqlite(repository) => repository.list(user_id).await,
+            Self::Memory(repository) => repository.list(user_id).await
+        }
+    }
+
+    async fn delete(&self, user_id: i32, id: i32) -> Result<(), PersonalTokenDeleteError> {
+        match self {
+            Self::Http(repository) => repository.delete(user_id, id).await,
+            Self::Postgres(repository) => repository.delete(user_id, id).await,
+            Self::Sqlite(repository) => repository.delete(user_id, id).await,
+            Self::Memory(repository) => repository.delete(user_id, id).await
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum AnyRefreshTokenRepository {
+    Http(HttpRefreshTokenRepository),
+    Postgres(PgRefreshTokenRepository),
+    Sqlite(SqliteRefreshTokenRepository),
+    Memory(MemoryRefreshTokenRepository)
+}
+
+#[async_trait]
+impl RefreshTokenRepository for AnyRefreshTokenRepository {
+    async fn insert(&self, token_data: &RefreshTokenData) -> Result<(), RefreshTokenInsertError> {
+        match self {
+            Self::Http(repository) => repository.insert(token_data).await,
+            Self::Postgres(repository) => repository.insert(token_data).await,
+            Self::Sqlite(repository) => repository.insert(token_data).await,
+            Self::Memory(repository) => repository.insert(token_data).await
+        }
+    }
+
+    async fn get(&self, id: &str) -> Result<RefreshToken, RefreshTokenGetError> {
+        match self {
+            Self::Http(repository) => repository.get(id).await,
+            Self::Postgres(repository) => repository.get(id).await,
+            Self::Sqlite(repository) => repository.get(id).await,
+            Self::Memory(repository) => repository.get(id).await
+        }
+    }
+
+    async fn mark_used(&self, id: &str) -> Result<(), RefreshTokenUseError> {
+        match self {
+            Self::Http(repository) => repository.mark_used(id).await,
+            Self::Postgres(repository) => repository.mark_used(id).await,
+            Self::Sqlite(repository) => repository.mark_used(id).await,
+            Self::Memory(repository) => repository.mark_used(id).await
+        }
+    }
+
+    async fn revoke_family(&self, family_id: &str) -> Result<(), RefreshTokenRevokeError> {
+        match self {
+            Self::Http(repository) => repository.revoke_family(family_id).await,
+            Self::Postgres(repository) => repository.revoke_family(family_id).await,
+            Self::Sqlite(repository) => repository.revoke_family(family_id).await,
+            Self::Memory(repository) => repository.revoke_family(family_id).await
+        }
+    }
+}
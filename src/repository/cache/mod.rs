@@ -0,0 +1,258 @@
+use std::{collections::HashMap, num::NonZeroUsize, sync::{Arc, Mutex, atomic::{AtomicU64, Ordering}}, time::{Duration, Instant}};
+
+use axum::async_trait;
+use chrono::Utc;
+use lru::LruCache;
+use tracing::debug;
+
+use crate::domain::sessions::{Session, SessionData, Revocation};
+
+use super::sessions::{SessionRepository, SessionGetError, SessionDeleteError, SessionInsertError, SessionUpdateError};
+
+#[derive(Debug, Clone, Copy)]
+pub struct SessionCacheConfig {
+    /// Maximum number of cached ids, 0 disables the cache.
+    pub capacity: usize,
+    pub ttl: Duration,
+    /// How long an unknown id is remembered as missing.
+    pub negative_ttl: Duration
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry {
+    session: Option<Session>,
+    valid_until: Instant
+}
+
+/// A repository read on a cache miss for one id, with the number of concurrent readers
+/// and a generation bumped by every invalidation of that id meanwhile.
+#[derive(Debug, Default)]
+struct Fetch {
+    readers: usize,
+    generation: u64
+}
+
+#[derive(Debug)]
+struct CacheState {
+    entries: LruCache<String, CacheEntry>,
+    /// Only holds ids with a read in flight, so it stays as small as the number of concurrent misses.
+    fetches: HashMap<String, Fetch>
+}
+
+impl CacheState {
+    fn invalidate(&mut self, id: &str) {
+        self.entries.pop(id);
+        if let Some(fetch) = self.fetches.get_mut(id) {
+            fetch.generation += 1;
+        }
+    }
+
+    fn invalidate_fetches(&mut self) {
+        self.fetches.values_mut().for_each(|fetch| fetch.generation += 1);
+    }
+
+    fn start_fetch(&mut self, id: &str) -> u64 {
+        let fetch = self.fetches.entry(String::from(id)).or_default();
+        fetch.readers += 1;
+        fetch.generation
+    }
+
+    /// Whether the id was invalidated since `start_fetch` returned `generation`.
+    fn finish_fetch(&mut self, id: &str, generation: u64) -> bool {
+        let Some(fetch) = self.fetches.get_mut(id) else {
+            return true;
+        };
+
+        let stale = fetch.generation != generation;
+        fetch.readers -= 1;
+        if fetch.readers == 0 {
+            self.fetches.remove(id);
+        }
+        stale
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64
+}
+
+/// Caches `get` results of the wrapped repository. Deletes made through this node are seen
+/// immediately, even by reads already in flight, deletes made elsewhere only once the entry expires.
+#[derive(Debug, Clone)]
+pub struct CachedSessionRepository<R> {
+    repository: R,
+    cache: Option<Arc<Mutex<CacheState>>>,
+    counters: Arc<Counters>,
+    ttl: Duration,
+    negative_ttl: Duration
+}
+
+impl<R> CachedSessionRepository<R> {
+    pub fn new(repository: R, config: SessionCacheConfig) -> Self {
+        Self {
+            repository,
+            cache: NonZeroUsize::new(config.capacity).map(|capacity| Arc::new(Mutex::new(CacheState {
+                entries: LruCache::new(capacity),
+                fetches: HashMap::new()
+            }))),
+            counters: Arc::default(),
+            ttl: config.ttl,
+            negative_ttl: config.negative_ttl
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.counters.hits.load(Ordering::Relaxed),
+            misses: self.counters.misses.load(Ordering::Relaxed)
+        }
+    }
+
+    /// Returns the cached entry or, on a miss, the generation to pass to `finish_fetch`.
+    fn lookup(cache: &Mutex<CacheState>, id: &str) -> Result<CacheEntry, u64> {
+        let mut cache = cache.lock().unwrap();
+        match cache.entries.get(id) {
+            Some(entry) if entry.valid_until > Instant::now() => return Ok(entry.clone()),
+            Some(_) => {
+                cache.entries.pop(id);
+            },
+            None => ()
+        };
+        Err(cache.start_fetch(id))
+    }
+
+    fn invalidate(&self, id: &str) {
+        if let Some(cache) = &self.cache {
+            cache.lock().unwrap().invalidate(id);
+        }
+    }
+
+    pub fn evict(&self, revocation: &Revocation) {
+        let Some(cache) = &self.cache else {
+            return;
+        };
+
+        let mut cache = cache.lock().unwrap();
+        match revocation {
+            Revocation::Session { id } => cache.invalidate(id),
+            Revocation::User { user_id } => {
+                let ids: Vec<String> = cache.entries
+                    .iter()
+                    .filter(|(_, entry)| matches!(&entry.session, Some(session) if session.user.id == *user_id))
+                    .map(|(id, _)| id.clone())
+                    .collect();
+                for id in ids {
+                    cache.entries.pop(&id);
+                }
+                // reads in flight don't know their user yet
+                cache.invalidate_fetches();
+            },
+            Revocation::All => {
+                cache.entries.clear();
+                cache.invalidate_fetches();
+            }
+        }
+    }
+
+    pub fn clear(&self) {
+        if let Some(cache) = &self.cache {
+            let mut cache = cache.lock().unwrap();
+            cache.entries.clear();
+            cache.invalidate_fetches();
+        }
+    }
+}
+
+#[async_trait]
+impl<R> SessionRepository for CachedSessionRepository<R>
+where
+    R: SessionRepository + Send + Sync
+{
+    async fn insert(&self, session_data: &SessionData) -> Result<(), SessionInsertError> {
+        // the id may have been remembered as missing
+        self.invalidate(&session_data.id);
+        self.repository.insert(session_data).await
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn get(&self, id: &str) -> Result<Session, SessionGetError> {
+        let Some(cache) = &self.cache else {
+            return self.repository.get(id).await;
+        };
+
+        let generation = match Self::lookup(cache, id) {
+            Ok(entry) => {
+                self.counters.hits.fetch_add(1, Ordering::Relaxed);
+                debug!("Session cache hit");
+                return entry.session.ok_or(SessionGetError::Missing);
+            },
+            Err(generation) => generation
+        };
+
+        self.counters.misses.fetch_add(1, Ordering::Relaxed);
+        debug!("Session cache miss");
+        let res = self.repository.get(id).await;
+
+        let entry = match &res {
+            Ok(session) => {
+                // never serve a session from the cache past its expiry
+                let remaining = Duration::from_secs((session.expires - Utc::now().timestamp()).max(0) as u64);
+                Some(CacheEntry {
+                    session: Some(session.clone()),
+                    valid_until: Instant::now() + self.ttl.min(remaining)
+                })
+            },
+            Err(SessionGetError::Missing) => Some(CacheEntry {
+                session: None,
+                valid_until: Instant::now() + self.negative_ttl
+            }),
+            Err(SessionGetError::Unknown) => None
+        };
+
+        let mut cache = cache.lock().unwrap();
+        // a delete or update that ran during the read may have made the result stale
+        let stale = cache.finish_fetch(id, generation);
+        if let (Some(entry), false) = (entry, stale) {
+            cache.entries.put(String::from(id), entry);
+        }
+        res
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), SessionDeleteError> {
+        self.invalidate(id);
+        let res = self.repository.delete(id).await;
+        // reads started before the delete finished must not cache the session again
+        self.invalidate(id);
+        res
+    }
+
+    async fn shorten(&self, id: &str, expires: i64) -> Result<(), SessionUpdateError> {
+        self.invalidate(id);
+        let res = self.repository.shorten(id, expires).await;
+        self.invalidate(id);
+        res
+    }
+
+    async fn mark_authenticated(&self, id: &str, authenticated_at: i64) -> Result<(), SessionUpdateError> {
+        self.invalidate(id);
+        let res = self.repository.mark_authenticated(id, authenticated_at).await;
+        self.invalidate(id);
+        res
+    }
+
+    async fn delete_expired(&self, before: i64) -> Result<u64, SessionDeleteError> {
+        // cached entries never outlive the session, so there is nothing to invalidate
+        self.repository.delete_expired(before).await
+    }
+}
+
+#[cfg(test)]
+mod tests;
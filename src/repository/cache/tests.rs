@@ -0,0 +1,281 @@
+use mockall::predicate;
+
+use crate::{repository::sessions::MockSessionRepository, domain::{users::User, sessions::SessionBinding}};
+
+use super::*;
+
+fn config() -> SessionCacheConfig {
+    SessionCacheConfig {
+        capacity: 2,
+        ttl: Duration::from_secs(60),
+        negative_ttl: Duration::from_secs(60)
+    }
+}
+
+fn mock_session(id: &str, expires: i64) -> Session {
+    Session {
+        id: String::from(id),
+        user: User {
+            id: 1,
+            email: String::from("email"),
+            password_hash: String::from("hash")
+        },
+        expires,
+        authenticated_at: 0,
+        binding: SessionBinding::default()
+    }
+}
+
+fn expires_later() -> i64 {
+    Utc::now().timestamp() + 3600
+}
+
+#[tokio::test]
+async fn get_cached() {
+    let mut repository = MockSessionRepository::new();
+    repository
+        .expect_get()
+        .with(predicate::eq("id"))
+        .times(1)
+        .returning(|id| Ok(mock_session(id, expires_later())));
+
+    let cached = CachedSessionRepository::new(repository, config());
+    assert!(cached.get("id").await.is_ok());
+    assert!(cached.get("id").await.is_ok());
+    assert_eq!(CacheStats { hits: 1, misses: 1 }, cached.stats());
+}
+
+#[tokio::test]
+async fn get_negative_cached() {
+    let mut repository = MockSessionRepository::new();
+    repository
+        .expect_get()
+        .times(1)
+        .returning(|_| Err(SessionGetError::Missing));
+
+    let cached = CachedSessionRepository::new(repository, config());
+    assert!(matches!(cached.get("id").await, Err(SessionGetError::Missing)));
+    assert!(matches!(cached.get("id").await, Err(SessionGetError::Missing)));
+    assert_eq!(CacheStats { hits: 1, misses: 1 }, cached.stats());
+}
+
+#[tokio::test]
+async fn get_unknown_error_not_cached() {
+    let mut repository = MockSessionRepository::new();
+    repository
+        .expect_get()
+        .times(2)
+        .returning(|_| Err(SessionGetError::Unknown));
+
+    let cached = CachedSessionRepository::new(repository, config());
+    assert!(matches!(cached.get("id").await, Err(SessionGetError::Unknown)));
+    assert!(matches!(cached.get("id").await, Err(SessionGetError::Unknown)));
+}
+
+#[tokio::test]
+async fn get_ttl_capped_by_expires() {
+    let mut repository = MockSessionRepository::new();
+    repository
+        .expect_get()
+        .times(2)
+        .returning(|id| Ok(mock_session(id, Utc::now().timestamp())));
+
+    let cached = CachedSessionRepository::new(repository, config());
+    assert!(cached.get("id").await.is_ok());
+    assert!(cached.get("id").await.is_ok());
+    assert_eq!(CacheStats { hits: 0, misses: 2 }, cached.stats());
+}
+
+#[tokio::test]
+async fn get_ttl_elapsed() {
+    let mut repository = MockSessionRepository::new();
+    repository
+        .expect_get()
+        .times(2)
+        .returning(|id| Ok(mock_session(id, expires_later())));
+
+    let cached = CachedSessionRepository::new(repository, SessionCacheConfig { ttl: Duration::from_millis(10), ..config() });
+    assert!(cached.get("id").await.is_ok());
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(cached.get("id").await.is_ok());
+}
+
+#[tokio::test]
+async fn get_lru_eviction() {
+    let mut repository = MockSessionRepository::new();
+    repository
+        .expect_get()
+        .with(predicate::eq("first"))
+        .times(2)
+        .returning(|id| Ok(mock_session(id, expires_later())));
+    repository
+        .expect_get()
+        .returning(|id| Ok(mock_session(id, expires_later())));
+
+    let cached = CachedSessionRepository::new(repository, config());
+    for id in ["first", "second", "third", "first"] {
+        assert!(cached.get(id).await.is_ok());
+    }
+}
+
+#[tokio::test]
+async fn delete_invalidates() {
+    let mut repository = MockSessionRepository::new();
+    repository
+        .expect_get()
+        .times(1)
+        .returning(|id| Ok(mock_session(id, expires_later())));
+    repository
+        .expect_get()
+        .times(1)
+        .returning(|_| Err(SessionGetError::Missing));
+    repository
+        .expect_delete()
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let cached = CachedSessionRepository::new(repository, config());
+    assert!(cached.get("id").await.is_ok());
+    assert!(cached.delete("id").await.is_ok());
+    assert!(matches!(cached.get("id").await, Err(SessionGetError::Missing)));
+}
+
+#[tokio::test]
+async fn shorten_invalidates() {
+    let mut repository = MockSessionRepository::new();
+    repository
+        .expect_get()
+        .times(2)
+        .returning(|id| Ok(mock_session(id, expires_later())));
+    repository
+        .expect_shorten()
+        .with(predicate::eq("id"), predicate::eq(1000))
+        .times(1)
+        .returning(|_, _| Ok(()));
+
+    let cached = CachedSessionRepository::new(repository, config());
+    assert!(cached.get("id").await.is_ok());
+    assert!(cached.shorten("id", 1000).await.is_ok());
+    assert!(cached.get("id").await.is_ok());
+}
+
+#[tokio::test]
+async fn insert_invalidates_negative_entry() {
+    let mut repository = MockSessionRepository::new();
+    repository
+        .expect_get()
+        .times(1)
+        .returning(|_| Err(SessionGetError::Missing));
+    repository
+        .expect_insert()
+        .times(1)
+        .returning(|_| Ok(()));
+    repository
+        .expect_get()
+        .times(1)
+        .returning(|id| Ok(mock_session(id, expires_later())));
+
+    let cached = CachedSessionRepository::new(repository, config());
+    assert!(cached.get("id").await.is_err());
+    assert!(cached.insert(&SessionData { id: String::from("id"), user_id: 1, expires: expires_later(), authenticated_at: 0, binding: SessionBinding::default() }).await.is_ok());
+    assert!(cached.get("id").await.is_ok());
+}
+
+#[tokio::test]
+async fn disabled() {
+    let mut repository = MockSessionRepository::new();
+    repository
+        .expect_get()
+        .times(2)
+        .returning(|id| Ok(mock_session(id, expires_later())));
+
+    let cached = CachedSessionRepository::new(repository, SessionCacheConfig { capacity: 0, ..config() });
+    assert!(cached.get("id").await.is_ok());
+    assert!(cached.get("id").await.is_ok());
+    assert_eq!(CacheStats::default(), cached.stats());
+}
+
+/// Holds every `get` until released, so a test can run other calls while the read is in flight.
+#[derive(Debug, Default)]
+struct PausedRepository {
+    deleted: std::sync::atomic::AtomicBool,
+    started: tokio::sync::Notify,
+    release: tokio::sync::Notify
+}
+
+#[async_trait]
+impl SessionRepository for Arc<PausedRepository> {
+    async fn insert(&self, _: &SessionData) -> Result<(), SessionInsertError> {
+        Ok(())
+    }
+
+    async fn get(&self, id: &str) -> Result<Session, SessionGetError> {
+        // read before pausing, as a database would
+        let deleted = self.deleted.load(Ordering::SeqCst);
+        self.started.notify_one();
+        self.release.notified().await;
+        match deleted {
+            true => Err(SessionGetError::Missing),
+            false => Ok(mock_session(id, expires_later()))
+        }
+    }
+
+    async fn delete(&self, _: &str) -> Result<(), SessionDeleteError> {
+        self.deleted.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn shorten(&self, _: &str, _: i64) -> Result<(), SessionUpdateError> {
+        Ok(())
+    }
+
+    async fn mark_authenticated(&self, _: &str, _: i64) -> Result<(), SessionUpdateError> {
+        Ok(())
+    }
+
+    async fn delete_expired(&self, _: i64) -> Result<u64, SessionDeleteError> {
+        Ok(0)
+    }
+}
+
+#[tokio::test]
+async fn get_racing_delete_not_cached() {
+    let repository = Arc::new(PausedRepository::default());
+    let cached = CachedSessionRepository::new(repository.clone(), config());
+
+    let racing_get = tokio::spawn({
+        let cached = cached.clone();
+        async move { cached.get("id").await }
+    });
+    repository.started.notified().await;
+
+    // the delete completes while the read that still saw the session is in flight
+    assert!(cached.delete("id").await.is_ok());
+    repository.release.notify_one();
+    assert!(racing_get.await.unwrap().is_ok());
+
+    // the next read reaches the repository instead of the cache
+    repository.release.notify_one();
+    assert!(matches!(cached.get("id").await, Err(SessionGetError::Missing)));
+    assert_eq!(CacheStats { hits: 0, misses: 2 }, cached.stats());
+}
+
+#[tokio::test]
+async fn get_racing_revocation_not_cached() {
+    let repository = Arc::new(PausedRepository::default());
+    let cached = CachedSessionRepository::new(repository.clone(), config());
+
+    let racing_get = tokio::spawn({
+        let cached = cached.clone();
+        async move { cached.get("id").await }
+    });
+    repository.started.notified().await;
+
+    cached.evict(&Revocation::User { user_id: 1 });
+    repository.release.notify_one();
+    assert!(racing_get.await.unwrap().is_ok());
+
+    repository.release.notify_one();
+    assert!(cached.get("id").await.is_ok());
+    assert_eq!(CacheStats { hits: 0, misses: 2 }, cached.stats());
+}
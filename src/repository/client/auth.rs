@@ -0,0 +1,123 @@
+use std::{fmt::{self, Debug}, str::FromStr};
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::Utc;
+use http::{HeaderName, HeaderValue, header};
+use rand::Rng;
+use reqwest::Request;
+use ring::hmac;
+use sha2::{Digest, Sha256};
+
+pub const SIGNATURE_HEADER: &str = "x-signature";
+pub const SIGNATURE_TIMESTAMP_HEADER: &str = "x-signature-timestamp";
+pub const SIGNATURE_NONCE_HEADER: &str = "x-signature-nonce";
+/// Headers covered by the signature besides the nonce, `Authorization` carries the session or token id
+/// the request is about, so swapping it must invalidate the signature.
+pub const SIGNED_HEADERS: [HeaderName; 2] = [header::AUTHORIZATION, header::CONTENT_TYPE];
+
+const NONCE_LENGTH: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ServiceAuthMode {
+    None,
+    Secret,
+    Hmac
+}
+
+impl FromStr for ServiceAuthMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "secret" => Ok(Self::Secret),
+            "hmac" => Ok(Self::Hmac),
+            other => Err(format!("Unknown service auth mode: {}", other))
+        }
+    }
+}
+
+/// Credentials attached to every outbound request to resource-management.
+#[derive(Clone)]
+pub enum ServiceAuth {
+    None,
+    /// Sends the secret as is in the given header.
+    Secret { header: HeaderName, secret: HeaderValue },
+    /// Signs the request with HMAC-SHA256, see `string_to_sign`.
+    Hmac { key: hmac::Key }
+}
+
+impl Debug for ServiceAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "None"),
+            Self::Secret { header, .. } => f.debug_struct("Secret").field("header", header).finish_non_exhaustive(),
+            Self::Hmac { .. } => f.debug_struct("Hmac").finish_non_exhaustive()
+        }
+    }
+}
+
+impl ServiceAuth {
+    pub fn new(mode: ServiceAuthMode, header: &str, secret: &str) -> anyhow::Result<Self> {
+        if mode != ServiceAuthMode::None && secret.is_empty() {
+            anyhow::bail!("Service auth mode {:?} requires a secret", mode);
+        }
+
+        Ok(match mode {
+            ServiceAuthMode::None => Self::None,
+            ServiceAuthMode::Secret => {
+                let mut secret = HeaderValue::from_str(secret)?;
+                secret.set_sensitive(true);
+                Self::Secret { header: HeaderName::from_str(header)?, secret }
+            },
+            ServiceAuthMode::Hmac => Self::Hmac { key: hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes()) }
+        })
+    }
+
+    /// Newline separated method, path with query, unix timestamp, nonce, the values of `SIGNED_HEADERS`
+    /// (empty if absent) and hex SHA-256 of the body.
+    pub fn string_to_sign(request: &Request, timestamp: i64, nonce: &str) -> String {
+        let body = request.body().and_then(|body| body.as_bytes()).unwrap_or_default();
+        let url = request.url();
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_owned()
+        };
+        let headers = SIGNED_HEADERS
+            .iter()
+            .map(|name| request.headers().get(name).map(|value| value.as_bytes()).unwrap_or_default())
+            .map(String::from_utf8_lossy)
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        format!("{}\n{}\n{}\n{}\n{}\n{:x}", request.method(), path, timestamp, nonce, headers, Sha256::digest(body))
+    }
+
+    fn nonce() -> String {
+        rand::thread_rng()
+            .gen::<[u8; NONCE_LENGTH]>()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Must run for every attempt, a resent nonce is indistinguishable from a replay.
+    pub fn apply(&self, request: &mut Request) {
+        match self {
+            Self::None => (),
+            Self::Secret { header, secret } => {
+                request.headers_mut().insert(header.clone(), secret.clone());
+            },
+            Self::Hmac { key } => {
+                let timestamp = Utc::now().timestamp();
+                let nonce = Self::nonce();
+                let signature = hmac::sign(key, Self::string_to_sign(request, timestamp, &nonce).as_bytes());
+
+                let headers = request.headers_mut();
+                headers.insert(SIGNATURE_TIMESTAMP_HEADER, HeaderValue::from(timestamp));
+                headers.insert(SIGNATURE_NONCE_HEADER, HeaderValue::from_str(&nonce).unwrap());
+                headers.insert(SIGNATURE_HEADER, HeaderValue::from_str(&STANDARD.encode(signature.as_ref())).unwrap());
+            }
+        }
+    }
+}
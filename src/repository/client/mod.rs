@@ -0,0 +1,235 @@
+mod auth;
+
+use std::{fmt::{self, Display}, fs, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use http::Method;
+use rand::Rng;
+use reqwest::{Certificate, Client, Identity, IntoUrl, RequestBuilder, Response};
+use tracing::{error, info, warn};
+
+use crate::constants::{HTTP_CONNECT_TIMEOUT_MS, HTTP_REQUEST_TIMEOUT_MS, HTTP_MAX_RETRIES, HTTP_RETRY_BASE_DELAY_MS, HTTP_BREAKER_THRESHOLD, HTTP_BREAKER_COOLDOWN_MS, SERVICE_AUTH_MODE, SERVICE_AUTH_HEADER, SERVICE_AUTH_SECRET, SERVICE_TLS_CERT, SERVICE_TLS_KEY, SERVICE_TLS_CA};
+
+pub use self::auth::{ServiceAuth, ServiceAuthMode, SIGNATURE_HEADER, SIGNATURE_TIMESTAMP_HEADER, SIGNATURE_NONCE_HEADER, SIGNED_HEADERS};
+
+#[derive(Debug, Clone)]
+pub struct HttpClientConfig {
+    pub auth: ServiceAuth,
+    /// Client certificate for mutual TLS.
+    pub identity: Option<Identity>,
+    /// Extra CA trusted when resource-management uses a private one.
+    pub root_certificate: Option<Certificate>,
+    pub connect_timeout: Duration,
+    pub request_timeout: Duration,
+    /// Retries on top of the first attempt, only used for idempotent requests.
+    pub max_retries: u32,
+    pub retry_base_delay: Duration,
+    /// Consecutive failed calls after which the breaker opens.
+    pub breaker_threshold: u32,
+    pub breaker_cooldown: Duration
+}
+
+impl HttpClientConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let identity = match (SERVICE_TLS_CERT.as_str(), SERVICE_TLS_KEY.as_str()) {
+            ("", "") => None,
+            ("", _) | (_, "") => anyhow::bail!("Both SERVICE_TLS_CERT and SERVICE_TLS_KEY must be set"),
+            (cert, key) => Some(Identity::from_pkcs8_pem(&fs::read(cert)?, &fs::read(key)?)?)
+        };
+        let root_certificate = match SERVICE_TLS_CA.as_str() {
+            "" => None,
+            ca => Some(Certificate::from_pem(&fs::read(ca)?)?)
+        };
+
+        Ok(Self {
+            auth: ServiceAuth::new(*SERVICE_AUTH_MODE, SERVICE_AUTH_HEADER.as_str(), SERVICE_AUTH_SECRET.as_str())?,
+            identity,
+            root_certificate,
+            connect_timeout: Duration::from_millis(*HTTP_CONNECT_TIMEOUT_MS),
+            request_timeout: Duration::from_millis(*HTTP_REQUEST_TIMEOUT_MS),
+            max_retries: *HTTP_MAX_RETRIES,
+            retry_base_delay: Duration::from_millis(*HTTP_RETRY_BASE_DELAY_MS),
+            breaker_threshold: *HTTP_BREAKER_THRESHOLD,
+            breaker_cooldown: Duration::from_millis(*HTTP_BREAKER_COOLDOWN_MS)
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum HttpError {
+    CircuitOpen,
+    Request(reqwest::Error)
+}
+
+impl Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CircuitOpen => write!(f, "circuit breaker is open"),
+            Self::Request(err) => err.fmt(f)
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    failures: u32,
+    open_until: Option<Instant>
+}
+
+#[derive(Debug)]
+struct CircuitBreaker {
+    state: Mutex<BreakerState>,
+    threshold: u32,
+    cooldown: Duration
+}
+
+impl CircuitBreaker {
+    /// Once the cooldown passes a single caller is let through as a probe,
+    /// everyone else keeps failing fast until it reports back. Takes the time so tests don't have to wait out the cooldown.
+    fn try_acquire(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match state.open_until {
+            Some(open_until) if open_until > now => false,
+            Some(_) => {
+                state.open_until = Some(now + self.cooldown);
+                true
+            },
+            None => true
+        }
+    }
+
+    fn record(&self, success: bool, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        if success {
+            if state.open_until.is_some() {
+                info!("Circuit breaker closed");
+            }
+            *state = BreakerState::default();
+            return;
+        }
+
+        state.failures += 1;
+        if state.failures >= self.threshold {
+            warn!("Circuit breaker opened after {} failures", state.failures);
+            state.open_until = Some(now + self.cooldown);
+        }
+    }
+}
+
+/// Client for resource-management shared by all HTTP repositories, so they also share one breaker.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    client: Client,
+    auth: Arc<ServiceAuth>,
+    breaker: Arc<CircuitBreaker>,
+    max_retries: u32,
+    retry_base_delay: Duration
+}
+
+impl HttpClient {
+    pub fn new(config: HttpClientConfig) -> Self {
+        let mut builder = Client::builder()
+            .connect_timeout(config.connect_timeout)
+            .timeout(config.request_timeout);
+        if let Some(identity) = config.identity {
+            builder = builder.identity(identity);
+        }
+        if let Some(certificate) = config.root_certificate {
+            builder = builder.add_root_certificate(certificate);
+        }
+
+        Self {
+            client: builder.build().unwrap(),
+            auth: Arc::new(config.auth),
+            breaker: Arc::new(CircuitBreaker {
+                state: Mutex::new(BreakerState::default()),
+                threshold: config.breaker_threshold,
+                cooldown: config.breaker_cooldown
+            }),
+            max_retries: config.max_retries,
+            retry_base_delay: config.retry_base_delay
+        }
+    }
+
+    pub fn get(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.client.get(url)
+    }
+
+    pub fn post(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.client.post(url)
+    }
+
+    pub fn patch(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.client.patch(url)
+    }
+
+    pub fn delete(&self, url: impl IntoUrl) -> RequestBuilder {
+        self.client.delete(url)
+    }
+
+    fn is_retryable(res: &Result<Response, reqwest::Error>) -> bool {
+        match res {
+            Ok(res) => res.status().is_server_error(),
+            Err(err) => err.is_timeout() || err.is_connect()
+        }
+    }
+
+    fn is_failure(res: &Result<Response, reqwest::Error>) -> bool {
+        match res {
+            Ok(res) => res.status().is_server_error(),
+            Err(_) => true
+        }
+    }
+
+    /// Exponential backoff with up to one base delay of jitter, so retrying callers spread out.
+    fn retry_delay(&self, attempt: u32) -> Duration {
+        let base = self.retry_base_delay.as_millis() as u64;
+        let jitter = rand::thread_rng().gen_range(0..=base);
+        Duration::from_millis((base << attempt) + jitter)
+    }
+
+    pub async fn send(&self, req: RequestBuilder) -> Result<Response, HttpError> {
+        let (client, request) = req.build_split();
+        let mut request = request.map_err(HttpError::Request)?;
+
+        if !self.breaker.try_acquire(Instant::now()) {
+            warn!("Circuit breaker is open, failing fast");
+            return Err(HttpError::CircuitOpen);
+        }
+
+        let retries = match *request.method() {
+            Method::GET | Method::DELETE => self.max_retries,
+            _ => 0
+        };
+
+        let url = request.url().clone();
+        let mut attempt = 0;
+        let res = loop {
+            // bodies of idempotent requests are never streams, so cloning them does not fail
+            let retry = if attempt < retries { request.try_clone() } else { None };
+            let Some(mut current) = retry else {
+                self.auth.apply(&mut request);
+                break client.execute(request).await;
+            };
+            // signed per attempt so that every retry carries a fresh nonce
+            self.auth.apply(&mut current);
+
+            let res = client.execute(current).await;
+            if !Self::is_retryable(&res) {
+                break res;
+            }
+
+            warn!("Attempt {} to {} failed, retrying", attempt + 1, url);
+            tokio::time::sleep(self.retry_delay(attempt)).await;
+            attempt += 1;
+        };
+
+        self.breaker.record(!Self::is_failure(&res), Instant::now());
+        res.map_err(|err| {
+            error!("Request to {} failed", url);
+            HttpError::Request(err)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,280 @@
+use std::{net::{SocketAddr, TcpListener}, sync::atomic::{AtomicUsize, Ordering}};
+
+use axum::{Router, routing, Extension};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use http::{HeaderMap, StatusCode};
+use ring::hmac;
+
+use crate::repository::users::{HttpUserRepository, UserRepository, UserGetError};
+
+use chrono::Utc;
+
+use super::*;
+
+fn config() -> HttpClientConfig {
+    HttpClientConfig {
+        auth: ServiceAuth::None,
+        identity: None,
+        root_certificate: None,
+        connect_timeout: Duration::from_millis(100),
+        request_timeout: Duration::from_millis(100),
+        max_retries: 2,
+        retry_base_delay: Duration::from_millis(1),
+        breaker_threshold: 2,
+        breaker_cooldown: Duration::from_millis(100)
+    }
+}
+
+/// Counts every hit, `/flaky` keeps failing until it has been hit three times.
+async fn stub_server() -> (SocketAddr, Arc<AtomicUsize>) {
+    let hits = Arc::new(AtomicUsize::new(0));
+
+    let router = Router::new()
+        .route("/ok", routing::get(|| async { StatusCode::OK }).post(|| async { StatusCode::CREATED }))
+        .route("/unavailable", routing::get(|| async { StatusCode::SERVICE_UNAVAILABLE }).post(|| async { StatusCode::SERVICE_UNAVAILABLE }))
+        .route("/flaky", routing::get(|Extension(hits): Extension<Arc<AtomicUsize>>| async move {
+            match hits.load(Ordering::SeqCst) {
+                n if n < 3 => StatusCode::SERVICE_UNAVAILABLE,
+                _ => StatusCode::OK
+            }
+        }))
+        .route("/slow", routing::get(|| async {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            StatusCode::OK
+        }))
+        .route("/headers", routing::get(|headers: HeaderMap| async move {
+            let header = |name: &str| headers.get(name).map(|value| value.to_str().unwrap().to_owned()).unwrap_or_default();
+            format!("{}\n{}\n{}", header("x-service-token"), header(SIGNATURE_TIMESTAMP_HEADER), header(SIGNATURE_HEADER))
+        }))
+        .route("/signature", routing::get(|headers: HeaderMap| async move {
+            let header = |name: &str| headers.get(name).map(|value| value.to_str().unwrap().to_owned()).unwrap_or_default();
+            format!("{}\n{}\n{}", header(SIGNATURE_TIMESTAMP_HEADER), header(SIGNATURE_NONCE_HEADER), header(SIGNATURE_HEADER))
+        }))
+        .route("/users/:email", routing::get(|| async { StatusCode::SERVICE_UNAVAILABLE }))
+        .layer(axum::middleware::from_fn({
+            let hits = hits.clone();
+            move |req, next: axum::middleware::Next<axum::body::Body>| {
+                hits.fetch_add(1, Ordering::SeqCst);
+                next.run(req)
+            }
+        }))
+        .layer(Extension(hits.clone()));
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(axum::Server::from_tcp(listener).unwrap().serve(router.into_make_service()));
+
+    (addr, hits)
+}
+
+fn url(addr: SocketAddr, path: &str) -> String {
+    format!("http://{}{}", addr, path)
+}
+
+#[tokio::test]
+async fn get_normal() {
+    let (addr, hits) = stub_server().await;
+    let client = HttpClient::new(config());
+
+    let res = client.send(client.get(url(addr, "/ok"))).await.unwrap();
+    assert_eq!(StatusCode::OK, res.status());
+    assert_eq!(1, hits.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn get_retries_until_success() {
+    let (addr, hits) = stub_server().await;
+    let client = HttpClient::new(config());
+
+    let res = client.send(client.get(url(addr, "/flaky"))).await.unwrap();
+    assert_eq!(StatusCode::OK, res.status());
+    assert_eq!(3, hits.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn get_retries_exhausted() {
+    let (addr, hits) = stub_server().await;
+    let client = HttpClient::new(config());
+
+    let res = client.send(client.get(url(addr, "/unavailable"))).await.unwrap();
+    assert_eq!(StatusCode::SERVICE_UNAVAILABLE, res.status());
+    assert_eq!(3, hits.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn post_not_retried() {
+    let (addr, hits) = stub_server().await;
+    let client = HttpClient::new(config());
+
+    let res = client.send(client.post(url(addr, "/unavailable"))).await.unwrap();
+    assert_eq!(StatusCode::SERVICE_UNAVAILABLE, res.status());
+    assert_eq!(1, hits.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn request_timeout() {
+    let (addr, _) = stub_server().await;
+    let client = HttpClient::new(HttpClientConfig { max_retries: 0, ..config() });
+
+    let started = Instant::now();
+    let res = client.send(client.get(url(addr, "/slow"))).await;
+    assert!(matches!(res, Err(HttpError::Request(err)) if err.is_timeout()));
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn breaker_opens_and_fails_fast() {
+    let (addr, hits) = stub_server().await;
+    let client = HttpClient::new(HttpClientConfig { max_retries: 0, ..config() });
+
+    for _ in 0..2 {
+        assert!(client.send(client.post(url(addr, "/unavailable"))).await.is_ok());
+    }
+    assert!(matches!(client.send(client.post(url(addr, "/ok"))).await, Err(HttpError::CircuitOpen)));
+    assert_eq!(2, hits.load(Ordering::SeqCst));
+}
+
+fn breaker() -> CircuitBreaker {
+    CircuitBreaker {
+        state: Mutex::default(),
+        threshold: 2,
+        cooldown: Duration::from_secs(30)
+    }
+}
+
+fn open(breaker: &CircuitBreaker, now: Instant) {
+    for _ in 0..breaker.threshold {
+        assert!(breaker.try_acquire(now));
+        breaker.record(false, now);
+    }
+}
+
+#[test]
+fn breaker_closes_after_successful_probe() {
+    let breaker = breaker();
+    let opened = Instant::now();
+    open(&breaker, opened);
+    assert!(!breaker.try_acquire(opened + breaker.cooldown - Duration::from_millis(1)));
+
+    let probed = opened + breaker.cooldown;
+    assert!(breaker.try_acquire(probed));
+    // only the probe goes through until it reports back
+    assert!(!breaker.try_acquire(probed));
+    breaker.record(true, probed);
+
+    assert!(breaker.try_acquire(probed));
+    assert!(breaker.try_acquire(probed));
+}
+
+#[test]
+fn breaker_reopens_after_failed_probe() {
+    let breaker = breaker();
+    let opened = Instant::now();
+    open(&breaker, opened);
+
+    let probed = opened + breaker.cooldown;
+    assert!(breaker.try_acquire(probed));
+    breaker.record(false, probed);
+
+    assert!(!breaker.try_acquire(probed + breaker.cooldown - Duration::from_millis(1)));
+    assert!(breaker.try_acquire(probed + breaker.cooldown));
+}
+
+#[tokio::test]
+async fn repository_fails_fast_when_open() {
+    let (addr, hits) = stub_server().await;
+    let client = HttpClient::new(HttpClientConfig { max_retries: 0, ..config() });
+    let repository = HttpUserRepository::new(&url(addr, "/users"), client);
+
+    for _ in 0..3 {
+        assert!(matches!(repository.get_by_email("email@email.com").await, Err(UserGetError::Unknown)));
+    }
+    assert_eq!(2, hits.load(Ordering::SeqCst));
+}
+
+async fn received_headers(client: &HttpClient, addr: SocketAddr) -> Vec<String> {
+    let res = client.send(client.get(url(addr, "/headers"))).await.unwrap();
+    res.text().await.unwrap().split('\n').map(String::from).collect()
+}
+
+#[tokio::test]
+async fn auth_none() {
+    let (addr, _) = stub_server().await;
+    let client = HttpClient::new(config());
+
+    assert_eq!(vec!["", "", ""], received_headers(&client, addr).await);
+}
+
+#[tokio::test]
+async fn auth_secret() {
+    let (addr, _) = stub_server().await;
+    let auth = ServiceAuth::new(ServiceAuthMode::Secret, "X-Service-Token", "secret").unwrap();
+    let client = HttpClient::new(HttpClientConfig { auth, ..config() });
+
+    assert_eq!(vec!["secret", "", ""], received_headers(&client, addr).await);
+}
+
+async fn received_signature(client: &HttpClient, addr: SocketAddr, bearer: &str) -> (i64, String, Vec<u8>) {
+    let res = client.send(client.get(url(addr, "/signature")).bearer_auth(bearer)).await.unwrap();
+    let body = res.text().await.unwrap();
+    let parts: Vec<_> = body.split('\n').collect();
+    (parts[0].parse().unwrap(), String::from(parts[1]), STANDARD.decode(parts[2]).unwrap())
+}
+
+#[tokio::test]
+async fn auth_hmac() {
+    let (addr, _) = stub_server().await;
+    let auth = ServiceAuth::new(ServiceAuthMode::Hmac, "", "secret").unwrap();
+    let client = HttpClient::new(HttpClientConfig { auth, ..config() });
+
+    let (timestamp, nonce, signature) = received_signature(&client, addr, "session").await;
+    assert!((Utc::now().timestamp() - timestamp).abs() <= 1);
+    assert_eq!(32, nonce.len());
+
+    let request = client.get(url(addr, "/signature")).bearer_auth("session").build().unwrap();
+    let key = hmac::Key::new(hmac::HMAC_SHA256, b"secret");
+    assert!(hmac::verify(&key, ServiceAuth::string_to_sign(&request, timestamp, &nonce).as_bytes(), &signature).is_ok());
+}
+
+#[tokio::test]
+async fn auth_hmac_covers_bearer() {
+    let (addr, _) = stub_server().await;
+    let auth = ServiceAuth::new(ServiceAuthMode::Hmac, "", "secret").unwrap();
+    let client = HttpClient::new(HttpClientConfig { auth, ..config() });
+
+    let (timestamp, nonce, signature) = received_signature(&client, addr, "session").await;
+
+    let swapped = client.get(url(addr, "/signature")).bearer_auth("other_session").build().unwrap();
+    let key = hmac::Key::new(hmac::HMAC_SHA256, b"secret");
+    assert!(hmac::verify(&key, ServiceAuth::string_to_sign(&swapped, timestamp, &nonce).as_bytes(), &signature).is_err());
+}
+
+#[tokio::test]
+async fn auth_hmac_fresh_nonce() {
+    let (addr, _) = stub_server().await;
+    let auth = ServiceAuth::new(ServiceAuthMode::Hmac, "", "secret").unwrap();
+    let client = HttpClient::new(HttpClientConfig { auth, ..config() });
+
+    let (_, first, _) = received_signature(&client, addr, "session").await;
+    let (_, second, _) = received_signature(&client, addr, "session").await;
+    assert_ne!(first, second);
+}
+
+#[test]
+fn string_to_sign_covers_signed_headers() {
+    let client = reqwest::Client::new();
+    let request = |bearer: &str| client.get("http://localhost/sessions").bearer_auth(bearer).build().unwrap();
+
+    assert_ne!(ServiceAuth::string_to_sign(&request("a"), 1, "nonce"), ServiceAuth::string_to_sign(&request("b"), 1, "nonce"));
+    assert_ne!(ServiceAuth::string_to_sign(&request("a"), 1, "nonce"), ServiceAuth::string_to_sign(&request("a"), 1, "other"));
+    assert_eq!(
+        "GET\n/sessions\n1\nnonce\nBearer a\n\ne3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        ServiceAuth::string_to_sign(&request("a"), 1, "nonce")
+    );
+}
+
+#[test]
+fn auth_requires_secret() {
+    assert!(ServiceAuth::new(ServiceAuthMode::Hmac, "", "").is_err());
+    assert!(ServiceAuth::new(ServiceAuthMode::None, "", "").is_ok());
+}
@@ -0,0 +1,453 @@
+use std::{collections::HashMap, fs, io, path::{Path, PathBuf}, sync::{Arc, RwLock, atomic::{AtomicBool, Ordering}}, time::Duration};
+
+use axum::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::{sync::{Notify, watch}, task::JoinHandle};
+use tracing::{error, info, warn};
+
+use crate::domain::{sessions::{Session, SessionData}, users::{User, UserData}, personal_tokens::{PersonalToken, PersonalTokenData}, tokens::{RefreshToken, RefreshTokenData}};
+
+use super::{users::{UserRepository, UserGetError, UserInsertError, UserUpdateError}, sessions::{SessionRepository, SessionGetError, SessionDeleteError, SessionInsertError, SessionUpdateError}, personal_tokens::{PersonalTokenRepository, PersonalTokenInsertError, PersonalTokenGetError, PersonalTokenDeleteError}, refresh_tokens::{RefreshTokenRepository, RefreshTokenInsertError, RefreshTokenGetError, RefreshTokenUseError, RefreshTokenRevokeError}};
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct MemoryState {
+    next_user_id: i32,
+    users: HashMap<String, User>,
+    sessions: HashMap<String, SessionData>,
+    // defaulted so that snapshots written before tokens were kept in memory still load
+    #[serde(default)]
+    next_personal_token_id: i32,
+    /// Keyed by token hash.
+    #[serde(default)]
+    personal_tokens: HashMap<String, PersonalToken>,
+    #[serde(default)]
+    refresh_tokens: HashMap<String, RefreshToken>
+}
+
+impl MemoryState {
+    fn has_user(&self, id: i32) -> bool {
+        self.users.values().any(|user| user.id == id)
+    }
+}
+
+/// Storage shared by the in-memory repositories, optionally snapshotted to a JSON file by `spawn_snapshot_writer`.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    state: RwLock<MemoryState>,
+    snapshot_path: Option<PathBuf>,
+    dirty: AtomicBool,
+    changed: Notify
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restores the state from `path` if it exists, later changes are written back to it.
+    pub fn with_snapshot(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let state = match fs::read(path) {
+            Ok(contents) => {
+                info!("Restoring in-memory repositories from {:?}", path);
+                serde_json::from_slice(&contents)?
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => MemoryState::default(),
+            Err(err) => return Err(err.into())
+        };
+
+        Ok(Self {
+            state: RwLock::new(state),
+            snapshot_path: Some(path.to_path_buf()),
+            ..Self::default()
+        })
+    }
+
+    /// Called with the write lock held, the snapshot itself is written later without it.
+    fn persist(&self) {
+        if self.snapshot_path.is_some() {
+            self.dirty.store(true, Ordering::Release);
+            self.changed.notify_one();
+        }
+    }
+
+    /// Snapshots are best effort, a failed write is logged and the change is kept in memory.
+    async fn write_snapshot(&self) {
+        let Some(path) = self.snapshot_path.clone() else {
+            return;
+        };
+        if !self.dirty.swap(false, Ordering::AcqRel) {
+            return;
+        }
+
+        let contents = match serde_json::to_vec(&*self.state.read().unwrap()) {
+            Ok(contents) => contents,
+            Err(err) => {
+                error!("Failed to serialize snapshot: {}", err);
+                return;
+            }
+        };
+
+        // write to a sibling file first so a crash never leaves a truncated snapshot behind
+        let res = tokio::task::spawn_blocking(move || {
+            let tmp_path = path.with_extension("tmp");
+            fs::write(&tmp_path, contents)
+                .and_then(|_| fs::rename(&tmp_path, &path))
+                .map_err(|err| format!("Failed to write snapshot to {:?}: {}", path, err))
+        }).await;
+
+        match res {
+            Ok(Ok(())) => (),
+            Ok(Err(err)) => error!(err),
+            Err(err) => error!(%err)
+        }
+    }
+}
+
+/// Writes the snapshot at most once per `debounce` while changes keep coming, and a last time on shutdown.
+/// Changes made within `debounce` of a crash are lost.
+pub fn spawn_snapshot_writer(store: Arc<MemoryStore>, debounce: Duration, mut shutdown: watch::Receiver<bool>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = store.changed.notified() => tokio::select! {
+                    _ = tokio::time::sleep(debounce) => (),
+                    _ = shutdown.changed() => ()
+                },
+                _ = shutdown.changed() => ()
+            }
+
+            store.write_snapshot().await;
+            if *shutdown.borrow() {
+                info!("Snapshot writer stopped");
+                return;
+            }
+        }
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct MemoryUserRepository {
+    store: Arc<MemoryStore>
+}
+
+impl MemoryUserRepository {
+    pub fn new(store: Arc<MemoryStore>) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl UserRepository for MemoryUserRepository {
+    #[tracing::instrument(skip_all, fields(email = user_data.email))]
+    async fn insert(&self, user_data: UserData) -> Result<(), UserInsertError> {
+        let mut state = self.store.state.write().unwrap();
+        if state.users.contains_key(&user_data.email) {
+            warn!("Duplicate user");
+            return Err(UserInsertError::Duplicate);
+        }
+
+        state.next_user_id += 1;
+        let user = User {
+            id: state.next_user_id,
+            email: user_data.email,
+            password_hash: user_data.password_hash
+        };
+        state.users.insert(user.email.clone(), user);
+
+        self.store.persist();
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn get_by_email(&self, email: &str) -> Result<User, UserGetError> {
+        match self.store.state.read().unwrap().users.get(email) {
+            Some(user) => Ok(user.clone()),
+            None => {
+                warn!("Missing user");
+                Err(UserGetError::Missing)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(self, password_hash))]
+    async fn update_password(&self, id: i32, password_hash: &str) -> Result<(), UserUpdateError> {
+        let mut state = self.store.state.write().unwrap();
+        let Some(user) = state.users.values_mut().find(|user| user.id == id) else {
+            warn!("Missing user");
+            return Err(UserUpdateError::Missing);
+        };
+        user.password_hash = String::from(password_hash);
+
+        self.store.persist();
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MemorySessionRepository {
+    store: Arc<MemoryStore>
+}
+
+impl MemorySessionRepository {
+    pub fn new(store: Arc<MemoryStore>) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl SessionRepository for MemorySessionRepository {
+    #[tracing::instrument(skip_all, fields(user_id = session_data.user_id))]
+    async fn insert(&self, session_data: &SessionData) -> Result<(), SessionInsertError> {
+        let mut state = self.store.state.write().unwrap();
+        if state.sessions.contains_key(&session_data.id) {
+            warn!("Duplicate session {:?}", session_data);
+            return Err(SessionInsertError::Duplicate);
+        }
+        if !state.has_user(session_data.user_id) {
+            error!("Session references missing user {}", session_data.user_id);
+            return Err(SessionInsertError::Unknown);
+        }
+
+        state.sessions.insert(session_data.id.clone(), session_data.clone());
+
+        self.store.persist();
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn get(&self, id: &str) -> Result<Session, SessionGetError> {
+        let state = self.store.state.read().unwrap();
+        let session = state.sessions
+            .get(id)
+            .and_then(|session| state.users
+                .values()
+                .find(|user| user.id == session.user_id)
+                .map(|user| Session {
+                    id: session.id.clone(),
+                    user: user.clone(),
+                    expires: session.expires,
+                    authenticated_at: session.authenticated_at,
+                    binding: session.binding.clone()
+                })
+            );
+
+        match session {
+            Some(session) => Ok(session),
+            None => {
+                warn!("Missing session {:?}", id);
+                Err(SessionGetError::Missing)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn delete(&self, id: &str) -> Result<(), SessionDeleteError> {
+        let mut state = self.store.state.write().unwrap();
+        if state.sessions.remove(id).is_some() {
+            self.store.persist();
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn shorten(&self, id: &str, expires: i64) -> Result<(), SessionUpdateError> {
+        let mut state = self.store.state.write().unwrap();
+        let Some(session) = state.sessions.get_mut(id) else {
+            warn!("Missing session {:?}", id);
+            return Err(SessionUpdateError::Missing);
+        };
+        session.expires = session.expires.min(expires);
+
+        self.store.persist();
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn mark_authenticated(&self, id: &str, authenticated_at: i64) -> Result<(), SessionUpdateError> {
+        let mut state = self.store.state.write().unwrap();
+        let Some(session) = state.sessions.get_mut(id) else {
+            warn!("Missing session {:?}", id);
+            return Err(SessionUpdateError::Missing);
+        };
+        session.authenticated_at = authenticated_at;
+
+        self.store.persist();
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn delete_expired(&self, before: i64) -> Result<u64, SessionDeleteError> {
+        let mut state = self.store.state.write().unwrap();
+        let count = state.sessions.len();
+        state.sessions.retain(|_, session| session.expires >= before);
+
+        let deleted = count - state.sessions.len();
+        if deleted > 0 {
+            self.store.persist();
+        }
+        Ok(deleted as u64)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MemoryPersonalTokenRepository {
+    store: Arc<MemoryStore>
+}
+
+impl MemoryPersonalTokenRepository {
+    pub fn new(store: Arc<MemoryStore>) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl PersonalTokenRepository for MemoryPersonalTokenRepository {
+    #[tracing::instrument(skip_all, fields(user_id = token_data.user_id))]
+    async fn insert(&self, token_data: &PersonalTokenData) -> Result<PersonalToken, PersonalTokenInsertError> {
+        let mut state = self.store.state.write().unwrap();
+        if state.personal_tokens.contains_key(&token_data.token_hash) {
+            warn!("Duplicate personal token");
+            return Err(PersonalTokenInsertError::Duplicate);
+        }
+        if !state.has_user(token_data.user_id) {
+            error!("Personal token references missing user {}", token_data.user_id);
+            return Err(PersonalTokenInsertError::Unknown);
+        }
+
+        state.next_personal_token_id += 1;
+        let token = PersonalToken {
+            id: state.next_personal_token_id,
+            user_id: token_data.user_id,
+            name: token_data.name.clone(),
+            scopes: token_data.scopes.clone(),
+            expires: token_data.expires
+        };
+        state.personal_tokens.insert(token_data.token_hash.clone(), token.clone());
+
+        self.store.persist();
+        Ok(token)
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn get_by_hash(&self, token_hash: &str) -> Result<PersonalToken, PersonalTokenGetError> {
+        match self.store.state.read().unwrap().personal_tokens.get(token_hash) {
+            Some(token) => Ok(token.clone()),
+            None => {
+                warn!("Missing personal token");
+                Err(PersonalTokenGetError::Missing)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn list(&self, user_id: i32) -> Result<Vec<PersonalToken>, PersonalTokenGetError> {
+        let mut tokens: Vec<_> = self.store.state.read().unwrap().personal_tokens
+            .values()
+            .filter(|token| token.user_id == user_id)
+            .cloned()
+            .collect();
+        tokens.sort_by_key(|token| token.id);
+        Ok(tokens)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn delete(&self, user_id: i32, id: i32) -> Result<(), PersonalTokenDeleteError> {
+        let mut state = self.store.state.write().unwrap();
+        let count = state.personal_tokens.len();
+        state.personal_tokens.retain(|_, token| token.id != id || token.user_id != user_id);
+
+        if state.personal_tokens.len() == count {
+            warn!("Missing personal token");
+            return Err(PersonalTokenDeleteError::Missing);
+        }
+
+        self.store.persist();
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MemoryRefreshTokenRepository {
+    store: Arc<MemoryStore>
+}
+
+impl MemoryRefreshTokenRepository {
+    pub fn new(store: Arc<MemoryStore>) -> Self {
+        Self { store }
+    }
+}
+
+#[async_trait]
+impl RefreshTokenRepository for MemoryRefreshTokenRepository {
+    #[tracing::instrument(skip_all, fields(user_id = token_data.user_id))]
+    async fn insert(&self, token_data: &RefreshTokenData) -> Result<(), RefreshTokenInsertError> {
+        let mut state = self.store.state.write().unwrap();
+        if state.refresh_tokens.contains_key(&token_data.id) {
+            warn!("Duplicate refresh token");
+            return Err(RefreshTokenInsertError::Duplicate);
+        }
+        if !state.has_user(token_data.user_id) {
+            error!("Refresh token references missing user {}", token_data.user_id);
+            return Err(RefreshTokenInsertError::Unknown);
+        }
+
+        state.refresh_tokens.insert(token_data.id.clone(), RefreshToken {
+            id: token_data.id.clone(),
+            family_id: token_data.family_id.clone(),
+            user_id: token_data.user_id,
+            session_id: token_data.session_id.clone(),
+            expires: token_data.expires,
+            used: false
+        });
+
+        self.store.persist();
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn get(&self, id: &str) -> Result<RefreshToken, RefreshTokenGetError> {
+        match self.store.state.read().unwrap().refresh_tokens.get(id) {
+            Some(token) => Ok(token.clone()),
+            None => {
+                warn!("Missing refresh token");
+                Err(RefreshTokenGetError::Missing)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn mark_used(&self, id: &str) -> Result<(), RefreshTokenUseError> {
+        // checked and set under the same write lock, so only one of two concurrent uses succeeds
+        let mut state = self.store.state.write().unwrap();
+        match state.refresh_tokens.get_mut(id) {
+            Some(token) if token.used => {
+                warn!("Refresh token already used");
+                return Err(RefreshTokenUseError::AlreadyUsed);
+            },
+            Some(token) => token.used = true,
+            None => {
+                warn!("Missing refresh token");
+                return Err(RefreshTokenUseError::Missing);
+            }
+        };
+
+        self.store.persist();
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn revoke_family(&self, family_id: &str) -> Result<(), RefreshTokenRevokeError> {
+        let mut state = self.store.state.write().unwrap();
+        let count = state.refresh_tokens.len();
+        state.refresh_tokens.retain(|_, token| token.family_id != family_id);
+
+        if state.refresh_tokens.len() != count {
+            self.store.persist();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests;
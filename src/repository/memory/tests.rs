@@ -0,0 +1,272 @@
+use std::env;
+
+use crate::{domain::sessions::SessionBinding, tasks::BackgroundTasks};
+
+use super::*;
+
+fn mock_user_data() -> UserData {
+    UserData {
+        email: String::from("email@email.com"),
+        password_hash: String::from("hash")
+    }
+}
+
+fn mock_session_data(user_id: i32) -> SessionData {
+    SessionData {
+        id: String::from("session"),
+        user_id,
+        expires: 1000,
+        authenticated_at: 0,
+        binding: SessionBinding::default()
+    }
+}
+
+fn repositories(store: MemoryStore) -> (MemoryUserRepository, MemorySessionRepository) {
+    let store = Arc::new(store);
+    (MemoryUserRepository::new(store.clone()), MemorySessionRepository::new(store))
+}
+
+#[tokio::test]
+async fn users_insert_and_get() {
+    let (users, _) = repositories(MemoryStore::new());
+
+    assert!(users.insert(mock_user_data()).await.is_ok());
+
+    let user = users.get_by_email("email@email.com").await.ok().unwrap();
+    assert_eq!(1, user.id);
+    assert_eq!("hash", user.password_hash);
+}
+
+#[tokio::test]
+async fn users_insert_duplicate() {
+    let (users, _) = repositories(MemoryStore::new());
+
+    assert!(users.insert(mock_user_data()).await.is_ok());
+    assert!(matches!(users.insert(mock_user_data()).await, Err(UserInsertError::Duplicate)));
+}
+
+#[tokio::test]
+async fn users_get_missing() {
+    let (users, _) = repositories(MemoryStore::new());
+
+    assert!(matches!(users.get_by_email("email@email.com").await, Err(UserGetError::Missing)));
+}
+
+#[tokio::test]
+async fn sessions_insert_and_get() {
+    let (users, sessions) = repositories(MemoryStore::new());
+    users.insert(mock_user_data()).await.ok().unwrap();
+
+    assert!(sessions.insert(&mock_session_data(1)).await.is_ok());
+
+    let session = sessions.get("session").await.ok().unwrap();
+    assert_eq!(1, session.user.id);
+    assert_eq!(1000, session.expires);
+}
+
+#[tokio::test]
+async fn sessions_insert_duplicate() {
+    let (users, sessions) = repositories(MemoryStore::new());
+    users.insert(mock_user_data()).await.ok().unwrap();
+
+    assert!(sessions.insert(&mock_session_data(1)).await.is_ok());
+    assert!(matches!(sessions.insert(&mock_session_data(1)).await, Err(SessionInsertError::Duplicate)));
+}
+
+#[tokio::test]
+async fn sessions_insert_missing_user() {
+    let (_, sessions) = repositories(MemoryStore::new());
+
+    assert!(matches!(sessions.insert(&mock_session_data(1)).await, Err(SessionInsertError::Unknown)));
+}
+
+#[tokio::test]
+async fn sessions_delete() {
+    let (users, sessions) = repositories(MemoryStore::new());
+    users.insert(mock_user_data()).await.ok().unwrap();
+    sessions.insert(&mock_session_data(1)).await.ok().unwrap();
+
+    assert!(sessions.delete("session").await.is_ok());
+    assert!(matches!(sessions.get("session").await, Err(SessionGetError::Missing)));
+    assert!(sessions.delete("session").await.is_ok());
+}
+
+#[tokio::test]
+async fn sessions_delete_expired() {
+    let (users, sessions) = repositories(MemoryStore::new());
+    users.insert(mock_user_data()).await.ok().unwrap();
+    sessions.insert(&mock_session_data(1)).await.ok().unwrap();
+    sessions.insert(&SessionData { id: String::from("active"), expires: 2000, ..mock_session_data(1) }).await.ok().unwrap();
+
+    assert_eq!(Some(1), sessions.delete_expired(1500).await.ok());
+    assert!(matches!(sessions.get("session").await, Err(SessionGetError::Missing)));
+    assert!(sessions.get("active").await.is_ok());
+}
+
+#[tokio::test]
+async fn sessions_shorten() {
+    let (users, sessions) = repositories(MemoryStore::new());
+    users.insert(mock_user_data()).await.ok().unwrap();
+    sessions.insert(&mock_session_data(1)).await.ok().unwrap();
+
+    assert!(sessions.shorten("session", 500).await.is_ok());
+    assert!(sessions.shorten("session", 800).await.is_ok());
+    assert_eq!(500, sessions.get("session").await.ok().unwrap().expires);
+    assert!(matches!(sessions.shorten("missing", 500).await, Err(SessionUpdateError::Missing)));
+}
+
+#[tokio::test]
+async fn sessions_mark_authenticated() {
+    let (users, sessions) = repositories(MemoryStore::new());
+    users.insert(mock_user_data()).await.ok().unwrap();
+    sessions.insert(&mock_session_data(1)).await.ok().unwrap();
+
+    assert!(sessions.mark_authenticated("session", 900).await.is_ok());
+    assert_eq!(900, sessions.get("session").await.ok().unwrap().authenticated_at);
+    assert!(matches!(sessions.mark_authenticated("missing", 900).await, Err(SessionUpdateError::Missing)));
+}
+
+#[tokio::test]
+async fn users_update_password() {
+    let (users, _) = repositories(MemoryStore::new());
+    users.insert(mock_user_data()).await.ok().unwrap();
+
+    assert!(users.update_password(1, "new_hash").await.is_ok());
+    assert_eq!("new_hash", users.get_by_email("email@email.com").await.ok().unwrap().password_hash);
+    assert!(matches!(users.update_password(2, "new_hash").await, Err(UserUpdateError::Missing)));
+}
+
+fn mock_personal_token_data(token_hash: &str) -> PersonalTokenData {
+    PersonalTokenData {
+        user_id: 1,
+        name: String::from("ci"),
+        token_hash: String::from(token_hash),
+        scopes: vec![String::from("documents:read")],
+        expires: None
+    }
+}
+
+fn mock_refresh_token_data(id: &str, family_id: &str) -> RefreshTokenData {
+    RefreshTokenData {
+        id: String::from(id),
+        family_id: String::from(family_id),
+        user_id: 1,
+        session_id: String::from("session"),
+        expires: 1000
+    }
+}
+
+#[tokio::test]
+async fn personal_tokens_insert_and_get() {
+    let store = Arc::new(MemoryStore::new());
+    let users = MemoryUserRepository::new(store.clone());
+    let tokens = MemoryPersonalTokenRepository::new(store);
+    users.insert(mock_user_data()).await.ok().unwrap();
+
+    let token = tokens.insert(&mock_personal_token_data("hash")).await.ok().unwrap();
+    assert_eq!(1, token.id);
+    assert!(matches!(tokens.insert(&mock_personal_token_data("hash")).await, Err(PersonalTokenInsertError::Duplicate)));
+    assert!(matches!(tokens.insert(&PersonalTokenData { user_id: 2, ..mock_personal_token_data("other") }).await, Err(PersonalTokenInsertError::Unknown)));
+
+    assert_eq!(token, tokens.get_by_hash("hash").await.ok().unwrap());
+    assert!(matches!(tokens.get_by_hash("missing").await, Err(PersonalTokenGetError::Missing)));
+}
+
+#[tokio::test]
+async fn personal_tokens_list_and_delete() {
+    let store = Arc::new(MemoryStore::new());
+    let users = MemoryUserRepository::new(store.clone());
+    let tokens = MemoryPersonalTokenRepository::new(store);
+    users.insert(mock_user_data()).await.ok().unwrap();
+    tokens.insert(&mock_personal_token_data("first")).await.ok().unwrap();
+    tokens.insert(&mock_personal_token_data("second")).await.ok().unwrap();
+
+    let ids: Vec<_> = tokens.list(1).await.ok().unwrap().into_iter().map(|token| token.id).collect();
+    assert_eq!(vec![1, 2], ids);
+    assert!(tokens.list(2).await.ok().unwrap().is_empty());
+
+    assert!(matches!(tokens.delete(2, 1).await, Err(PersonalTokenDeleteError::Missing)));
+    assert!(tokens.delete(1, 1).await.is_ok());
+    assert!(matches!(tokens.delete(1, 1).await, Err(PersonalTokenDeleteError::Missing)));
+    assert!(matches!(tokens.get_by_hash("first").await, Err(PersonalTokenGetError::Missing)));
+}
+
+#[tokio::test]
+async fn refresh_tokens_mark_used() {
+    let store = Arc::new(MemoryStore::new());
+    let users = MemoryUserRepository::new(store.clone());
+    let tokens = MemoryRefreshTokenRepository::new(store);
+    users.insert(mock_user_data()).await.ok().unwrap();
+
+    assert!(tokens.insert(&mock_refresh_token_data("token", "family")).await.is_ok());
+    assert!(matches!(tokens.insert(&mock_refresh_token_data("token", "family")).await, Err(RefreshTokenInsertError::Duplicate)));
+    assert!(!tokens.get("token").await.ok().unwrap().used);
+
+    assert!(tokens.mark_used("token").await.is_ok());
+    assert!(matches!(tokens.mark_used("token").await, Err(RefreshTokenUseError::AlreadyUsed)));
+    assert!(matches!(tokens.mark_used("missing").await, Err(RefreshTokenUseError::Missing)));
+    assert!(tokens.get("token").await.ok().unwrap().used);
+}
+
+#[tokio::test]
+async fn refresh_tokens_revoke_family() {
+    let store = Arc::new(MemoryStore::new());
+    let users = MemoryUserRepository::new(store.clone());
+    let tokens = MemoryRefreshTokenRepository::new(store);
+    users.insert(mock_user_data()).await.ok().unwrap();
+    tokens.insert(&mock_refresh_token_data("first", "family")).await.ok().unwrap();
+    tokens.insert(&mock_refresh_token_data("second", "family")).await.ok().unwrap();
+    tokens.insert(&mock_refresh_token_data("other", "other_family")).await.ok().unwrap();
+
+    assert!(tokens.revoke_family("family").await.is_ok());
+    assert!(matches!(tokens.get("first").await, Err(RefreshTokenGetError::Missing)));
+    assert!(matches!(tokens.get("second").await, Err(RefreshTokenGetError::Missing)));
+    assert!(tokens.get("other").await.is_ok());
+}
+
+fn snapshot_path(name: &str) -> std::path::PathBuf {
+    let path = env::temp_dir().join(format!("agartex-memory-{}-{}.json", name, std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+#[tokio::test]
+async fn snapshot_roundtrip() {
+    let path = snapshot_path("roundtrip");
+
+    let store = Arc::new(MemoryStore::with_snapshot(&path).unwrap());
+    let mut tasks = BackgroundTasks::new();
+    tasks.push(spawn_snapshot_writer(store.clone(), Duration::from_secs(60), tasks.shutdown_signal()));
+    let (users, sessions) = (MemoryUserRepository::new(store.clone()), MemorySessionRepository::new(store));
+    users.insert(mock_user_data()).await.ok().unwrap();
+    sessions.insert(&mock_session_data(1)).await.ok().unwrap();
+    // the last changes are flushed on shutdown
+    tasks.shutdown().await;
+
+    let (users, sessions) = repositories(MemoryStore::with_snapshot(&path).unwrap());
+    assert!(users.get_by_email("email@email.com").await.is_ok());
+    assert!(sessions.get("session").await.is_ok());
+    assert!(users.insert(UserData { email: String::from("other@email.com"), ..mock_user_data() }).await.is_ok());
+    assert_eq!(2, users.get_by_email("other@email.com").await.ok().unwrap().id);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[tokio::test]
+async fn snapshot_written_after_debounce() {
+    let path = snapshot_path("debounce");
+
+    let store = Arc::new(MemoryStore::with_snapshot(&path).unwrap());
+    let tasks = BackgroundTasks::new();
+    let _writer = spawn_snapshot_writer(store.clone(), Duration::from_millis(20), tasks.shutdown_signal());
+    let users = MemoryUserRepository::new(store);
+    users.insert(mock_user_data()).await.ok().unwrap();
+
+    assert!(!path.exists());
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let (users, _) = repositories(MemoryStore::with_snapshot(&path).unwrap());
+    assert!(users.get_by_email("email@email.com").await.is_ok());
+
+    fs::remove_file(&path).unwrap();
+}
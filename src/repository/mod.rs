@@ -1,2 +1,15 @@
+pub mod client;
 pub mod users;
 pub mod sessions;
+pub mod refresh_tokens;
+pub mod personal_tokens;
+pub mod postgres;
+pub mod memory;
+pub mod sqlite;
+pub mod backend;
+pub mod cache;
+pub mod revocation;
+
+fn is_unique_violation(err: &sqlx::Error) -> bool {
+    matches!(err, sqlx::Error::Database(err) if err.is_unique_violation())
+}
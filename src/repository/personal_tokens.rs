@@ -0,0 +1,181 @@
+use std::str::FromStr;
+
+use axum::async_trait;
+use http::StatusCode;
+use mockall::automock;
+use reqwest::Url;
+use tracing::{error, warn};
+
+use crate::domain::personal_tokens::{PersonalToken, PersonalTokenData};
+
+use super::client::HttpClient;
+
+pub enum PersonalTokenInsertError {
+    Duplicate,
+    Unknown
+}
+
+pub enum PersonalTokenGetError {
+    Missing,
+    Unknown
+}
+
+pub enum PersonalTokenDeleteError {
+    Missing,
+    Unknown
+}
+
+#[automock]
+#[async_trait]
+pub trait PersonalTokenRepository {
+    async fn insert(&self, token_data: &PersonalTokenData) -> Result<PersonalToken, PersonalTokenInsertError>;
+    async fn get_by_hash(&self, token_hash: &str) -> Result<PersonalToken, PersonalTokenGetError>;
+    async fn list(&self, user_id: i32) -> Result<Vec<PersonalToken>, PersonalTokenGetError>;
+    async fn delete(&self, user_id: i32, id: i32) -> Result<(), PersonalTokenDeleteError>;
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpPersonalTokenRepository {
+    manager_personal_tokens_url: Url,
+    client: HttpClient
+}
+
+impl HttpPersonalTokenRepository {
+    pub fn new(url: &str, client: HttpClient) -> Self {
+        Self {
+            manager_personal_tokens_url: Url::from_str(url).unwrap(),
+            client
+        }
+    }
+}
+
+#[async_trait]
+impl PersonalTokenRepository for HttpPersonalTokenRepository {
+    #[tracing::instrument(skip_all, fields(user_id = token_data.user_id))]
+    async fn insert(&self, token_data: &PersonalTokenData) -> Result<PersonalToken, PersonalTokenInsertError> {
+        let req = self.client
+            .post(self.manager_personal_tokens_url.clone())
+            .json(&token_data);
+
+        let res = match self.client.send(req).await {
+            Ok(res) => res,
+            Err(err) => {
+                error!(%err);
+                return Err(PersonalTokenInsertError::Unknown);
+            }
+        };
+
+        let body = match res.status() {
+            StatusCode::CREATED => res.json::<PersonalToken>(),
+            StatusCode::CONFLICT => {
+                warn!("Duplicate personal token");
+                return Err(PersonalTokenInsertError::Duplicate);
+            },
+            code => {
+                error!("Unexpected code {:?}", code);
+                return Err(PersonalTokenInsertError::Unknown);
+            }
+        };
+
+        body.await.map_err(|err| {
+            error!(%err);
+            PersonalTokenInsertError::Unknown
+        })
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn get_by_hash(&self, token_hash: &str) -> Result<PersonalToken, PersonalTokenGetError> {
+        let req = self.client
+            .get(self.manager_personal_tokens_url.clone())
+            .bearer_auth(token_hash);
+
+        let res = match self.client.send(req).await {
+            Ok(res) => res,
+            Err(err) => {
+                error!(%err);
+                return Err(PersonalTokenGetError::Unknown);
+            }
+        };
+
+        let body = match res.status() {
+            StatusCode::OK => res.json::<PersonalToken>(),
+            StatusCode::NOT_FOUND => {
+                warn!("Missing personal token");
+                return Err(PersonalTokenGetError::Missing);
+            },
+            code => {
+                error!("Unexpected code {:?}", code);
+                return Err(PersonalTokenGetError::Unknown);
+            }
+        };
+
+        body.await.map_err(|err| {
+            error!(%err);
+            PersonalTokenGetError::Unknown
+        })
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn list(&self, user_id: i32) -> Result<Vec<PersonalToken>, PersonalTokenGetError> {
+        let req = self.client
+            .get(self.manager_personal_tokens_url.clone())
+            .query(&[("user_id", user_id)]);
+
+        let res = match self.client.send(req).await {
+            Ok(res) => res,
+            Err(err) => {
+                error!(%err);
+                return Err(PersonalTokenGetError::Unknown);
+            }
+        };
+
+        let body = match res.status() {
+            StatusCode::OK => res.json::<Vec<PersonalToken>>(),
+            code => {
+                error!("Unexpected code {:?}", code);
+                return Err(PersonalTokenGetError::Unknown);
+            }
+        };
+
+        body.await.map_err(|err| {
+            error!(%err);
+            PersonalTokenGetError::Unknown
+        })
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn delete(&self, user_id: i32, id: i32) -> Result<(), PersonalTokenDeleteError> {
+        let mut url = self.manager_personal_tokens_url.clone();
+        match url.path_segments_mut() {
+            Ok(mut path) => path.push(&id.to_string()),
+            Err(_) => {
+                error!("Bad Resource Management URL: {:?}", self.manager_personal_tokens_url);
+                return Err(PersonalTokenDeleteError::Unknown);
+            }
+        };
+
+        let req = self.client
+            .delete(url)
+            .query(&[("user_id", user_id)]);
+
+        let res = match self.client.send(req).await {
+            Ok(res) => res,
+            Err(err) => {
+                error!(%err);
+                return Err(PersonalTokenDeleteError::Unknown);
+            }
+        };
+
+        match res.status() {
+            StatusCode::NO_CONTENT => Ok(()),
+            StatusCode::NOT_FOUND => {
+                warn!("Missing personal token");
+                Err(PersonalTokenDeleteError::Missing)
+            },
+            code => {
+                error!("Unexpected code {:?}", code);
+                Err(PersonalTokenDeleteError::Unknown)
+            }
+        }
+    }
+}
@@ -0,0 +1,482 @@
+use axum::async_trait;
+use sqlx::{PgPool, postgres::{PgConnectOptions, PgPoolOptions}, Row, postgres::PgRow};
+use tracing::{error, info, warn};
+
+use crate::domain::{sessions::{Session, SessionData, SessionBinding}, users::{User, UserData}, personal_tokens::{PersonalToken, PersonalTokenData}, tokens::{RefreshToken, RefreshTokenData}};
+
+use super::{is_unique_violation, users::{UserRepository, UserGetError, UserInsertError, UserUpdateError}, sessions::{SessionRepository, SessionGetError, SessionDeleteError, SessionInsertError, SessionUpdateError}, personal_tokens::{PersonalTokenRepository, PersonalTokenInsertError, PersonalTokenGetError, PersonalTokenDeleteError}, refresh_tokens::{RefreshTokenRepository, RefreshTokenInsertError, RefreshTokenGetError, RefreshTokenUseError, RefreshTokenRevokeError}};
+
+/// Connects using the PG* environment variables.
+pub async fn pool(max_connections: u32) -> Result<PgPool, sqlx::Error> {
+    PgPoolOptions::new()
+        .max_connections(max_connections)
+        .connect_with(PgConnectOptions::new())
+        .await
+}
+
+/// Connects using the PG* environment variables and applies pending migrations.
+pub async fn connect(max_connections: u32) -> anyhow::Result<PgPool> {
+    let pool = pool(max_connections).await?;
+
+    info!("Running database migrations");
+    sqlx::migrate!("./migrations/postgres").run(&pool).await?;
+
+    Ok(pool)
+}
+
+fn user_from_row(row: &PgRow) -> Result<User, sqlx::Error> {
+    Ok(User {
+        id: row.try_get("user_id")?,
+        email: row.try_get("email")?,
+        password_hash: row.try_get("password_hash")?
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct PgUserRepository {
+    pool: PgPool
+}
+
+impl PgUserRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl UserRepository for PgUserRepository {
+    #[tracing::instrument(skip_all, fields(email = user_data.email))]
+    async fn insert(&self, user_data: UserData) -> Result<(), UserInsertError> {
+        let res = sqlx::query("INSERT INTO users (email, password_hash) VALUES ($1, $2)")
+            .bind(&user_data.email)
+            .bind(&user_data.password_hash)
+            .execute(&self.pool)
+            .await;
+
+        match res {
+            Ok(_) => Ok(()),
+            Err(err) if is_unique_violation(&err) => {
+                warn!("Duplicate user");
+                Err(UserInsertError::Duplicate)
+            },
+            Err(err) => {
+                error!(%err);
+                Err(UserInsertError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn get_by_email(&self, email: &str) -> Result<User, UserGetError> {
+        let res = sqlx::query("SELECT id AS user_id, email, password_hash FROM users WHERE email = $1")
+            .bind(email)
+            .fetch_optional(&self.pool)
+            .await;
+
+        match res.and_then(|row| row.as_ref().map(user_from_row).transpose()) {
+            Ok(Some(user)) => Ok(user),
+            Ok(None) => {
+                warn!("Missing user");
+                Err(UserGetError::Missing)
+            },
+            Err(err) => {
+                error!(%err);
+                Err(UserGetError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(self, password_hash))]
+    async fn update_password(&self, id: i32, password_hash: &str) -> Result<(), UserUpdateError> {
+        let res = sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2")
+            .bind(password_hash)
+            .bind(id)
+            .execute(&self.pool)
+            .await;
+
+        match res {
+            Ok(res) if res.rows_affected() == 0 => {
+                warn!("Missing user");
+                Err(UserUpdateError::Missing)
+            },
+            Ok(_) => Ok(()),
+            Err(err) => {
+                error!(%err);
+                Err(UserUpdateError::Unknown)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PgSessionRepository {
+    pool: PgPool
+}
+
+impl PgSessionRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl SessionRepository for PgSessionRepository {
+    #[tracing::instrument(skip_all, fields(user_id = session_data.user_id))]
+    async fn insert(&self, session_data: &SessionData) -> Result<(), SessionInsertError> {
+        let res = sqlx::query("INSERT INTO sessions (id, user_id, expires, authenticated_at, user_agent_family, ip_subnet) VALUES ($1, $2, $3, $4, $5, $6)")
+            .bind(&session_data.id)
+            .bind(session_data.user_id)
+            .bind(session_data.expires)
+            .bind(session_data.authenticated_at)
+            .bind(&session_data.binding.user_agent_family)
+            .bind(&session_data.binding.ip_subnet)
+            .execute(&self.pool)
+            .await;
+
+        match res {
+            Ok(_) => Ok(()),
+            Err(err) if is_unique_violation(&err) => {
+                warn!("Duplicate session {:?}", session_data);
+                Err(SessionInsertError::Duplicate)
+            },
+            Err(err) => {
+                error!(%err);
+                Err(SessionInsertError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn get(&self, id: &str) -> Result<Session, SessionGetError> {
+        let res = sqlx::query(
+            "SELECT s.id, s.expires, s.authenticated_at, s.user_agent_family, s.ip_subnet, u.id AS user_id, u.email, u.password_hash
+             FROM sessions s JOIN users u ON u.id = s.user_id
+             WHERE s.id = $1"
+        )
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await;
+
+        let session = res.and_then(|row| row.map(|row| Ok(Session {
+            id: row.try_get("id")?,
+            user: user_from_row(&row)?,
+            expires: row.try_get("expires")?,
+            authenticated_at: row.try_get("authenticated_at")?,
+            binding: SessionBinding {
+                user_agent_family: row.try_get("user_agent_family")?,
+                ip_subnet: row.try_get("ip_subnet")?
+            }
+        })).transpose());
+
+        match session {
+            Ok(Some(session)) => Ok(session),
+            Ok(None) => {
+                warn!("Missing session {:?}", id);
+                Err(SessionGetError::Missing)
+            },
+            Err(err) => {
+                error!(%err);
+                Err(SessionGetError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn delete(&self, id: &str) -> Result<(), SessionDeleteError> {
+        sqlx::query("DELETE FROM sessions WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+            .map_err(|err| {
+                error!(%err);
+                SessionDeleteError::Unknown
+            })
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn shorten(&self, id: &str, expires: i64) -> Result<(), SessionUpdateError> {
+        let res = sqlx::query("UPDATE sessions SET expires = LEAST(expires, $1) WHERE id = $2")
+            .bind(expires)
+            .bind(id)
+            .execute(&self.pool)
+            .await;
+
+        match res {
+            Ok(res) if res.rows_affected() == 0 => {
+                warn!("Missing session {:?}", id);
+                Err(SessionUpdateError::Missing)
+            },
+            Ok(_) => Ok(()),
+            Err(err) => {
+                error!(%err);
+                Err(SessionUpdateError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn mark_authenticated(&self, id: &str, authenticated_at: i64) -> Result<(), SessionUpdateError> {
+        let res = sqlx::query("UPDATE sessions SET authenticated_at = $1 WHERE id = $2")
+            .bind(authenticated_at)
+            .bind(id)
+            .execute(&self.pool)
+            .await;
+
+        match res {
+            Ok(res) if res.rows_affected() == 0 => {
+                warn!("Missing session {:?}", id);
+                Err(SessionUpdateError::Missing)
+            },
+            Ok(_) => Ok(()),
+            Err(err) => {
+                error!(%err);
+                Err(SessionUpdateError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn delete_expired(&self, before: i64) -> Result<u64, SessionDeleteError> {
+        sqlx::query("DELETE FROM sessions WHERE expires < $1")
+            .bind(before)
+            .execute(&self.pool)
+            .await
+            .map(|res| res.rows_affected())
+            .map_err(|err| {
+                error!(%err);
+                SessionDeleteError::Unknown
+            })
+    }
+}
+
+fn personal_token_from_row(row: &PgRow) -> Result<PersonalToken, sqlx::Error> {
+    Ok(PersonalToken {
+        id: row.try_get("id")?,
+        user_id: row.try_get("user_id")?,
+        name: row.try_get("name")?,
+        scopes: row.try_get("scopes")?,
+        expires: row.try_get("expires")?
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct PgPersonalTokenRepository {
+    pool: PgPool
+}
+
+impl PgPersonalTokenRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl PersonalTokenRepository for PgPersonalTokenRepository {
+    #[tracing::instrument(skip_all, fields(user_id = token_data.user_id))]
+    async fn insert(&self, token_data: &PersonalTokenData) -> Result<PersonalToken, PersonalTokenInsertError> {
+        let res = sqlx::query("INSERT INTO personal_tokens (user_id, name, token_hash, scopes, expires) VALUES ($1, $2, $3, $4, $5) RETURNING id")
+            .bind(token_data.user_id)
+            .bind(&token_data.name)
+            .bind(&token_data.token_hash)
+            .bind(&token_data.scopes)
+            .bind(token_data.expires)
+            .fetch_one(&self.pool)
+            .await
+            .and_then(|row| row.try_get("id"));
+
+        match res {
+            Ok(id) => Ok(PersonalToken {
+                id,
+                user_id: token_data.user_id,
+                name: token_data.name.clone(),
+                scopes: token_data.scopes.clone(),
+                expires: token_data.expires
+            }),
+            Err(err) if is_unique_violation(&err) => {
+                warn!("Duplicate personal token");
+                Err(PersonalTokenInsertError::Duplicate)
+            },
+            Err(err) => {
+                error!(%err);
+                Err(PersonalTokenInsertError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn get_by_hash(&self, token_hash: &str) -> Result<PersonalToken, PersonalTokenGetError> {
+        let res = sqlx::query("SELECT id, user_id, name, scopes, expires FROM personal_tokens WHERE token_hash = $1")
+            .bind(token_hash)
+            .fetch_optional(&self.pool)
+            .await;
+
+        match res.and_then(|row| row.as_ref().map(personal_token_from_row).transpose()) {
+            Ok(Some(token)) => Ok(token),
+            Ok(None) => {
+                warn!("Missing personal token");
+                Err(PersonalTokenGetError::Missing)
+            },
+            Err(err) => {
+                error!(%err);
+                Err(PersonalTokenGetError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn list(&self, user_id: i32) -> Result<Vec<PersonalToken>, PersonalTokenGetError> {
+        let res = sqlx::query("SELECT id, user_id, name, scopes, expires FROM personal_tokens WHERE user_id = $1 ORDER BY id")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await;
+
+        res.and_then(|rows| rows.iter().map(personal_token_from_row).collect())
+            .map_err(|err| {
+                error!(%err);
+                PersonalTokenGetError::Unknown
+            })
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn delete(&self, user_id: i32, id: i32) -> Result<(), PersonalTokenDeleteError> {
+        let res = sqlx::query("DELETE FROM personal_tokens WHERE id = $1 AND user_id = $2")
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await;
+
+        match res {
+            Ok(res) if res.rows_affected() == 0 => {
+                warn!("Missing personal token");
+                Err(PersonalTokenDeleteError::Missing)
+            },
+            Ok(_) => Ok(()),
+            Err(err) => {
+                error!(%err);
+                Err(PersonalTokenDeleteError::Unknown)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PgRefreshTokenRepository {
+    pool: PgPool
+}
+
+impl PgRefreshTokenRepository {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl RefreshTokenRepository for PgRefreshTokenRepository {
+    #[tracing::instrument(skip_all, fields(user_id = token_data.user_id))]
+    async fn insert(&self, token_data: &RefreshTokenData) -> Result<(), RefreshTokenInsertError> {
+        let res = sqlx::query("INSERT INTO refresh_tokens (id, family_id, user_id, session_id, expires) VALUES ($1, $2, $3, $4, $5)")
+            .bind(&token_data.id)
+            .bind(&token_data.family_id)
+            .bind(token_data.user_id)
+            .bind(&token_data.session_id)
+            .bind(token_data.expires)
+            .execute(&self.pool)
+            .await;
+
+        match res {
+            Ok(_) => Ok(()),
+            Err(err) if is_unique_violation(&err) => {
+                warn!("Duplicate refresh token");
+                Err(RefreshTokenInsertError::Duplicate)
+            },
+            Err(err) => {
+                error!(%err);
+                Err(RefreshTokenInsertError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn get(&self, id: &str) -> Result<RefreshToken, RefreshTokenGetError> {
+        let res = sqlx::query("SELECT id, family_id, user_id, session_id, expires, used FROM refresh_tokens WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await;
+
+        let token = res.and_then(|row| row.map(|row| Ok(RefreshToken {
+            id: row.try_get("id")?,
+            family_id: row.try_get("family_id")?,
+            user_id: row.try_get("user_id")?,
+            session_id: row.try_get("session_id")?,
+            expires: row.try_get("expires")?,
+            used: row.try_get("used")?
+        })).transpose());
+
+        match token {
+            Ok(Some(token)) => Ok(token),
+            Ok(None) => {
+                warn!("Missing refresh token");
+                Err(RefreshTokenGetError::Missing)
+            },
+            Err(err) => {
+                error!(%err);
+                Err(RefreshTokenGetError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn mark_used(&self, id: &str) -> Result<(), RefreshTokenUseError> {
+        // the NOT used condition lets only one of two concurrent uses update the row
+        let res = sqlx::query("UPDATE refresh_tokens SET used = TRUE WHERE id = $1 AND NOT used")
+            .bind(id)
+            .execute(&self.pool)
+            .await;
+
+        match res {
+            Ok(res) if res.rows_affected() > 0 => return Ok(()),
+            Ok(_) => (),
+            Err(err) => {
+                error!(%err);
+                return Err(RefreshTokenUseError::Unknown);
+            }
+        };
+
+        let exists = sqlx::query("SELECT 1 FROM refresh_tokens WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await;
+
+        match exists {
+            Ok(Some(_)) => {
+                warn!("Refresh token already used");
+                Err(RefreshTokenUseError::AlreadyUsed)
+            },
+            Ok(None) => {
+                warn!("Missing refresh token");
+                Err(RefreshTokenUseError::Missing)
+            },
+            Err(err) => {
+                error!(%err);
+                Err(RefreshTokenUseError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn revoke_family(&self, family_id: &str) -> Result<(), RefreshTokenRevokeError> {
+        sqlx::query("DELETE FROM refresh_tokens WHERE family_id = $1")
+            .bind(family_id)
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+            .map_err(|err| {
+                error!(%err);
+                RefreshTokenRevokeError::Unknown
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,121 @@
+use sqlx::Executor;
+
+use super::*;
+
+// Needs a database reachable through the PG* variables, run with `cargo test -- --ignored`.
+// Every test migrates its own schema so they don't see each other's rows.
+async fn test_pool(schema: &str) -> PgPool {
+    let admin = pool(1).await.unwrap();
+    admin.execute(format!("DROP SCHEMA IF EXISTS {0} CASCADE; CREATE SCHEMA {0}", schema).as_str()).await.unwrap();
+
+    let pool = PgPoolOptions::new()
+        .max_connections(1)
+        .connect_with(PgConnectOptions::new().options([("search_path", schema)]))
+        .await
+        .unwrap();
+    sqlx::migrate!("./migrations/postgres").run(&pool).await.unwrap();
+    pool
+}
+
+fn mock_user_data() -> UserData {
+    UserData {
+        email: String::from("email@email.com"),
+        password_hash: String::from("hash")
+    }
+}
+
+fn mock_session_data(id: &str) -> SessionData {
+    SessionData {
+        id: String::from(id),
+        user_id: 1,
+        expires: 1000,
+        authenticated_at: 0,
+        binding: SessionBinding::default()
+    }
+}
+
+fn mock_personal_token_data(token_hash: &str) -> PersonalTokenData {
+    PersonalTokenData {
+        user_id: 1,
+        name: String::from("ci"),
+        token_hash: String::from(token_hash),
+        scopes: vec![String::from("documents:read"), String::from("documents:write")],
+        expires: Some(1000)
+    }
+}
+
+fn mock_refresh_token_data(id: &str, family_id: &str) -> RefreshTokenData {
+    RefreshTokenData {
+        id: String::from(id),
+        family_id: String::from(family_id),
+        user_id: 1,
+        session_id: String::from("session"),
+        expires: 1000
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres database"]
+async fn users_duplicate_and_missing() {
+    let users = PgUserRepository::new(test_pool("test_users").await);
+
+    assert!(matches!(users.get_by_email("email@email.com").await, Err(UserGetError::Missing)));
+    assert!(users.insert(mock_user_data()).await.is_ok());
+    assert!(matches!(users.insert(mock_user_data()).await, Err(UserInsertError::Duplicate)));
+    assert!(matches!(users.update_password(2, "new_hash").await, Err(UserUpdateError::Missing)));
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres database"]
+async fn sessions_duplicate_and_missing() {
+    let pool = test_pool("test_sessions").await;
+    let users = PgUserRepository::new(pool.clone());
+    let sessions = PgSessionRepository::new(pool);
+    users.insert(mock_user_data()).await.ok().unwrap();
+
+    assert!(sessions.insert(&mock_session_data("session")).await.is_ok());
+    assert!(matches!(sessions.insert(&mock_session_data("session")).await, Err(SessionInsertError::Duplicate)));
+    assert_eq!("email@email.com", sessions.get("session").await.ok().unwrap().user.email);
+
+    assert!(sessions.delete("session").await.is_ok());
+    assert!(matches!(sessions.get("session").await, Err(SessionGetError::Missing)));
+    assert!(matches!(sessions.shorten("session", 500).await, Err(SessionUpdateError::Missing)));
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres database"]
+async fn personal_tokens_duplicate_and_missing() {
+    let pool = test_pool("test_personal_tokens").await;
+    let users = PgUserRepository::new(pool.clone());
+    let tokens = PgPersonalTokenRepository::new(pool);
+    users.insert(mock_user_data()).await.ok().unwrap();
+
+    let token = tokens.insert(&mock_personal_token_data("hash")).await.ok().unwrap();
+    assert!(matches!(tokens.insert(&mock_personal_token_data("hash")).await, Err(PersonalTokenInsertError::Duplicate)));
+    assert_eq!(token, tokens.get_by_hash("hash").await.ok().unwrap());
+    assert_eq!(vec![token.clone()], tokens.list(1).await.ok().unwrap());
+
+    assert!(matches!(tokens.delete(2, token.id).await, Err(PersonalTokenDeleteError::Missing)));
+    assert!(tokens.delete(1, token.id).await.is_ok());
+    assert!(matches!(tokens.get_by_hash("hash").await, Err(PersonalTokenGetError::Missing)));
+}
+
+#[tokio::test]
+#[ignore = "needs a Postgres database"]
+async fn refresh_tokens_duplicate_and_missing() {
+    let pool = test_pool("test_refresh_tokens").await;
+    let users = PgUserRepository::new(pool.clone());
+    let tokens = PgRefreshTokenRepository::new(pool);
+    users.insert(mock_user_data()).await.ok().unwrap();
+
+    assert!(tokens.insert(&mock_refresh_token_data("token", "family")).await.is_ok());
+    assert!(matches!(tokens.insert(&mock_refresh_token_data("token", "family")).await, Err(RefreshTokenInsertError::Duplicate)));
+
+    assert!(tokens.mark_used("token").await.is_ok());
+    assert!(matches!(tokens.mark_used("token").await, Err(RefreshTokenUseError::AlreadyUsed)));
+    assert!(matches!(tokens.mark_used("missing").await, Err(RefreshTokenUseError::Missing)));
+    assert!(tokens.get("token").await.ok().unwrap().used);
+
+    assert!(tokens.revoke_family("family").await.is_ok());
+    assert!(matches!(tokens.get("token").await, Err(RefreshTokenGetError::Missing)));
+}
@@ -0,0 +1,180 @@
+use std::str::FromStr;
+
+use axum::async_trait;
+use http::StatusCode;
+use mockall::automock;
+use reqwest::Url;
+use tracing::{error, warn};
+
+use crate::domain::tokens::{RefreshToken, RefreshTokenData};
+
+use super::client::HttpClient;
+
+pub enum RefreshTokenInsertError {
+    Duplicate,
+    Unknown
+}
+
+pub enum RefreshTokenGetError {
+    Missing,
+    Unknown
+}
+
+pub enum RefreshTokenUseError {
+    AlreadyUsed,
+    Missing,
+    Unknown
+}
+
+pub enum RefreshTokenRevokeError {
+    Unknown
+}
+
+/// Refresh tokens are grouped into families, one per initial issue.
+/// Every rotation adds a token to the family and marks the presented one as used.
+#[automock]
+#[async_trait]
+pub trait RefreshTokenRepository {
+    async fn insert(&self, token_data: &RefreshTokenData) -> Result<(), RefreshTokenInsertError>;
+    async fn get(&self, id: &str) -> Result<RefreshToken, RefreshTokenGetError>;
+    /// Must fail with `AlreadyUsed` if the token was used before, even by a concurrent request.
+    async fn mark_used(&self, id: &str) -> Result<(), RefreshTokenUseError>;
+    async fn revoke_family(&self, family_id: &str) -> Result<(), RefreshTokenRevokeError>;
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpRefreshTokenRepository {
+    manager_refresh_tokens_url: Url,
+    client: HttpClient
+}
+
+impl HttpRefreshTokenRepository {
+    pub fn new(url: &str, client: HttpClient) -> Self {
+        Self {
+            manager_refresh_tokens_url: Url::from_str(url).unwrap(),
+            client
+        }
+    }
+}
+
+#[async_trait]
+impl RefreshTokenRepository for HttpRefreshTokenRepository {
+    #[tracing::instrument(skip_all, fields(user_id = token_data.user_id))]
+    async fn insert(&self, token_data: &RefreshTokenData) -> Result<(), RefreshTokenInsertError> {
+        let req = self.client
+            .post(self.manager_refresh_tokens_url.clone())
+            .json(&token_data);
+
+        let res = match self.client.send(req).await {
+            Ok(res) => res,
+            Err(err) => {
+                error!(%err);
+                return Err(RefreshTokenInsertError::Unknown);
+            }
+        };
+
+        match res.status() {
+            StatusCode::CREATED => Ok(()),
+            StatusCode::CONFLICT => {
+                warn!("Duplicate refresh token");
+                Err(RefreshTokenInsertError::Duplicate)
+            },
+            code => {
+                error!("Unexpected code {:?}", code);
+                Err(RefreshTokenInsertError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn get(&self, id: &str) -> Result<RefreshToken, RefreshTokenGetError> {
+        let req = self.client
+            .get(self.manager_refresh_tokens_url.clone())
+            .bearer_auth(id);
+
+        let res = match self.client.send(req).await {
+            Ok(res) => res,
+            Err(err) => {
+                error!(%err);
+                return Err(RefreshTokenGetError::Unknown);
+            }
+        };
+
+        let body = match res.status() {
+            StatusCode::OK => res.json::<RefreshToken>(),
+            StatusCode::NOT_FOUND => {
+                warn!("Missing refresh token");
+                return Err(RefreshTokenGetError::Missing);
+            },
+            code => {
+                error!("Unexpected code {:?}", code);
+                return Err(RefreshTokenGetError::Unknown);
+            }
+        };
+
+        body.await.map_err(|err| {
+            error!(%err);
+            RefreshTokenGetError::Unknown
+        })
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn mark_used(&self, id: &str) -> Result<(), RefreshTokenUseError> {
+        let req = self.client
+            .patch(self.manager_refresh_tokens_url.clone())
+            .bearer_auth(id)
+            .json(&serde_json::json!({ "used": true }));
+
+        let res = match self.client.send(req).await {
+            Ok(res) => res,
+            Err(err) => {
+                error!(%err);
+                return Err(RefreshTokenUseError::Unknown);
+            }
+        };
+
+        match res.status() {
+            StatusCode::NO_CONTENT => Ok(()),
+            StatusCode::CONFLICT => {
+                warn!("Refresh token already used");
+                Err(RefreshTokenUseError::AlreadyUsed)
+            },
+            StatusCode::NOT_FOUND => {
+                warn!("Missing refresh token");
+                Err(RefreshTokenUseError::Missing)
+            },
+            code => {
+                error!("Unexpected code {:?}", code);
+                Err(RefreshTokenUseError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn revoke_family(&self, family_id: &str) -> Result<(), RefreshTokenRevokeError> {
+        let mut url = self.manager_refresh_tokens_url.clone();
+        match url.path_segments_mut() {
+            Ok(mut path) => path.extend(["families", family_id]),
+            Err(_) => {
+                error!("Bad Resource Management URL: {:?}", self.manager_refresh_tokens_url);
+                return Err(RefreshTokenRevokeError::Unknown);
+            }
+        };
+
+        let res = match self.client.send(self.client.delete(url)).await {
+            Ok(res) => res,
+            Err(err) => {
+                error!(%err);
+                return Err(RefreshTokenRevokeError::Unknown);
+            }
+        };
+
+        match res.status() {
+            StatusCode::NO_CONTENT => Ok(()),
+            code => {
+                error!("Unexpected code {:?}", code);
+                Err(RefreshTokenRevokeError::Unknown)
+            }
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::{fmt::{self, Debug}, str::FromStr, sync::Arc};
+
+use axum::async_trait;
+use mockall::automock;
+use tokio::{sync::{broadcast::{self, error::RecvError}, watch}, task::JoinHandle};
+use tracing::{info, warn};
+
+use crate::domain::sessions::Revocation;
+
+use super::{cache::CachedSessionRepository, sessions::SessionRepository};
+
+pub mod postgres;
+
+#[derive(Debug, PartialEq)]
+pub enum RevocationPublishError {
+    Unknown
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RevocationBackend {
+    None,
+    Postgres
+}
+
+impl FromStr for RevocationBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "postgres" => Ok(Self::Postgres),
+            other => Err(format!("Unknown revocation backend: {}", other))
+        }
+    }
+}
+
+/// Carries revocations between instances. Subscribers also receive revocations published by their own instance.
+#[automock]
+#[async_trait]
+pub trait RevocationTransport {
+    async fn publish(&self, revocation: &Revocation) -> Result<(), RevocationPublishError>;
+    fn subscribe(&self) -> broadcast::Receiver<Revocation>;
+}
+
+/// Delivers revocations within the process, instances sharing one stand in for a cluster in tests.
+#[derive(Debug, Clone)]
+pub struct LoopbackRevocationTransport {
+    sender: broadcast::Sender<Revocation>
+}
+
+impl LoopbackRevocationTransport {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+}
+
+#[async_trait]
+impl RevocationTransport for LoopbackRevocationTransport {
+    async fn publish(&self, revocation: &Revocation) -> Result<(), RevocationPublishError> {
+        // no subscribers is not an error, there is simply nobody to tell
+        let _ = self.sender.send(revocation.clone());
+        Ok(())
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<Revocation> {
+        self.sender.subscribe()
+    }
+}
+
+/// Shareable handle to whichever transport is configured.
+#[derive(Clone)]
+pub struct RevocationPublisher(Arc<dyn RevocationTransport + Send + Sync>);
+
+impl RevocationPublisher {
+    pub fn new<T: RevocationTransport + Send + Sync + 'static>(transport: T) -> Self {
+        Self(Arc::new(transport))
+    }
+
+    pub async fn publish(&self, revocation: &Revocation) -> Result<(), RevocationPublishError> {
+        self.0.publish(revocation).await
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Revocation> {
+        self.0.subscribe()
+    }
+}
+
+impl Debug for RevocationPublisher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RevocationPublisher").finish_non_exhaustive()
+    }
+}
+
+/// Evicts received revocations from the local session cache until the transport closes or `shutdown` turns `true`.
+pub fn spawn_revocation_listener<R>(
+    mut receiver: broadcast::Receiver<Revocation>,
+    cache: CachedSessionRepository<R>,
+    mut shutdown: watch::Receiver<bool>
+) -> JoinHandle<()>
+where
+    R: SessionRepository + Send + Sync + 'static
+{
+    tokio::spawn(async move {
+        loop {
+            let res = tokio::select! {
+                res = receiver.recv() => res,
+                _ = shutdown.changed() => {
+                    info!("Revocation listener stopped");
+                    return;
+                }
+            };
+
+            match res {
+                Ok(revocation) => {
+                    info!("Received revocation {:?}", revocation);
+                    cache.evict(&revocation);
+                },
+                Err(RecvError::Lagged(count)) => {
+                    // the missed revocations are unknown, so nothing cached can be trusted
+                    warn!("Missed {} revocations, clearing session cache", count);
+                    cache.clear();
+                },
+                Err(RecvError::Closed) => {
+                    warn!("Revocation transport closed");
+                    return;
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use axum::async_trait;
+use sqlx::{PgPool, postgres::PgListener};
+use tokio::{sync::{broadcast, watch}, task::JoinHandle};
+use tracing::{error, info, warn};
+
+use crate::domain::sessions::Revocation;
+
+use super::{RevocationTransport, RevocationPublishError};
+
+const CHANNEL: &str = "session_revocations";
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Uses `LISTEN`/`NOTIFY`, so every instance connected to the same database is told.
+#[derive(Debug, Clone)]
+pub struct PgRevocationTransport {
+    pool: PgPool,
+    sender: broadcast::Sender<Revocation>
+}
+
+impl PgRevocationTransport {
+    /// Also returns the task forwarding notifications to subscribers, it runs until `shutdown` turns `true`.
+    pub async fn connect(pool: PgPool, capacity: usize, mut shutdown: watch::Receiver<bool>) -> Result<(Self, JoinHandle<()>), sqlx::Error> {
+        let mut listener = PgListener::connect_with(&pool).await?;
+        listener.listen(CHANNEL).await?;
+
+        let (sender, _) = broadcast::channel(capacity);
+        let forward_sender = sender.clone();
+        let handle = tokio::spawn(async move {
+            loop {
+                let res = tokio::select! {
+                    res = listener.try_recv() => res,
+                    _ = shutdown.changed() => {
+                        info!("Revocation forwarding stopped");
+                        return;
+                    }
+                };
+
+                match res {
+                    Ok(Some(notification)) => {
+                        match serde_json::from_str::<Revocation>(notification.payload()) {
+                            Ok(revocation) => {
+                                let _ = forward_sender.send(revocation);
+                            },
+                            Err(err) => error!("Malformed revocation {:?}: {}", notification.payload(), err)
+                        }
+                        continue;
+                    },
+                    Ok(None) => warn!("Revocation listener disconnected"),
+                    Err(err) => error!(%err)
+                }
+
+                // notifications sent in the meantime are lost, so subscribers drop everything once listening again
+                if !reconnect(&mut listener, &mut shutdown).await {
+                    info!("Revocation forwarding stopped");
+                    return;
+                }
+                let _ = forward_sender.send(Revocation::All);
+            }
+        });
+
+        Ok((Self { pool, sender }, handle))
+    }
+}
+
+/// Retries until the listener is connected again, returns `false` if shut down first.
+async fn reconnect(listener: &mut PgListener, shutdown: &mut watch::Receiver<bool>) -> bool {
+    loop {
+        // any query through the listener reconnects it and renews its `LISTEN`
+        match sqlx::query("SELECT 1").execute(&mut *listener).await {
+            Ok(_) => {
+                info!("Revocation listener reconnected");
+                return true;
+            },
+            Err(err) => error!(%err)
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(RECONNECT_DELAY) => (),
+            _ = shutdown.changed() => return false
+        }
+    }
+}
+
+#[async_trait]
+impl RevocationTransport for PgRevocationTransport {
+    #[tracing::instrument(skip(self))]
+    async fn publish(&self, revocation: &Revocation) -> Result<(), RevocationPublishError> {
+        let payload = serde_json::to_string(revocation).map_err(|err| {
+            error!(%err);
+            RevocationPublishError::Unknown
+        })?;
+
+        sqlx::query("SELECT pg_notify($1, $2)")
+            .bind(CHANNEL)
+            .bind(payload)
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+            .map_err(|err| {
+                error!(%err);
+                RevocationPublishError::Unknown
+            })
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<Revocation> {
+        self.sender.subscribe()
+    }
+}
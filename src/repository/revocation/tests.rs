@@ -0,0 +1,225 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use sqlx::Executor;
+
+use crate::{tasks::BackgroundTasks, repository::{cache::SessionCacheConfig, postgres::pool, memory::{MemoryStore, MemorySessionRepository, MemoryUserRepository}, sessions::SessionGetError, users::UserRepository}, domain::{users::UserData, sessions::{SessionData, SessionBinding, ClientInfo}}, service::{sessions::{HashSessionService, SessionService}, hash::MockHashService}};
+
+use super::*;
+
+fn config() -> SessionCacheConfig {
+    SessionCacheConfig {
+        capacity: 16,
+        ttl: Duration::from_secs(60),
+        negative_ttl: Duration::from_secs(60)
+    }
+}
+
+/// A peer caching sessions "a", "b" (user 1) and "cc" (user 2), with a miss counted for each fetch.
+async fn peer() -> CachedSessionRepository<MemorySessionRepository> {
+    let store = Arc::new(MemoryStore::new());
+    let users = MemoryUserRepository::new(store.clone());
+    for email in ["first@email.com", "second@email.com"] {
+        users.insert(UserData { email: String::from(email), password_hash: String::from("hash") }).await.ok().unwrap();
+    }
+
+    let sessions = MemorySessionRepository::new(store);
+    for id in ["a", "b", "cc"] {
+        let session_data = SessionData { id: String::from(id), user_id: id.len() as i32, expires: Utc::now().timestamp() + 3600, authenticated_at: 0, binding: SessionBinding::default() };
+        sessions.insert(&session_data).await.ok().unwrap();
+    }
+
+    let cache = CachedSessionRepository::new(sessions, config());
+    for id in ["a", "b", "cc"] {
+        assert!(cache.get(id).await.is_ok());
+    }
+    cache
+}
+
+async fn misses_after_refetch(cache: &CachedSessionRepository<MemorySessionRepository>) -> u64 {
+    let before = cache.stats().misses;
+    for id in ["a", "b", "cc"] {
+        assert!(cache.get(id).await.is_ok());
+    }
+    cache.stats().misses - before
+}
+
+/// Tests calling this run with the clock paused, so the sleep only ends once every other task,
+/// the listener included, has nothing left to do.
+async fn settle() {
+    tokio::time::sleep(Duration::from_secs(1)).await;
+}
+
+#[tokio::test(start_paused = true)]
+async fn loopback_session_revocation() {
+    let tasks = BackgroundTasks::new();
+    let transport = LoopbackRevocationTransport::new(16);
+    let cache = peer().await;
+    spawn_revocation_listener(transport.subscribe(), cache.clone(), tasks.shutdown_signal());
+
+    transport.publish(&Revocation::Session { id: String::from("a") }).await.unwrap();
+    settle().await;
+
+    assert_eq!(1, misses_after_refetch(&cache).await);
+}
+
+#[tokio::test(start_paused = true)]
+async fn loopback_user_revocation() {
+    let tasks = BackgroundTasks::new();
+    let transport = LoopbackRevocationTransport::new(16);
+    let cache = peer().await;
+    spawn_revocation_listener(transport.subscribe(), cache.clone(), tasks.shutdown_signal());
+
+    transport.publish(&Revocation::User { user_id: 1 }).await.unwrap();
+    settle().await;
+
+    assert_eq!(2, misses_after_refetch(&cache).await);
+}
+
+#[tokio::test(start_paused = true)]
+async fn loopback_lagged_clears_cache() {
+    let tasks = BackgroundTasks::new();
+    let transport = LoopbackRevocationTransport::new(1);
+    let cache = peer().await;
+    let receiver = transport.subscribe();
+
+    // overflow the channel before the listener gets to read it
+    transport.publish(&Revocation::Session { id: String::from("x") }).await.unwrap();
+    transport.publish(&Revocation::Session { id: String::from("y") }).await.unwrap();
+    spawn_revocation_listener(receiver, cache.clone(), tasks.shutdown_signal());
+    settle().await;
+
+    assert_eq!(3, misses_after_refetch(&cache).await);
+}
+
+#[tokio::test(start_paused = true)]
+async fn logout_evicts_on_peers() {
+    let tasks = BackgroundTasks::new();
+    let transport = LoopbackRevocationTransport::new(16);
+    let store = Arc::new(MemoryStore::new());
+    MemoryUserRepository::new(store.clone())
+        .insert(UserData { email: String::from("email@email.com"), password_hash: String::from("hash") })
+        .await
+        .ok()
+        .unwrap();
+
+    let sessions = MemorySessionRepository::new(store);
+    let session_data = SessionData { id: String::from("session"), user_id: 1, expires: Utc::now().timestamp() + 3600, authenticated_at: 0, binding: SessionBinding::default() };
+    sessions.insert(&session_data).await.ok().unwrap();
+
+    let first = CachedSessionRepository::new(sessions.clone(), config());
+    let second = CachedSessionRepository::new(sessions, config());
+    spawn_revocation_listener(transport.subscribe(), second.clone(), tasks.shutdown_signal());
+
+    assert!(second.get("session").await.is_ok());
+
+    let service = HashSessionService::new(first, MemoryUserRepository::new(Arc::new(MemoryStore::new())), MockHashService::new(), 1)
+        .with_revocations(RevocationPublisher::new(transport));
+    assert!(service.logout("session").await.is_ok());
+    settle().await;
+
+    assert!(matches!(second.get("session").await, Err(SessionGetError::Missing)));
+}
+
+#[tokio::test(start_paused = true)]
+async fn reauthenticate_evicts_on_peers() {
+    let tasks = BackgroundTasks::new();
+    let transport = LoopbackRevocationTransport::new(16);
+    let store = Arc::new(MemoryStore::new());
+    let users = MemoryUserRepository::new(store.clone());
+    users
+        .insert(UserData { email: String::from("email@email.com"), password_hash: String::from("hash") })
+        .await
+        .ok()
+        .unwrap();
+
+    let sessions = MemorySessionRepository::new(store);
+    let session_data = SessionData { id: String::from("session"), user_id: 1, expires: Utc::now().timestamp() + 3600, authenticated_at: 0, binding: SessionBinding::default() };
+    sessions.insert(&session_data).await.ok().unwrap();
+
+    let first = CachedSessionRepository::new(sessions.clone(), config());
+    let second = CachedSessionRepository::new(sessions, config());
+    spawn_revocation_listener(transport.subscribe(), second.clone(), tasks.shutdown_signal());
+
+    assert_eq!(0, second.get("session").await.ok().unwrap().authenticated_at);
+
+    let mut hash_service = MockHashService::new();
+    hash_service
+        .expect_verify()
+        .returning(|_, _| Ok(true));
+    let service = HashSessionService::new(first, users, hash_service, 1)
+        .with_revocations(RevocationPublisher::new(transport));
+    assert!(service.reauthenticate("session", "password", &ClientInfo::default()).await.is_ok());
+    settle().await;
+
+    assert!(second.get("session").await.ok().unwrap().authenticated_at > 0);
+}
+
+#[tokio::test(start_paused = true)]
+async fn refresh_user_evicts_locally() {
+    // what a single instance without a revocation backend runs
+    let tasks = BackgroundTasks::new();
+    let transport = LoopbackRevocationTransport::new(16);
+    let cache = peer().await;
+    spawn_revocation_listener(transport.subscribe(), cache.clone(), tasks.shutdown_signal());
+
+    let service = HashSessionService::new(cache.clone(), MemoryUserRepository::new(Arc::new(MemoryStore::new())), MockHashService::new(), 1)
+        .with_revocations(RevocationPublisher::new(transport));
+    service.refresh_user(1).await;
+    settle().await;
+
+    assert_eq!(2, misses_after_refetch(&cache).await);
+}
+
+#[tokio::test(start_paused = true)]
+async fn loopback_all_revocation_clears_cache() {
+    let tasks = BackgroundTasks::new();
+    let transport = LoopbackRevocationTransport::new(16);
+    let cache = peer().await;
+    spawn_revocation_listener(transport.subscribe(), cache.clone(), tasks.shutdown_signal());
+
+    transport.publish(&Revocation::All).await.unwrap();
+    settle().await;
+
+    assert_eq!(3, misses_after_refetch(&cache).await);
+}
+
+#[test]
+fn all_revocation_not_sent_between_instances() {
+    assert!(serde_json::to_string(&Revocation::All).is_err());
+    assert!(serde_json::from_str::<Revocation>(r#"{"type":"all"}"#).is_err());
+}
+
+#[tokio::test]
+async fn listener_stops_on_shutdown() {
+    let mut tasks = BackgroundTasks::new();
+    let transport = LoopbackRevocationTransport::new(16);
+    tasks.push(spawn_revocation_listener(transport.subscribe(), peer().await, tasks.shutdown_signal()));
+
+    // the transport is still open, so only the shutdown signal ends the listener
+    tokio::time::timeout(Duration::from_secs(1), tasks.shutdown()).await.unwrap();
+}
+
+// Needs a database reachable through the PG* variables, run with `cargo test -- --ignored`.
+#[tokio::test]
+#[ignore = "needs a Postgres database"]
+async fn postgres_reconnect_clears_caches() {
+    let mut tasks = BackgroundTasks::new();
+    let (transport, forwarder) = postgres::PgRevocationTransport::connect(pool(2).await.unwrap(), 16, tasks.shutdown_signal()).await.unwrap();
+    tasks.push(forwarder);
+    let mut receiver = transport.subscribe();
+
+    // drop the listener's connection as a database restart would
+    let admin = pool(1).await.unwrap();
+    admin.execute("SELECT pg_terminate_backend(pid) FROM pg_stat_activity WHERE pid <> pg_backend_pid() AND query LIKE 'LISTEN%'").await.unwrap();
+
+    let received = tokio::time::timeout(Duration::from_secs(5), receiver.recv()).await.unwrap().unwrap();
+    assert_eq!(Revocation::All, received);
+
+    // listening again after the reconnect
+    transport.publish(&Revocation::User { user_id: 1 }).await.unwrap();
+    let received = tokio::time::timeout(Duration::from_secs(5), receiver.recv()).await.unwrap().unwrap();
+    assert_eq!(Revocation::User { user_id: 1 }, received);
+
+    tokio::time::timeout(Duration::from_secs(1), tasks.shutdown()).await.unwrap();
+}
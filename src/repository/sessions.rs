@@ -3,11 +3,14 @@ use std::str::FromStr;
 use axum::async_trait;
 use http::StatusCode;
 use mockall::automock;
-use reqwest::{Client, Url};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
 use tracing::{error, warn};
 
 use crate::domain::sessions::{Session, SessionData};
 
+use super::client::HttpClient;
+
 pub enum SessionGetError {
     Missing,
     Unknown
@@ -22,25 +25,89 @@ pub enum SessionInsertError {
     Unknown
 }
 
+pub enum SessionUpdateError {
+    Missing,
+    Unknown
+}
+
 #[automock]
 #[async_trait]
 pub trait SessionRepository {
     async fn insert(&self, session_data: &SessionData) -> Result<(), SessionInsertError>;
     async fn get(&self, id: &str) -> Result<Session, SessionGetError>;
     async fn delete(&self, id: &str) -> Result<(), SessionDeleteError>;
+    /// Brings the expiry of a session forward to `expires`, a session that expires earlier is left untouched.
+    async fn shorten(&self, id: &str, expires: i64) -> Result<(), SessionUpdateError>;
+    /// Records that the user of a session just proved who they are again.
+    async fn mark_authenticated(&self, id: &str, authenticated_at: i64) -> Result<(), SessionUpdateError>;
+    /// Removes every session that expired before `before`, returning how many were removed.
+    async fn delete_expired(&self, before: i64) -> Result<u64, SessionDeleteError>;
+}
+
+#[derive(Debug, Serialize)]
+struct SessionExpiry {
+    expires: i64
+}
+
+#[derive(Debug, Serialize)]
+struct SessionAuthentication {
+    authenticated_at: i64
+}
+
+#[derive(Debug, Deserialize)]
+struct DeletedSessions {
+    deleted: u64
 }
 
 #[derive(Debug, Clone)]
 pub struct HttpSessionRepository {
     manager_sessions_url: Url,
-    client: Client
+    client: HttpClient
 }
 
 impl HttpSessionRepository {
-    pub fn new(url: &str) -> Self {
+    pub fn new(url: &str, client: HttpClient) -> Self {
         Self { 
             manager_sessions_url: Url::from_str(url).unwrap(),
-            client: Client::new()
+            client
+        }
+    }
+
+    /// Each updatable field is its own sub-resource, so that the resource manager never has to guess
+    /// the update from the body.
+    async fn send_update<T: Serialize>(&self, id: &str, field: &str, body: &T) -> Result<(), SessionUpdateError> {
+        let mut url = self.manager_sessions_url.clone();
+        match url.path_segments_mut() {
+            Ok(mut path) => path.push(field),
+            Err(_) => {
+                error!("Bad Resource Management URL: {:?}", self.manager_sessions_url);
+                return Err(SessionUpdateError::Unknown);
+            }
+        };
+
+        let req = self.client
+            .patch(url)
+            .bearer_auth(id)
+            .json(body);
+
+        let res = match self.client.send(req).await {
+            Ok(res) => res,
+            Err(err) => {
+                error!(%err);
+                return Err(SessionUpdateError::Unknown);
+            }
+        };
+
+        match res.status() {
+            StatusCode::NO_CONTENT => Ok(()),
+            StatusCode::NOT_FOUND => {
+                warn!("Missing session {:?}", id);
+                Err(SessionUpdateError::Missing)
+            },
+            code => {
+                error!("Unexpected code {:?}", code);
+                Err(SessionUpdateError::Unknown)
+            }
         }
     }
 }
@@ -54,7 +121,7 @@ impl SessionRepository for HttpSessionRepository {
             .json(&session_data);
 
 
-        let res = match req.send().await {
+        let res = match self.client.send(req).await {
             Ok(res) => res,
             Err(err) => {
                 error!(%err);
@@ -81,7 +148,7 @@ impl SessionRepository for HttpSessionRepository {
             .get(self.manager_sessions_url.clone())
             .bearer_auth(id);
     
-        let res = match req.send().await {
+        let res = match self.client.send(req).await {
             Ok(res) => res,
             Err(err) => {
                 error!(%err);
@@ -113,7 +180,7 @@ impl SessionRepository for HttpSessionRepository {
             .delete(self.manager_sessions_url.clone())
             .bearer_auth(id);
     
-        let res = match req.send().await {
+        let res = match self.client.send(req).await {
             Ok(res) => res,
             Err(err) => {
                 error!(%err);
@@ -129,4 +196,53 @@ impl SessionRepository for HttpSessionRepository {
             }
         }
     }
+
+    #[tracing::instrument(skip_all)]
+    async fn shorten(&self, id: &str, expires: i64) -> Result<(), SessionUpdateError> {
+        self.send_update(id, "expires", &SessionExpiry { expires }).await
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn mark_authenticated(&self, id: &str, authenticated_at: i64) -> Result<(), SessionUpdateError> {
+        self.send_update(id, "authenticated-at", &SessionAuthentication { authenticated_at }).await
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn delete_expired(&self, before: i64) -> Result<u64, SessionDeleteError> {
+        let mut url = self.manager_sessions_url.clone();
+        match url.path_segments_mut() {
+            Ok(mut path) => path.push("expired"),
+            Err(_) => {
+                error!("Bad Resource Management URL: {:?}", self.manager_sessions_url);
+                return Err(SessionDeleteError::Unknown);
+            }
+        };
+
+        let req = self.client
+            .delete(url)
+            .query(&[("before", before)]);
+
+        let res = match self.client.send(req).await {
+            Ok(res) => res,
+            Err(err) => {
+                error!(%err);
+                return Err(SessionDeleteError::Unknown);
+            }
+        };
+
+        let body = match res.status() {
+            StatusCode::OK => res.json::<DeletedSessions>(),
+            code => {
+                error!("Unexpected code {:?}", code);
+                return Err(SessionDeleteError::Unknown);
+            }
+        };
+
+        body.await
+            .map(|body| body.deleted)
+            .map_err(|err| {
+                error!(%err);
+                SessionDeleteError::Unknown
+            })
+    }
 }
@@ -0,0 +1,489 @@
+use axum::async_trait;
+use sqlx::{SqlitePool, sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions, SqliteRow}, Row};
+use tracing::{error, info, warn};
+
+use crate::domain::{sessions::{Session, SessionData, SessionBinding}, users::{User, UserData}, personal_tokens::{PersonalToken, PersonalTokenData}, tokens::{RefreshToken, RefreshTokenData}};
+
+use super::{is_unique_violation, users::{UserRepository, UserGetError, UserInsertError, UserUpdateError}, sessions::{SessionRepository, SessionGetError, SessionDeleteError, SessionInsertError, SessionUpdateError}, personal_tokens::{PersonalTokenRepository, PersonalTokenInsertError, PersonalTokenGetError, PersonalTokenDeleteError}, refresh_tokens::{RefreshTokenRepository, RefreshTokenInsertError, RefreshTokenGetError, RefreshTokenUseError, RefreshTokenRevokeError}};
+
+/// Opens (creating if needed) the database at `path` in WAL mode and applies pending migrations.
+pub async fn connect(path: &str, max_connections: u32) -> anyhow::Result<SqlitePool> {
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal)
+        .foreign_keys(true);
+
+    let pool = SqlitePoolOptions::new()
+        .max_connections(max_connections)
+        .connect_with(options)
+        .await?;
+
+    info!("Running database migrations");
+    sqlx::migrate!("./migrations/sqlite").run(&pool).await?;
+
+    Ok(pool)
+}
+
+fn user_from_row(row: &SqliteRow) -> Result<User, sqlx::Error> {
+    Ok(User {
+        id: row.try_get("user_id")?,
+        email: row.try_get("email")?,
+        password_hash: row.try_get("password_hash")?
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct SqliteUserRepository {
+    pool: SqlitePool
+}
+
+impl SqliteUserRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl UserRepository for SqliteUserRepository {
+    #[tracing::instrument(skip_all, fields(email = user_data.email))]
+    async fn insert(&self, user_data: UserData) -> Result<(), UserInsertError> {
+        let res = sqlx::query("INSERT INTO users (email, password_hash) VALUES (?, ?)")
+            .bind(&user_data.email)
+            .bind(&user_data.password_hash)
+            .execute(&self.pool)
+            .await;
+
+        match res {
+            Ok(_) => Ok(()),
+            Err(err) if is_unique_violation(&err) => {
+                warn!("Duplicate user");
+                Err(UserInsertError::Duplicate)
+            },
+            Err(err) => {
+                error!(%err);
+                Err(UserInsertError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn get_by_email(&self, email: &str) -> Result<User, UserGetError> {
+        let res = sqlx::query("SELECT id AS user_id, email, password_hash FROM users WHERE email = ?")
+            .bind(email)
+            .fetch_optional(&self.pool)
+            .await;
+
+        match res.and_then(|row| row.as_ref().map(user_from_row).transpose()) {
+            Ok(Some(user)) => Ok(user),
+            Ok(None) => {
+                warn!("Missing user");
+                Err(UserGetError::Missing)
+            },
+            Err(err) => {
+                error!(%err);
+                Err(UserGetError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(self, password_hash))]
+    async fn update_password(&self, id: i32, password_hash: &str) -> Result<(), UserUpdateError> {
+        let res = sqlx::query("UPDATE users SET password_hash = ? WHERE id = ?")
+            .bind(password_hash)
+            .bind(id)
+            .execute(&self.pool)
+            .await;
+
+        match res {
+            Ok(res) if res.rows_affected() == 0 => {
+                warn!("Missing user");
+                Err(UserUpdateError::Missing)
+            },
+            Ok(_) => Ok(()),
+            Err(err) => {
+                error!(%err);
+                Err(UserUpdateError::Unknown)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SqliteSessionRepository {
+    pool: SqlitePool
+}
+
+impl SqliteSessionRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl SessionRepository for SqliteSessionRepository {
+    #[tracing::instrument(skip_all, fields(user_id = session_data.user_id))]
+    async fn insert(&self, session_data: &SessionData) -> Result<(), SessionInsertError> {
+        let res = sqlx::query("INSERT INTO sessions (id, user_id, expires, authenticated_at, user_agent_family, ip_subnet) VALUES (?, ?, ?, ?, ?, ?)")
+            .bind(&session_data.id)
+            .bind(session_data.user_id)
+            .bind(session_data.expires)
+            .bind(session_data.authenticated_at)
+            .bind(&session_data.binding.user_agent_family)
+            .bind(&session_data.binding.ip_subnet)
+            .execute(&self.pool)
+            .await;
+
+        match res {
+            Ok(_) => Ok(()),
+            Err(err) if is_unique_violation(&err) => {
+                warn!("Duplicate session {:?}", session_data);
+                Err(SessionInsertError::Duplicate)
+            },
+            Err(err) => {
+                error!(%err);
+                Err(SessionInsertError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn get(&self, id: &str) -> Result<Session, SessionGetError> {
+        let res = sqlx::query(
+            "SELECT s.id, s.expires, s.authenticated_at, s.user_agent_family, s.ip_subnet, u.id AS user_id, u.email, u.password_hash
+             FROM sessions s JOIN users u ON u.id = s.user_id
+             WHERE s.id = ?"
+        )
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await;
+
+        let session = res.and_then(|row| row.map(|row| Ok(Session {
+            id: row.try_get("id")?,
+            user: user_from_row(&row)?,
+            expires: row.try_get("expires")?,
+            authenticated_at: row.try_get("authenticated_at")?,
+            binding: SessionBinding {
+                user_agent_family: row.try_get("user_agent_family")?,
+                ip_subnet: row.try_get("ip_subnet")?
+            }
+        })).transpose());
+
+        match session {
+            Ok(Some(session)) => Ok(session),
+            Ok(None) => {
+                warn!("Missing session {:?}", id);
+                Err(SessionGetError::Missing)
+            },
+            Err(err) => {
+                error!(%err);
+                Err(SessionGetError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn delete(&self, id: &str) -> Result<(), SessionDeleteError> {
+        sqlx::query("DELETE FROM sessions WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+            .map_err(|err| {
+                error!(%err);
+                SessionDeleteError::Unknown
+            })
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn shorten(&self, id: &str, expires: i64) -> Result<(), SessionUpdateError> {
+        let res = sqlx::query("UPDATE sessions SET expires = MIN(expires, ?) WHERE id = ?")
+            .bind(expires)
+            .bind(id)
+            .execute(&self.pool)
+            .await;
+
+        match res {
+            Ok(res) if res.rows_affected() == 0 => {
+                warn!("Missing session {:?}", id);
+                Err(SessionUpdateError::Missing)
+            },
+            Ok(_) => Ok(()),
+            Err(err) => {
+                error!(%err);
+                Err(SessionUpdateError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn mark_authenticated(&self, id: &str, authenticated_at: i64) -> Result<(), SessionUpdateError> {
+        let res = sqlx::query("UPDATE sessions SET authenticated_at = ? WHERE id = ?")
+            .bind(authenticated_at)
+            .bind(id)
+            .execute(&self.pool)
+            .await;
+
+        match res {
+            Ok(res) if res.rows_affected() == 0 => {
+                warn!("Missing session {:?}", id);
+                Err(SessionUpdateError::Missing)
+            },
+            Ok(_) => Ok(()),
+            Err(err) => {
+                error!(%err);
+                Err(SessionUpdateError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn delete_expired(&self, before: i64) -> Result<u64, SessionDeleteError> {
+        sqlx::query("DELETE FROM sessions WHERE expires < ?")
+            .bind(before)
+            .execute(&self.pool)
+            .await
+            .map(|res| res.rows_affected())
+            .map_err(|err| {
+                error!(%err);
+                SessionDeleteError::Unknown
+            })
+    }
+}
+
+fn personal_token_from_row(row: &SqliteRow) -> Result<PersonalToken, sqlx::Error> {
+    let scopes: String = row.try_get("scopes")?;
+    Ok(PersonalToken {
+        id: row.try_get("id")?,
+        user_id: row.try_get("user_id")?,
+        name: row.try_get("name")?,
+        scopes: serde_json::from_str(&scopes).map_err(|err| sqlx::Error::Decode(err.into()))?,
+        expires: row.try_get("expires")?
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct SqlitePersonalTokenRepository {
+    pool: SqlitePool
+}
+
+impl SqlitePersonalTokenRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl PersonalTokenRepository for SqlitePersonalTokenRepository {
+    #[tracing::instrument(skip_all, fields(user_id = token_data.user_id))]
+    async fn insert(&self, token_data: &PersonalTokenData) -> Result<PersonalToken, PersonalTokenInsertError> {
+        let scopes = serde_json::to_string(&token_data.scopes).map_err(|err| {
+            error!(%err);
+            PersonalTokenInsertError::Unknown
+        })?;
+
+        let res = sqlx::query("INSERT INTO personal_tokens (user_id, name, token_hash, scopes, expires) VALUES (?, ?, ?, ?, ?) RETURNING id")
+            .bind(token_data.user_id)
+            .bind(&token_data.name)
+            .bind(&token_data.token_hash)
+            .bind(scopes)
+            .bind(token_data.expires)
+            .fetch_one(&self.pool)
+            .await
+            .and_then(|row| row.try_get("id"));
+
+        match res {
+            Ok(id) => Ok(PersonalToken {
+                id,
+                user_id: token_data.user_id,
+                name: token_data.name.clone(),
+                scopes: token_data.scopes.clone(),
+                expires: token_data.expires
+            }),
+            Err(err) if is_unique_violation(&err) => {
+                warn!("Duplicate personal token");
+                Err(PersonalTokenInsertError::Duplicate)
+            },
+            Err(err) => {
+                error!(%err);
+                Err(PersonalTokenInsertError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn get_by_hash(&self, token_hash: &str) -> Result<PersonalToken, PersonalTokenGetError> {
+        let res = sqlx::query("SELECT id, user_id, name, scopes, expires FROM personal_tokens WHERE token_hash = ?")
+            .bind(token_hash)
+            .fetch_optional(&self.pool)
+            .await;
+
+        match res.and_then(|row| row.as_ref().map(personal_token_from_row).transpose()) {
+            Ok(Some(token)) => Ok(token),
+            Ok(None) => {
+                warn!("Missing personal token");
+                Err(PersonalTokenGetError::Missing)
+            },
+            Err(err) => {
+                error!(%err);
+                Err(PersonalTokenGetError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn list(&self, user_id: i32) -> Result<Vec<PersonalToken>, PersonalTokenGetError> {
+        let res = sqlx::query("SELECT id, user_id, name, scopes, expires FROM personal_tokens WHERE user_id = ? ORDER BY id")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await;
+
+        res.and_then(|rows| rows.iter().map(personal_token_from_row).collect())
+            .map_err(|err| {
+                error!(%err);
+                PersonalTokenGetError::Unknown
+            })
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn delete(&self, user_id: i32, id: i32) -> Result<(), PersonalTokenDeleteError> {
+        let res = sqlx::query("DELETE FROM personal_tokens WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id)
+            .execute(&self.pool)
+            .await;
+
+        match res {
+            Ok(res) if res.rows_affected() == 0 => {
+                warn!("Missing personal token");
+                Err(PersonalTokenDeleteError::Missing)
+            },
+            Ok(_) => Ok(()),
+            Err(err) => {
+                error!(%err);
+                Err(PersonalTokenDeleteError::Unknown)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SqliteRefreshTokenRepository {
+    pool: SqlitePool
+}
+
+impl SqliteRefreshTokenRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl RefreshTokenRepository for SqliteRefreshTokenRepository {
+    #[tracing::instrument(skip_all, fields(user_id = token_data.user_id))]
+    async fn insert(&self, token_data: &RefreshTokenData) -> Result<(), RefreshTokenInsertError> {
+        let res = sqlx::query("INSERT INTO refresh_tokens (id, family_id, user_id, session_id, expires) VALUES (?, ?, ?, ?, ?)")
+            .bind(&token_data.id)
+            .bind(&token_data.family_id)
+            .bind(token_data.user_id)
+            .bind(&token_data.session_id)
+            .bind(token_data.expires)
+            .execute(&self.pool)
+            .await;
+
+        match res {
+            Ok(_) => Ok(()),
+            Err(err) if is_unique_violation(&err) => {
+                warn!("Duplicate refresh token");
+                Err(RefreshTokenInsertError::Duplicate)
+            },
+            Err(err) => {
+                error!(%err);
+                Err(RefreshTokenInsertError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn get(&self, id: &str) -> Result<RefreshToken, RefreshTokenGetError> {
+        let res = sqlx::query("SELECT id, family_id, user_id, session_id, expires, used FROM refresh_tokens WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await;
+
+        let token = res.and_then(|row| row.map(|row| Ok(RefreshToken {
+            id: row.try_get("id")?,
+            family_id: row.try_get("family_id")?,
+            user_id: row.try_get("user_id")?,
+            session_id: row.try_get("session_id")?,
+            expires: row.try_get("expires")?,
+            used: row.try_get("used")?
+        })).transpose());
+
+        match token {
+            Ok(Some(token)) => Ok(token),
+            Ok(None) => {
+                warn!("Missing refresh token");
+                Err(RefreshTokenGetError::Missing)
+            },
+            Err(err) => {
+                error!(%err);
+                Err(RefreshTokenGetError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn mark_used(&self, id: &str) -> Result<(), RefreshTokenUseError> {
+        // the used = 0 condition lets only one of two concurrent uses update the row
+        let res = sqlx::query("UPDATE refresh_tokens SET used = 1 WHERE id = ? AND used = 0")
+            .bind(id)
+            .execute(&self.pool)
+            .await;
+
+        match res {
+            Ok(res) if res.rows_affected() > 0 => return Ok(()),
+            Ok(_) => (),
+            Err(err) => {
+                error!(%err);
+                return Err(RefreshTokenUseError::Unknown);
+            }
+        };
+
+        let exists = sqlx::query("SELECT 1 FROM refresh_tokens WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await;
+
+        match exists {
+            Ok(Some(_)) => {
+                warn!("Refresh token already used");
+                Err(RefreshTokenUseError::AlreadyUsed)
+            },
+            Ok(None) => {
+                warn!("Missing refresh token");
+                Err(RefreshTokenUseError::Missing)
+            },
+            Err(err) => {
+                error!(%err);
+                Err(RefreshTokenUseError::Unknown)
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn revoke_family(&self, family_id: &str) -> Result<(), RefreshTokenRevokeError> {
+        sqlx::query("DELETE FROM refresh_tokens WHERE family_id = ?")
+            .bind(family_id)
+            .execute(&self.pool)
+            .await
+            .map(|_| ())
+            .map_err(|err| {
+                error!(%err);
+                RefreshTokenRevokeError::Unknown
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,213 @@
+use chrono::Utc;
+
+use super::*;
+
+async fn pool() -> SqlitePool {
+    // every connection to an in-memory database opens a fresh one, so keep a single connection
+    connect(":memory:", 1).await.unwrap()
+}
+
+fn mock_user_data() -> UserData {
+    UserData {
+        email: String::from("email@email.com"),
+        password_hash: String::from("hash")
+    }
+}
+
+fn mock_session_data(id: &str, expires: i64) -> SessionData {
+    SessionData {
+        id: String::from(id),
+        user_id: 1,
+        expires,
+        authenticated_at: 0,
+        binding: SessionBinding::default()
+    }
+}
+
+#[tokio::test]
+async fn users_insert_duplicate() {
+    let users = SqliteUserRepository::new(pool().await);
+
+    assert!(users.insert(mock_user_data()).await.is_ok());
+    assert!(matches!(users.insert(mock_user_data()).await, Err(UserInsertError::Duplicate)));
+}
+
+#[tokio::test]
+async fn users_get_missing() {
+    let users = SqliteUserRepository::new(pool().await);
+
+    assert!(matches!(users.get_by_email("email@email.com").await, Err(UserGetError::Missing)));
+}
+
+#[tokio::test]
+async fn sessions_insert_and_get() {
+    let pool = pool().await;
+    let users = SqliteUserRepository::new(pool.clone());
+    let sessions = SqliteSessionRepository::new(pool);
+    users.insert(mock_user_data()).await.ok().unwrap();
+
+    assert!(sessions.insert(&mock_session_data("session", 1000)).await.is_ok());
+    assert!(matches!(sessions.insert(&mock_session_data("session", 1000)).await, Err(SessionInsertError::Duplicate)));
+
+    let session = sessions.get("session").await.ok().unwrap();
+    assert_eq!("email@email.com", session.user.email);
+    assert_eq!(1000, session.expires);
+
+    assert!(sessions.delete("session").await.is_ok());
+    assert!(matches!(sessions.get("session").await, Err(SessionGetError::Missing)));
+}
+
+#[tokio::test]
+async fn sessions_delete_expired() {
+    let pool = pool().await;
+    let users = SqliteUserRepository::new(pool.clone());
+    let sessions = SqliteSessionRepository::new(pool);
+    users.insert(mock_user_data()).await.ok().unwrap();
+
+    let now = Utc::now().timestamp();
+    sessions.insert(&mock_session_data("expired", now - 100)).await.ok().unwrap();
+    sessions.insert(&mock_session_data("active", now + 100)).await.ok().unwrap();
+
+    assert_eq!(Some(1), sessions.delete_expired(now).await.ok());
+    assert!(matches!(sessions.get("expired").await, Err(SessionGetError::Missing)));
+    assert!(sessions.get("active").await.is_ok());
+}
+
+#[tokio::test]
+async fn sessions_binding_roundtrip() {
+    let pool = pool().await;
+    let users = SqliteUserRepository::new(pool.clone());
+    let sessions = SqliteSessionRepository::new(pool);
+    users.insert(mock_user_data()).await.ok().unwrap();
+
+    let binding = SessionBinding {
+        user_agent_family: Some(String::from("Firefox")),
+        ip_subnet: Some(String::from("203.0.113.0/24"))
+    };
+    sessions.insert(&SessionData { binding: binding.clone(), ..mock_session_data("session", 1000) }).await.ok().unwrap();
+
+    assert_eq!(binding, sessions.get("session").await.ok().unwrap().binding);
+}
+
+#[tokio::test]
+async fn sessions_shorten() {
+    let pool = pool().await;
+    let users = SqliteUserRepository::new(pool.clone());
+    let sessions = SqliteSessionRepository::new(pool);
+    users.insert(mock_user_data()).await.ok().unwrap();
+    sessions.insert(&mock_session_data("session", 1000)).await.ok().unwrap();
+
+    assert!(sessions.shorten("session", 500).await.is_ok());
+    assert!(sessions.shorten("session", 800).await.is_ok());
+    assert_eq!(500, sessions.get("session").await.ok().unwrap().expires);
+    assert!(matches!(sessions.shorten("missing", 500).await, Err(SessionUpdateError::Missing)));
+}
+
+#[tokio::test]
+async fn sessions_mark_authenticated() {
+    let pool = pool().await;
+    let users = SqliteUserRepository::new(pool.clone());
+    let sessions = SqliteSessionRepository::new(pool);
+    users.insert(mock_user_data()).await.ok().unwrap();
+    sessions.insert(&SessionData { authenticated_at: 100, ..mock_session_data("session", 1000) }).await.ok().unwrap();
+
+    assert_eq!(100, sessions.get("session").await.ok().unwrap().authenticated_at);
+    assert!(sessions.mark_authenticated("session", 900).await.is_ok());
+    assert_eq!(900, sessions.get("session").await.ok().unwrap().authenticated_at);
+    assert!(matches!(sessions.mark_authenticated("missing", 900).await, Err(SessionUpdateError::Missing)));
+}
+
+#[tokio::test]
+async fn users_update_password() {
+    let users = SqliteUserRepository::new(pool().await);
+    users.insert(mock_user_data()).await.ok().unwrap();
+
+    assert!(users.update_password(1, "new_hash").await.is_ok());
+    assert_eq!("new_hash", users.get_by_email("email@email.com").await.ok().unwrap().password_hash);
+    assert!(matches!(users.update_password(2, "new_hash").await, Err(UserUpdateError::Missing)));
+}
+
+fn mock_personal_token_data(token_hash: &str) -> PersonalTokenData {
+    PersonalTokenData {
+        user_id: 1,
+        name: String::from("ci"),
+        token_hash: String::from(token_hash),
+        scopes: vec![String::from("documents:read"), String::from("documents:write")],
+        expires: Some(1000)
+    }
+}
+
+fn mock_refresh_token_data(id: &str, family_id: &str) -> RefreshTokenData {
+    RefreshTokenData {
+        id: String::from(id),
+        family_id: String::from(family_id),
+        user_id: 1,
+        session_id: String::from("session"),
+        expires: 1000
+    }
+}
+
+#[tokio::test]
+async fn personal_tokens_insert_and_get() {
+    let pool = pool().await;
+    let users = SqliteUserRepository::new(pool.clone());
+    let tokens = SqlitePersonalTokenRepository::new(pool);
+    users.insert(mock_user_data()).await.ok().unwrap();
+
+    let token = tokens.insert(&mock_personal_token_data("hash")).await.ok().unwrap();
+    assert!(matches!(tokens.insert(&mock_personal_token_data("hash")).await, Err(PersonalTokenInsertError::Duplicate)));
+
+    assert_eq!(token, tokens.get_by_hash("hash").await.ok().unwrap());
+    assert_eq!(vec![String::from("documents:read"), String::from("documents:write")], token.scopes);
+    assert!(matches!(tokens.get_by_hash("missing").await, Err(PersonalTokenGetError::Missing)));
+}
+
+#[tokio::test]
+async fn personal_tokens_list_and_delete() {
+    let pool = pool().await;
+    let users = SqliteUserRepository::new(pool.clone());
+    let tokens = SqlitePersonalTokenRepository::new(pool);
+    users.insert(mock_user_data()).await.ok().unwrap();
+    let first = tokens.insert(&mock_personal_token_data("first")).await.ok().unwrap();
+    let second = tokens.insert(&PersonalTokenData { expires: None, ..mock_personal_token_data("second") }).await.ok().unwrap();
+
+    assert_eq!(vec![first.clone(), second], tokens.list(1).await.ok().unwrap());
+
+    assert!(matches!(tokens.delete(2, first.id).await, Err(PersonalTokenDeleteError::Missing)));
+    assert!(tokens.delete(1, first.id).await.is_ok());
+    assert!(matches!(tokens.delete(1, first.id).await, Err(PersonalTokenDeleteError::Missing)));
+    assert_eq!(1, tokens.list(1).await.ok().unwrap().len());
+}
+
+#[tokio::test]
+async fn refresh_tokens_mark_used() {
+    let pool = pool().await;
+    let users = SqliteUserRepository::new(pool.clone());
+    let tokens = SqliteRefreshTokenRepository::new(pool);
+    users.insert(mock_user_data()).await.ok().unwrap();
+
+    assert!(tokens.insert(&mock_refresh_token_data("token", "family")).await.is_ok());
+    assert!(matches!(tokens.insert(&mock_refresh_token_data("token", "family")).await, Err(RefreshTokenInsertError::Duplicate)));
+    assert!(!tokens.get("token").await.ok().unwrap().used);
+
+    assert!(tokens.mark_used("token").await.is_ok());
+    assert!(matches!(tokens.mark_used("token").await, Err(RefreshTokenUseError::AlreadyUsed)));
+    assert!(matches!(tokens.mark_used("missing").await, Err(RefreshTokenUseError::Missing)));
+    assert!(tokens.get("token").await.ok().unwrap().used);
+}
+
+#[tokio::test]
+async fn refresh_tokens_revoke_family() {
+    let pool = pool().await;
+    let users = SqliteUserRepository::new(pool.clone());
+    let tokens = SqliteRefreshTokenRepository::new(pool);
+    users.insert(mock_user_data()).await.ok().unwrap();
+    tokens.insert(&mock_refresh_token_data("first", "family")).await.ok().unwrap();
+    tokens.insert(&mock_refresh_token_data("second", "family")).await.ok().unwrap();
+    tokens.insert(&mock_refresh_token_data("other", "other_family")).await.ok().unwrap();
+
+    assert!(tokens.revoke_family("family").await.is_ok());
+    assert!(matches!(tokens.get("first").await, Err(RefreshTokenGetError::Missing)));
+    assert!(matches!(tokens.get("second").await, Err(RefreshTokenGetError::Missing)));
+    assert!(tokens.get("other").await.is_ok());
+}
@@ -3,11 +3,14 @@ use std::str::FromStr;
 use axum::async_trait;
 use http::StatusCode;
 use mockall::automock;
-use reqwest::{Client, Url};
+use reqwest::Url;
+use serde::Serialize;
 use tracing::{error, warn};
 
 use crate::domain::users::{User, UserData};
 
+use super::client::HttpClient;
+
 pub enum UserGetError {
     Missing,
     Unknown
@@ -18,25 +21,36 @@ pub enum UserInsertError {
     Unknown
 }
 
+pub enum UserUpdateError {
+    Missing,
+    Unknown
+}
+
 #[automock]
 #[async_trait]
 pub trait UserRepository {
     async fn get_by_email(&self, email: &str) -> Result<User, UserGetError>;
     async fn insert(&self, user_data: UserData) -> Result<(), UserInsertError>;
+    async fn update_password(&self, id: i32, password_hash: &str) -> Result<(), UserUpdateError>;
+}
+
+#[derive(Debug, Serialize)]
+struct PasswordUpdate<'a> {
+    password_hash: &'a str
 }
 
 
 #[derive(Debug, Clone)]
 pub struct HttpUserRepository {
     manager_users_url: Url,
-    client: Client
+    client: HttpClient
 }
 
 impl HttpUserRepository {
-    pub fn new(url: &str) -> Self {
+    pub fn new(url: &str, client: HttpClient) -> Self {
         Self {
             manager_users_url: Url::from_str(url).unwrap(),
-            client: Client::new()
+            client
         }
     }
 }
@@ -49,7 +63,7 @@ impl UserRepository for HttpUserRepository {
             .post(self.manager_users_url.clone())
             .json(&user_data);
 
-        let res = match req.send().await {
+        let res = match self.client.send(req).await {
             Ok(res) => res,
             Err(err) => {
                 error!(%err);
@@ -83,7 +97,7 @@ impl UserRepository for HttpUserRepository {
         
         let req = self.client.get(url);
 
-        let res = match req.send().await {
+        let res = match self.client.send(req).await {
             Ok(res) => res,
             Err(err) => {
                 error!(%err);
@@ -108,4 +122,40 @@ impl UserRepository for HttpUserRepository {
             UserGetError::Unknown
         })
     }
+
+    #[tracing::instrument(skip(self, password_hash))]
+    async fn update_password(&self, id: i32, password_hash: &str) -> Result<(), UserUpdateError> {
+        let mut url = self.manager_users_url.clone();
+        match url.path_segments_mut() {
+            Ok(mut path) => path.extend([id.to_string().as_str(), "password"]),
+            Err(_) => {
+                error!("Bad Resource Management URL: {:?}", self.manager_users_url);
+                return Err(UserUpdateError::Unknown);
+            }
+        };
+
+        let req = self.client
+            .patch(url)
+            .json(&PasswordUpdate { password_hash });
+
+        let res = match self.client.send(req).await {
+            Ok(res) => res,
+            Err(err) => {
+                error!(%err);
+                return Err(UserUpdateError::Unknown);
+            }
+        };
+
+        match res.status() {
+            StatusCode::NO_CONTENT => Ok(()),
+            StatusCode::NOT_FOUND => {
+                warn!("Missing user");
+                Err(UserUpdateError::Missing)
+            },
+            code => {
+                error!("Unexpected code {:?}", code);
+                Err(UserUpdateError::Unknown)
+            }
+        }
+    }
 }
@@ -1,28 +1,278 @@
+mod personal_tokens;
 mod sessions;
+mod tokens;
 mod users;
 
-use axum::Router;
+use std::{fmt::Debug, sync::Arc, time::Duration};
 
-use crate::{service::{sessions::HashSessionService, hash::BcryptHashService, users::HashUserService}, repository::{sessions::HttpSessionRepository, users::HttpUserRepository}, constants::{RESOURCE_MANAGEMENT_URL, SESSION_ID_GEN_RETRIES}};
+use axum::{Router, middleware};
 
-use self::{users::users_router, sessions::sessions_router};
+use crate::{service::{sessions::{SessionService, HashSessionService, SessionBindingPolicy}, hash::BcryptHashService, users::{UserService, HashUserService}, tokens::{TokenService, JwtTokenService, load_signing_keys}, refresh_tokens::{RefreshTokenService, RotatingRefreshTokenService}, personal_tokens::{PersonalTokenService, HashPersonalTokenService}}, repository::{revocation::{RevocationBackend, RevocationPublisher, LoopbackRevocationTransport, spawn_revocation_listener, postgres::PgRevocationTransport}, cache::{CachedSessionRepository, SessionCacheConfig}, client::{HttpClient, HttpClientConfig}, sessions::HttpSessionRepository, users::HttpUserRepository, refresh_tokens::HttpRefreshTokenRepository, personal_tokens::HttpPersonalTokenRepository, backend::{RepositoryBackend, AnyUserRepository, AnySessionRepository, AnyPersonalTokenRepository, AnyRefreshTokenRepository}, postgres::{self, PgUserRepository, PgSessionRepository, PgPersonalTokenRepository, PgRefreshTokenRepository}, memory::{MemoryStore, spawn_snapshot_writer, MemoryUserRepository, MemorySessionRepository, MemoryPersonalTokenRepository, MemoryRefreshTokenRepository}, sqlite::{self, SqliteUserRepository, SqliteSessionRepository, SqlitePersonalTokenRepository, SqliteRefreshTokenRepository}}, constants::{RESOURCE_MANAGEMENT_URL, SESSION_ID_GEN_RETRIES, JWT_SIGNING_KEYS, ACCESS_TOKEN_LENGTH_SECONDS, REFRESH_TOKEN_LENGTH_SECONDS, REPOSITORY_BACKEND, PG_MAX_CONNECTIONS, MEMORY_SNAPSHOT_PATH, SQLITE_PATH, SQLITE_MAX_CONNECTIONS, MEMORY_SNAPSHOT_DEBOUNCE_MILLIS, SESSION_PURGE_INTERVAL_SECONDS, SESSION_PURGE_JITTER_SECONDS, METRICS_REPORT_INTERVAL_SECONDS, SESSION_CACHE_CAPACITY, SESSION_CACHE_TTL_SECONDS, SESSION_CACHE_NEGATIVE_TTL_SECONDS, REVOCATION_BACKEND, REVOCATION_CHANNEL_CAPACITY, CSRF_PROTECTION, SESSION_BINDING, SESSION_BINDING_ENFORCEMENT, SESSION_BINDING_IPV4_PREFIX, SESSION_BINDING_IPV6_PREFIX, SESSION_COOKIE, SESSION_COOKIE_SEAL, CSRF_COOKIE, BREACHED_PASSWORDS_PATH, PASSWORD_POLICY}, passwords::BreachedPasswords};
+
+use crate::{tasks::{BackgroundTasks, SessionPurgeConfig, SessionPurgeMetrics, spawn_session_purge, spawn_metrics_report}, csrf::{CsrfConfig, csrf_protection}, cors::CorsConfig};
+
+use self::{users::users_router, sessions::sessions_router, tokens::tokens_router, personal_tokens::personal_tokens_router};
+
+struct Repositories {
+    users: AnyUserRepository,
+    sessions: AnySessionRepository,
+    personal_tokens: AnyPersonalTokenRepository,
+    refresh_tokens: AnyRefreshTokenRepository
+}
+
+async fn repositories(backend: RepositoryBackend, client: &HttpClient, tasks: &mut BackgroundTasks) -> anyhow::Result<Repositories> {
+    match backend {
+        RepositoryBackend::Http => {
+            let users_url = RESOURCE_MANAGEMENT_URL.clone() + "/users";
+            let sessions_url = RESOURCE_MANAGEMENT_URL.clone() + "/sessions";
+            let personal_tokens_url = RESOURCE_MANAGEMENT_URL.clone() + "/personal-tokens";
+            let refresh_tokens_url = RESOURCE_MANAGEMENT_URL.clone() + "/refresh-tokens";
+            Ok(Repositories {
+                users: AnyUserRepository::Http(HttpUserRepository::new(users_url.as_str(), client.clone())),
+                sessions: AnySessionRepository::Http(HttpSessionRepository::new(sessions_url.as_str(), client.clone())),
+                personal_tokens: AnyPersonalTokenRepository::Http(HttpPersonalTokenRepository::new(personal_tokens_url.as_str(), client.clone())),
+                refresh_tokens: AnyRefreshTokenRepository::Http(HttpRefreshTokenRepository::new(refresh_tokens_url.as_str(), client.clone()))
+            })
+        },
+        RepositoryBackend::Postgres => {
+            let pool = postgres::connect(*PG_MAX_CONNECTIONS).await?;
+            Ok(Repositories {
+                users: AnyUserRepository::Postgres(PgUserRepository::new(pool.clone())),
+                sessions: AnySessionRepository::Postgres(PgSessionRepository::new(pool.clone())),
+                personal_tokens: AnyPersonalTokenRepository::Postgres(PgPersonalTokenRepository::new(pool.clone())),
+                refresh_tokens: AnyRefreshTokenRepository::Postgres(PgRefreshTokenRepository::new(pool))
+            })
+        },
+        RepositoryBackend::Sqlite => {
+            let pool = sqlite::connect(SQLITE_PATH.as_str(), *SQLITE_MAX_CONNECTIONS).await?;
+            Ok(Repositories {
+                users: AnyUserRepository::Sqlite(SqliteUserRepository::new(pool.clone())),
+                sessions: AnySessionRepository::Sqlite(SqliteSessionRepository::new(pool.clone())),
+                personal_tokens: AnyPersonalTokenRepository::Sqlite(SqlitePersonalTokenRepository::new(pool.clone())),
+                refresh_tokens: AnyRefreshTokenRepository::Sqlite(SqliteRefreshTokenRepository::new(pool))
+            })
+        },
+        RepositoryBackend::Memory => {
+            let store = match MEMORY_SNAPSHOT_PATH.as_str() {
+                "" => Arc::new(MemoryStore::new()),
+                path => {
+                    let store = Arc::new(MemoryStore::with_snapshot(path)?);
+                    let debounce = Duration::from_millis(*MEMORY_SNAPSHOT_DEBOUNCE_MILLIS);
+                    tasks.push(spawn_snapshot_writer(store.clone(), debounce, tasks.shutdown_signal()));
+                    store
+                }
+            };
+            Ok(Repositories {
+                users: AnyUserRepository::Memory(MemoryUserRepository::new(store.clone())),
+                sessions: AnySessionRepository::Memory(MemorySessionRepository::new(store.clone())),
+                personal_tokens: AnyPersonalTokenRepository::Memory(MemoryPersonalTokenRepository::new(store.clone())),
+                refresh_tokens: AnyRefreshTokenRepository::Memory(MemoryRefreshTokenRepository::new(store))
+            })
+        }
+    }
+}
+
+/// Without a backend revocations still reach this instance's own cache, e.g. to drop sessions
+/// holding a changed password hash.
+async fn revocations(backend: RevocationBackend, tasks: &mut BackgroundTasks) -> anyhow::Result<RevocationPublisher> {
+    match backend {
+        RevocationBackend::None => Ok(RevocationPublisher::new(LoopbackRevocationTransport::new(*REVOCATION_CHANNEL_CAPACITY))),
+        RevocationBackend::Postgres => {
+            let pool = postgres::pool(2).await?;
+            let (transport, forwarder) = PgRevocationTransport::connect(pool, *REVOCATION_CHANNEL_CAPACITY, tasks.shutdown_signal()).await?;
+            tasks.push(forwarder);
+            Ok(RevocationPublisher::new(transport))
+        }
+    }
+}
+
+/// Services shared by the routers, generic so that any implementation, including mocks, can be mounted.
+#[derive(Debug)]
+pub struct AppState<U, S, P> {
+    pub users_service: Arc<U>,
+    pub sessions_service: Arc<S>,
+    pub personal_tokens_service: Arc<P>,
+    /// Empty unless set, which disables breach screening.
+    pub breached_passwords: Arc<BreachedPasswords>
+}
+
+impl<U, S, P> AppState<U, S, P> {
+    pub fn new(users_service: U, sessions_service: S, personal_tokens_service: P) -> Self {
+        Self {
+            users_service: Arc::new(users_service),
+            sessions_service: Arc::new(sessions_service),
+            personal_tokens_service: Arc::new(personal_tokens_service),
+            breached_passwords: Arc::new(BreachedPasswords::default())
+        }
+    }
+}
+
+pub fn app_router<U, S, P>(state: &AppState<U, S, P>) -> Router
+where
+    U: UserService + Debug + Send + Sync + 'static,
+    S: SessionService + Debug + Send + Sync + 'static,
+    P: PersonalTokenService + Debug + Send + Sync + 'static
+{
+    Router::new()
+        .nest("/users", users_router(state.users_service.clone(), state.sessions_service.clone(), state.breached_passwords.clone()))
+        .nest("/sessions", sessions_router(state.sessions_service.clone(), state.personal_tokens_service.clone()))
+        .nest("/personal-tokens", personal_tokens_router(state.sessions_service.clone(), state.personal_tokens_service.clone()))
+}
+
+/// Assembles the authentication routes from any set of service implementations,
+/// e.g. to mount them inside another application.
+#[derive(Debug)]
+pub struct RouterBuilder<U, S, P> {
+    state: AppState<U, S, P>,
+    tokens: Option<Router>,
+    csrf: Option<CsrfConfig>,
+    cors: Option<CorsConfig>
+}
+
+impl<U, S, P> RouterBuilder<U, S, P>
+where
+    U: UserService + Debug + Send + Sync + 'static,
+    S: SessionService + Debug + Send + Sync + 'static,
+    P: PersonalTokenService + Debug + Send + Sync + 'static
+{
+    pub fn new(users_service: U, sessions_service: S, personal_tokens_service: P) -> Self {
+        Self::from_state(AppState::new(users_service, sessions_service, personal_tokens_service))
+    }
+
+    pub fn from_state(state: AppState<U, S, P>) -> Self {
+        Self { state, tokens: None, csrf: None, cors: None }
+    }
+
+    /// Rejects new passwords found in the given list, on registration and password change.
+    pub fn with_breached_passwords(mut self, breached_passwords: BreachedPasswords) -> Self {
+        self.state.breached_passwords = Arc::new(breached_passwords);
+        self
+    }
+
+    /// Adds the access token, refresh and JWKS routes.
+    pub fn with_tokens<T, R>(mut self, token_service: T, refresh_service: R) -> Self
+    where
+        T: TokenService + Debug + Send + Sync + 'static,
+        R: RefreshTokenService + Debug + Send + Sync + 'static
+    {
+        self.tokens = Some(tokens_router(self.state.sessions_service.clone(), Arc::new(token_service), Arc::new(refresh_service)));
+        self
+    }
+
+    /// Rejects cross-site unsafe requests authenticated with the session cookie, see `csrf::check`.
+    pub fn with_csrf(mut self, config: CsrfConfig) -> Self {
+        self.csrf = Some(config);
+        self
+    }
+
+    /// Lets the given browser origins call the routes with credentials.
+    pub fn with_cors(mut self, config: CorsConfig) -> Self {
+        self.cors = Some(config);
+        self
+    }
+
+    pub fn build(self) -> Router {
+        let mut router = app_router(&self.state);
+        if let Some(tokens) = self.tokens {
+            router = router.merge(tokens);
+        }
+        if let Some(config) = self.csrf {
+            router = router.layer(middleware::from_fn_with_state(Arc::new(config), csrf_protection));
+        }
+        // outermost so that preflight requests are answered before anything else runs
+        match self.cors {
+            Some(config) => router.layer(config.layer()),
+            None => router
+        }
+    }
+}
+
+/// Builds the router configured from the environment, see `constants`, along with the jobs it relies on.
+pub async fn main_router() -> anyhow::Result<(Router, BackgroundTasks)> {
+    SESSION_COOKIE.validate()?;
+    CSRF_COOKIE.validate()?;
+    // fail on startup rather than on the first request if the keys or the password policy don't load
+    lazy_static::initialize(&SESSION_COOKIE_SEAL);
+    lazy_static::initialize(&PASSWORD_POLICY);
+    let breached_passwords = BreachedPasswords::load(BREACHED_PASSWORDS_PATH.as_str())?;
+
+    let mut tasks = BackgroundTasks::new();
+    let client = HttpClient::new(HttpClientConfig::from_env()?);
+    let Repositories {
+        users: user_repository,
+        sessions: session_repository,
+        personal_tokens: personal_token_repository,
+        refresh_tokens: refresh_token_repository
+    } = repositories(*REPOSITORY_BACKEND, &client, &mut tasks).await?;
+
+    let session_repository = CachedSessionRepository::new(session_repository, SessionCacheConfig {
+        capacity: *SESSION_CACHE_CAPACITY,
+        ttl: Duration::from_secs(*SESSION_CACHE_TTL_SECONDS),
+        negative_ttl: Duration::from_secs(*SESSION_CACHE_NEGATIVE_TTL_SECONDS)
+    });
+
+    let revocations = revocations(*REVOCATION_BACKEND, &mut tasks).await?;
+    tasks.push(spawn_revocation_listener(revocations.subscribe(), session_repository.clone(), tasks.shutdown_signal()));
 
-pub fn main_router() -> Router {
-    let users_url = RESOURCE_MANAGEMENT_URL.clone() + "/users";
-    let sessions_url = RESOURCE_MANAGEMENT_URL.clone() + "/sessions";
-    
-    let users_service = HashUserService::new(
-        HttpUserRepository::new(users_url.as_str()),
-        BcryptHashService::new()
-    );
     let sessions_service = HashSessionService::new(
-        HttpSessionRepository::new(sessions_url.as_str()),
-        HttpUserRepository::new(users_url.as_str()),
+        session_repository.clone(),
+        user_repository.clone(),
         BcryptHashService::new(),
         *SESSION_ID_GEN_RETRIES
+    ).with_binding(SessionBindingPolicy {
+        mode: *SESSION_BINDING,
+        enforcement: *SESSION_BINDING_ENFORCEMENT,
+        ipv4_prefix: *SESSION_BINDING_IPV4_PREFIX,
+        ipv6_prefix: *SESSION_BINDING_IPV6_PREFIX
+    }).with_revocations(revocations);
+
+    let purge_config = SessionPurgeConfig {
+        interval: Duration::from_secs(*SESSION_PURGE_INTERVAL_SECONDS),
+        jitter: Duration::from_secs(*SESSION_PURGE_JITTER_SECONDS)
+    };
+    let purge_metrics = Arc::new(SessionPurgeMetrics::default());
+    tasks.push(spawn_session_purge(session_repository.clone(), purge_config, purge_metrics.clone(), tasks.shutdown_signal()));
+    if *METRICS_REPORT_INTERVAL_SECONDS > 0 {
+        let report_interval = Duration::from_secs(*METRICS_REPORT_INTERVAL_SECONDS);
+        tasks.push(spawn_metrics_report(purge_metrics, session_repository, report_interval, tasks.shutdown_signal()));
+    }
+
+    let mut builder = RouterBuilder::new(
+        HashUserService::new(
+            user_repository,
+            BcryptHashService::new()
+        ),
+        sessions_service,
+        HashPersonalTokenService::new(
+            personal_token_repository,
+            *SESSION_ID_GEN_RETRIES
+        )
+    ).with_breached_passwords(breached_passwords);
+
+    if *CSRF_PROTECTION {
+        builder = builder.with_csrf(CsrfConfig::from_env());
+    }
+    let cors_config = CorsConfig::from_env().map_err(anyhow::Error::msg)?;
+    if !cors_config.allowed_origins.is_empty() {
+        builder = builder.with_cors(cors_config);
+    }
+
+    let signing_keys = load_signing_keys(JWT_SIGNING_KEYS.as_str())?;
+    if signing_keys.is_empty() {
+        return Ok((builder.build(), tasks));
+    }
+
+    let builder = builder.with_tokens(
+        JwtTokenService::new(signing_keys, *ACCESS_TOKEN_LENGTH_SECONDS),
+        RotatingRefreshTokenService::new(
+            refresh_token_repository,
+            *REFRESH_TOKEN_LENGTH_SECONDS,
+            *SESSION_ID_GEN_RETRIES
+        )
     );
 
-    Router::new()
-        .nest("/users", users_router(users_service))
-        .nest("/sessions", sessions_router(sessions_service))
+    Ok((builder.build(), tasks))
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,22 @@
+use std::{fmt::Debug, sync::Arc};
+
+use axum::{Router, routing, Extension};
+
+use crate::{service::{sessions::SessionService, personal_tokens::PersonalTokenService}, control::personal_tokens::{post_personal_tokens, get_personal_tokens, delete_personal_tokens}};
+
+pub fn personal_tokens_router<S, P>(sessions_service: Arc<S>, personal_tokens_service: Arc<P>) -> Router
+where
+    S: SessionService + Debug + Send + Sync + 'static,
+    P: PersonalTokenService + Debug + Send + Sync + 'static
+{
+    let root_handler = routing
+        ::get(get_personal_tokens::<Arc<S>, Arc<P>>)
+        .post(post_personal_tokens::<Arc<S>, Arc<P>>);
+    let id_handler = routing::delete(delete_personal_tokens::<Arc<S>, Arc<P>>);
+
+    Router::new()
+        .route("/", root_handler)
+        .route("/:id", id_handler)
+        .layer(Extension(sessions_service))
+        .layer(Extension(personal_tokens_service))
+}
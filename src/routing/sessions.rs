@@ -1,14 +1,23 @@
+use std::{fmt::Debug, sync::Arc};
+
 use axum::{Router, routing, Extension};
 
-use crate::{service::{sessions::HashSessionService, hash::BcryptHashService}, repository::{sessions::HttpSessionRepository, users::HttpUserRepository}, control::sessions::{get_sessions, post_sessions, delete_sessions}};
+use crate::{service::{sessions::SessionService, personal_tokens::PersonalTokenService}, control::sessions::{get_sessions, post_sessions, delete_sessions, post_reauthenticate}};
 
-pub fn sessions_router(sessions_service: HashSessionService<HttpSessionRepository, HttpUserRepository, BcryptHashService>) -> Router {
+pub fn sessions_router<S, P>(sessions_service: Arc<S>, personal_tokens_service: Arc<P>) -> Router
+where
+    S: SessionService + Debug + Send + Sync + 'static,
+    P: PersonalTokenService + Debug + Send + Sync + 'static
+{
     let root_handler = routing
-        ::get(get_sessions::<HashSessionService<HttpSessionRepository, HttpUserRepository, BcryptHashService>>)
-        .post(post_sessions::<HashSessionService<HttpSessionRepository, HttpUserRepository, BcryptHashService>>)
-        .delete(delete_sessions::<HashSessionService<HttpSessionRepository, HttpUserRepository, BcryptHashService>>);
+        ::get(get_sessions::<Arc<S>, Arc<P>>)
+        .post(post_sessions::<Arc<S>>)
+        .delete(delete_sessions::<Arc<S>>);
+    let reauthenticate_handler = routing::post(post_reauthenticate::<Arc<S>>);
 
     Router::new()
         .route("/", root_handler)
+        .route("/reauthenticate", reauthenticate_handler)
         .layer(Extension(sessions_service))
+        .layer(Extension(personal_tokens_service))
 }
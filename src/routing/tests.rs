@@ -0,0 +1,527 @@
+use axum::body::Body;
+use axum_extra::extract::cookie::Cookie;
+use chrono::Utc;
+use http::{Request, StatusCode, header, Method};
+use mockall::predicate;
+use tower::ServiceExt;
+
+use crate::{service::{users::{MockUserService, UserCreationError}, sessions::{MockSessionService, SessionVerifyError}, personal_tokens::MockPersonalTokenService, tokens::MockTokenService, refresh_tokens::MockRefreshTokenService}, domain::{users::{User, Credentials}, sessions::{SessionData, SessionBinding}, personal_tokens::PersonalToken, tokens::{AccessToken, RefreshTokenData}}, constants::{SESSION_COOKIE_NAME, CSRF_COOKIE_NAME, CSRF_HEADER, SESSION_ID_LENGTH, USER_ID_HEADER, REAUTH_MAX_AGE_SECONDS}};
+
+use super::*;
+
+type MockState = AppState<MockUserService, MockSessionService, MockPersonalTokenService>;
+
+fn mock_credentials() -> Credentials {
+    Credentials {
+        email: String::from("email@email.com"),
+        password: String::from("quiet lantern orbits marmalade")
+    }
+}
+
+fn mock_credentials_body() -> Body {
+    Body::from(r#"{"email":"email@email.com","password":"quiet lantern orbits marmalade"}"#)
+}
+
+fn mock_session_id() -> String {
+    "1".repeat(SESSION_ID_LENGTH)
+}
+
+fn mock_user() -> User {
+    User {
+        id: 1,
+        email: String::from("email@email.com"),
+        password_hash: String::from("hash")
+    }
+}
+
+fn state(users_service: MockUserService, sessions_service: MockSessionService, personal_tokens_service: MockPersonalTokenService) -> MockState {
+    AppState::new(users_service, sessions_service, personal_tokens_service)
+}
+
+fn verifying_sessions_service() -> MockSessionService {
+    let mut sessions_service = MockSessionService::new();
+    sessions_service
+        .expect_verify()
+        .with(predicate::eq(mock_session_id()), predicate::always())
+        .returning(|_, _| Ok(mock_user()));
+    sessions_service
+}
+
+fn json_request(method: Method, uri: &str, body: Body) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .unwrap()
+}
+
+fn cookie_request(method: Method, uri: &str) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::COOKIE, format!("{}={}", SESSION_COOKIE_NAME.as_str(), mock_session_id()))
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn register() {
+    let mut users_service = MockUserService::new();
+    users_service
+        .expect_register()
+        .with(predicate::eq(mock_credentials()))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let router = app_router(&state(users_service, MockSessionService::new(), MockPersonalTokenService::new()));
+    let res = router.oneshot(json_request(Method::POST, "/users", mock_credentials_body())).await.unwrap();
+
+    assert_eq!(StatusCode::CREATED, res.status());
+}
+
+#[tokio::test]
+async fn register_duplicate() {
+    let mut users_service = MockUserService::new();
+    users_service
+        .expect_register()
+        .times(1)
+        .returning(|_| Err(UserCreationError::DuplicateEmail));
+
+    let router = app_router(&state(users_service, MockSessionService::new(), MockPersonalTokenService::new()));
+    let res = router.oneshot(json_request(Method::POST, "/users", mock_credentials_body())).await.unwrap();
+
+    assert_eq!(StatusCode::CONFLICT, res.status());
+}
+
+#[tokio::test]
+async fn register_invalid_body() {
+    let mut users_service = MockUserService::new();
+    users_service
+        .expect_register()
+        .never();
+
+    let router = app_router(&state(users_service, MockSessionService::new(), MockPersonalTokenService::new()));
+    let body = Body::from(r#"{"email":"email","password":"password"}"#);
+    let res = router.oneshot(json_request(Method::POST, "/users", body)).await.unwrap();
+
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+}
+
+#[tokio::test]
+async fn register_weak_password() {
+    let mut users_service = MockUserService::new();
+    users_service
+        .expect_register()
+        .never();
+
+    let router = app_router(&state(users_service, MockSessionService::new(), MockPersonalTokenService::new()));
+    let body = Body::from(r#"{"email":"email@email.com","password":"Password1!"}"#);
+    let res = router.oneshot(json_request(Method::POST, "/users", body)).await.unwrap();
+
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    let errs: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let err = &errs["password"][0];
+    assert_eq!("weak_password", err["code"]);
+    assert!(err["params"]["score"].is_u64());
+    assert!(err["params"]["feedback"]["suggestions"].is_array());
+    assert!(err["params"].get("value").is_none());
+}
+
+#[tokio::test]
+async fn register_breached_password() {
+    let mut users_service = MockUserService::new();
+    users_service
+        .expect_register()
+        .never();
+
+    // the fixture lists this passphrase
+    let breached = BreachedPasswords::load(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/breached_passwords.txt")).unwrap();
+    let router = RouterBuilder::from_state(state(users_service, MockSessionService::new(), MockPersonalTokenService::new()))
+        .with_breached_passwords(breached)
+        .build();
+    let body = Body::from(r#"{"email":"email@email.com","password":"correct horse battery staple"}"#);
+    let res = router.oneshot(json_request(Method::POST, "/users", body)).await.unwrap();
+
+    assert_eq!(StatusCode::UNPROCESSABLE_ENTITY, res.status());
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    let errs: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!("breached_password", errs["password"][0]["code"]);
+}
+
+#[tokio::test]
+async fn password_policy() {
+    let router = app_router(&state(MockUserService::new(), MockSessionService::new(), MockPersonalTokenService::new()));
+    let req = Request::builder().method(Method::GET).uri("/users/password-policy").body(Body::empty()).unwrap();
+    let res = router.oneshot(req).await.unwrap();
+
+    assert_eq!(StatusCode::OK, res.status());
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    let policy: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(8, policy["min_length"]);
+    assert_eq!(3, policy["min_score"]);
+    assert!(policy["required_classes"].is_array());
+}
+
+fn password_change_request() -> Request<Body> {
+    Request::builder()
+        .method(Method::PUT)
+        .uri("/users/password")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::COOKIE, format!("{}={}", SESSION_COOKIE_NAME.as_str(), mock_session_id()))
+        .body(Body::from(r#"{"current_password":"quiet lantern orbits marmalade","new_password":"brisk harbour tulips gondola"}"#))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn change_password_stale_session() {
+    let mut users_service = MockUserService::new();
+    users_service
+        .expect_change_password()
+        .never();
+
+    let mut sessions_service = MockSessionService::new();
+    sessions_service
+        .expect_verify_recent()
+        .with(predicate::eq(mock_session_id()), predicate::always(), predicate::eq(*REAUTH_MAX_AGE_SECONDS))
+        .times(1)
+        .returning(|_, _, _| Err(SessionVerifyError::StaleAuthentication));
+
+    let router = app_router(&state(users_service, sessions_service, MockPersonalTokenService::new()));
+    let res = router.oneshot(password_change_request()).await.unwrap();
+
+    assert_eq!(StatusCode::FORBIDDEN, res.status());
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!("reauthentication_required", body["error"]);
+    assert_eq!(*REAUTH_MAX_AGE_SECONDS, body["max_age"]);
+}
+
+#[tokio::test]
+async fn change_password_invalid_session() {
+    let mut users_service = MockUserService::new();
+    users_service
+        .expect_change_password()
+        .never();
+
+    let mut sessions_service = MockSessionService::new();
+    sessions_service
+        .expect_verify_recent()
+        .times(1)
+        .returning(|_, _, _| Err(SessionVerifyError::Missing));
+
+    let router = app_router(&state(users_service, sessions_service, MockPersonalTokenService::new()));
+    let res = router.oneshot(password_change_request()).await.unwrap();
+
+    assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+}
+
+#[tokio::test]
+async fn login() {
+    let mut sessions_service = MockSessionService::new();
+    sessions_service
+        .expect_login()
+        .with(predicate::eq(mock_credentials()), predicate::always())
+        .times(1)
+        .returning(|_, _| Ok(SessionData { id: mock_session_id(), user_id: 1, expires: Utc::now().timestamp() + 100, authenticated_at: 0, binding: SessionBinding::default() }));
+
+    let router = app_router(&state(MockUserService::new(), sessions_service, MockPersonalTokenService::new()));
+    let res = router.oneshot(json_request(Method::POST, "/sessions", mock_credentials_body())).await.unwrap();
+
+    assert_eq!(StatusCode::CREATED, res.status());
+    let session_cookie = format!("{}={}", SESSION_COOKIE_NAME.as_str(), mock_session_id());
+    assert!(res.headers().get_all(header::SET_COOKIE).iter().any(|cookie| cookie.to_str().unwrap().starts_with(&session_cookie)));
+
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(1, body["user_id"]);
+    assert_eq!(None, body.get("token"));
+    assert!(body["csrf_token"].is_string());
+}
+
+#[tokio::test]
+async fn verify() {
+    let router = app_router(&state(MockUserService::new(), verifying_sessions_service(), MockPersonalTokenService::new()));
+    let res = router.oneshot(cookie_request(Method::GET, "/sessions")).await.unwrap();
+
+    assert_eq!(StatusCode::OK, res.status());
+    assert_eq!("1", res.headers().get(USER_ID_HEADER.as_str()).unwrap());
+}
+
+#[tokio::test]
+async fn verify_bearer() {
+    let router = app_router(&state(MockUserService::new(), verifying_sessions_service(), MockPersonalTokenService::new()));
+    let req = Request::builder()
+        .uri("/sessions")
+        .header(header::AUTHORIZATION, format!("Bearer {}", mock_session_id()))
+        .body(Body::empty())
+        .unwrap();
+    let res = router.oneshot(req).await.unwrap();
+
+    assert_eq!(StatusCode::OK, res.status());
+    assert_eq!("1", res.headers().get(USER_ID_HEADER.as_str()).unwrap());
+}
+
+#[tokio::test]
+async fn verify_missing_session() {
+    let mut sessions_service = MockSessionService::new();
+    sessions_service
+        .expect_verify()
+        .times(1)
+        .returning(|_, _| Err(SessionVerifyError::Missing));
+
+    let router = app_router(&state(MockUserService::new(), sessions_service, MockPersonalTokenService::new()));
+    let res = router.oneshot(cookie_request(Method::GET, "/sessions")).await.unwrap();
+
+    assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+}
+
+#[tokio::test]
+async fn verify_no_credentials() {
+    let router = app_router(&state(MockUserService::new(), MockSessionService::new(), MockPersonalTokenService::new()));
+    let res = router.oneshot(Request::builder().uri("/sessions").body(Body::empty()).unwrap()).await.unwrap();
+
+    assert_eq!(StatusCode::UNAUTHORIZED, res.status());
+}
+
+#[tokio::test]
+async fn logout() {
+    let mut sessions_service = MockSessionService::new();
+    sessions_service
+        .expect_logout()
+        .with(predicate::eq(mock_session_id()))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let router = app_router(&state(MockUserService::new(), sessions_service, MockPersonalTokenService::new()));
+    let res = router.oneshot(cookie_request(Method::DELETE, "/sessions")).await.unwrap();
+
+    assert_eq!(StatusCode::OK, res.status());
+    assert!(res.headers().contains_key(header::SET_COOKIE));
+}
+
+#[tokio::test]
+async fn logout_csrf_rejected() {
+    let router = RouterBuilder::from_state(state(MockUserService::new(), MockSessionService::new(), MockPersonalTokenService::new()))
+        .with_csrf(CsrfConfig::default())
+        .build();
+    let res = router.oneshot(cookie_request(Method::DELETE, "/sessions")).await.unwrap();
+
+    assert_eq!(StatusCode::FORBIDDEN, res.status());
+}
+
+#[tokio::test]
+async fn logout_after_verify_issues_csrf_token() {
+    let mut sessions_service = MockSessionService::new();
+    sessions_service
+        .expect_verify()
+        .times(1)
+        .returning(|_, _| Ok(mock_user()));
+    sessions_service
+        .expect_logout()
+        .times(1)
+        .returning(|_| Ok(()));
+
+    // a session started before CSRF protection was enabled has no token cookie
+    let router = RouterBuilder::from_state(state(MockUserService::new(), sessions_service, MockPersonalTokenService::new()))
+        .with_csrf(CsrfConfig::default())
+        .build();
+    let res = router.clone().oneshot(cookie_request(Method::GET, "/sessions")).await.unwrap();
+    assert_eq!(StatusCode::OK, res.status());
+
+    let csrf_cookie = Cookie::parse(res.headers()[header::SET_COOKIE].to_str().unwrap().to_owned()).unwrap();
+    assert_eq!(CSRF_COOKIE_NAME.as_str(), csrf_cookie.name());
+
+    let req = Request::builder()
+        .method(Method::DELETE)
+        .uri("/sessions")
+        .header(header::COOKIE, format!("{}={}; {}", SESSION_COOKIE_NAME.as_str(), mock_session_id(), csrf_cookie.stripped()))
+        .header(CSRF_HEADER.as_str(), csrf_cookie.value())
+        .body(Body::empty())
+        .unwrap();
+    let res = router.oneshot(req).await.unwrap();
+
+    assert_eq!(StatusCode::OK, res.status());
+}
+
+#[tokio::test]
+async fn logout_csrf_token() {
+    let mut sessions_service = MockSessionService::new();
+    sessions_service
+        .expect_logout()
+        .with(predicate::eq(mock_session_id()))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let router = RouterBuilder::from_state(state(MockUserService::new(), sessions_service, MockPersonalTokenService::new()))
+        .with_csrf(CsrfConfig::default())
+        .build();
+    let req = Request::builder()
+        .method(Method::DELETE)
+        .uri("/sessions")
+        .header(header::COOKIE, format!("{}={}; {}=token", SESSION_COOKIE_NAME.as_str(), mock_session_id(), CSRF_COOKIE_NAME.as_str()))
+        .header(CSRF_HEADER.as_str(), "token")
+        .body(Body::empty())
+        .unwrap();
+    let res = router.oneshot(req).await.unwrap();
+
+    assert_eq!(StatusCode::OK, res.status());
+}
+
+#[tokio::test]
+async fn login_csrf_untrusted_origin() {
+    let config = CsrfConfig { trusted_origins: vec![String::from("https://agartex.com")], exempt_bearer: true };
+    let router = RouterBuilder::from_state(state(MockUserService::new(), MockSessionService::new(), MockPersonalTokenService::new()))
+        .with_csrf(config)
+        .build();
+    let mut req = json_request(Method::POST, "/sessions", mock_credentials_body());
+    req.headers_mut().insert(header::ORIGIN, http::HeaderValue::from_static("https://evil.com"));
+    let res = router.oneshot(req).await.unwrap();
+
+    assert_eq!(StatusCode::FORBIDDEN, res.status());
+}
+
+fn cors_router() -> Router {
+    let config = CorsConfig {
+        allowed_origins: vec![crate::cors::OriginPattern::Subdomain { scheme: String::from("https"), suffix: String::from(".agartex.com") }],
+        max_age: std::time::Duration::from_secs(600)
+    };
+    RouterBuilder::from_state(state(MockUserService::new(), verifying_sessions_service(), MockPersonalTokenService::new()))
+        .with_csrf(CsrfConfig::default())
+        .with_cors(config)
+        .build()
+}
+
+fn preflight_request(origin: &str) -> Request<Body> {
+    Request::builder()
+        .method(Method::OPTIONS)
+        .uri("/sessions")
+        .header(header::ORIGIN, origin)
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, "DELETE")
+        .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "x-csrf-token")
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn cors_preflight() {
+    let res = cors_router().oneshot(preflight_request("https://app.agartex.com")).await.unwrap();
+
+    assert_eq!(StatusCode::OK, res.status());
+    assert_eq!("https://app.agartex.com", res.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN]);
+    assert_eq!("true", res.headers()[header::ACCESS_CONTROL_ALLOW_CREDENTIALS]);
+    assert_eq!("600", res.headers()[header::ACCESS_CONTROL_MAX_AGE]);
+    assert!(res.headers()[header::ACCESS_CONTROL_ALLOW_HEADERS].to_str().unwrap().contains(CSRF_HEADER.as_str()));
+}
+
+#[tokio::test]
+async fn cors_preflight_untrusted_origin() {
+    let res = cors_router().oneshot(preflight_request("https://evil.com")).await.unwrap();
+
+    assert!(!res.headers().contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+}
+
+#[tokio::test]
+async fn cors_exposes_user_id() {
+    let mut req = cookie_request(Method::GET, "/sessions");
+    req.headers_mut().insert(header::ORIGIN, http::HeaderValue::from_static("https://app.agartex.com"));
+    let res = cors_router().oneshot(req).await.unwrap();
+
+    assert_eq!(StatusCode::OK, res.status());
+    assert_eq!("https://app.agartex.com", res.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN]);
+    assert!(res.headers()[header::ACCESS_CONTROL_EXPOSE_HEADERS].to_str().unwrap().contains(USER_ID_HEADER.as_str()));
+}
+
+#[tokio::test]
+async fn list_personal_tokens() {
+    let mut personal_tokens_service = MockPersonalTokenService::new();
+    personal_tokens_service
+        .expect_list()
+        .with(predicate::eq(1))
+        .times(1)
+        .returning(|_| Ok(vec![PersonalToken { id: 2, user_id: 1, name: String::from("ci"), scopes: vec![String::from("documents:read")], expires: None }]));
+
+    let router = app_router(&state(MockUserService::new(), verifying_sessions_service(), personal_tokens_service));
+    let res = router.oneshot(cookie_request(Method::GET, "/personal-tokens")).await.unwrap();
+
+    assert_eq!(StatusCode::OK, res.status());
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    let tokens: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(2, tokens[0]["id"]);
+}
+
+#[tokio::test]
+async fn create_personal_token_stale_session() {
+    let mut sessions_service = MockSessionService::new();
+    sessions_service
+        .expect_verify_recent()
+        .with(predicate::eq(mock_session_id()), predicate::always(), predicate::always())
+        .times(1)
+        .returning(|_, _, _| Err(SessionVerifyError::StaleAuthentication));
+
+    let mut personal_tokens_service = MockPersonalTokenService::new();
+    personal_tokens_service
+        .expect_create()
+        .never();
+
+    let mut req = json_request(Method::POST, "/personal-tokens", Body::from(r#"{"name": "ci", "scopes": ["documents:read"]}"#));
+    req.headers_mut().insert(header::AUTHORIZATION, format!("Bearer {}", mock_session_id()).parse().unwrap());
+
+    let router = app_router(&state(MockUserService::new(), sessions_service, personal_tokens_service));
+    let res = router.oneshot(req).await.unwrap();
+
+    assert_eq!(StatusCode::FORBIDDEN, res.status());
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!("reauthentication_required", body["error"]);
+}
+
+#[tokio::test]
+async fn issue_access_token() {
+    let mut token_service = MockTokenService::new();
+    token_service
+        .expect_issue()
+        .with(predicate::eq(1), predicate::eq(mock_session_id()))
+        .times(1)
+        .returning(|_, _| Ok(AccessToken { access_token: String::from("jwt"), token_type: String::from("Bearer"), expires_in: 300, refresh_token: None }));
+
+    let mut refresh_service = MockRefreshTokenService::new();
+    refresh_service
+        .expect_issue()
+        .times(1)
+        .returning(|user_id, session_id| Ok(RefreshTokenData {
+            id: String::from("refresh"),
+            family_id: String::from("family"),
+            user_id,
+            session_id: String::from(session_id),
+            expires: Utc::now().timestamp() + 100
+        }));
+
+    let router = RouterBuilder::from_state(state(MockUserService::new(), verifying_sessions_service(), MockPersonalTokenService::new()))
+        .with_tokens(token_service, refresh_service)
+        .build();
+    let res = router.oneshot(cookie_request(Method::POST, "/sessions/token")).await.unwrap();
+
+    assert_eq!(StatusCode::CREATED, res.status());
+    let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+    let token: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!("jwt", token["access_token"]);
+    assert_eq!("refresh", token["refresh_token"]);
+}
+
+#[tokio::test]
+async fn tokens_disabled() {
+    let router = RouterBuilder::new(MockUserService::new(), MockSessionService::new(), MockPersonalTokenService::new()).build();
+    let res = router.oneshot(cookie_request(Method::POST, "/sessions/token")).await.unwrap();
+
+    assert_eq!(StatusCode::NOT_FOUND, res.status());
+}
+
+#[tokio::test]
+async fn unknown_route() {
+    let router = app_router(&state(MockUserService::new(), MockSessionService::new(), MockPersonalTokenService::new()));
+    let res = router.oneshot(Request::builder().uri("/unknown").body(Body::empty()).unwrap()).await.unwrap();
+
+    assert_eq!(StatusCode::NOT_FOUND, res.status());
+}
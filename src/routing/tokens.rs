@@ -0,0 +1,24 @@
+use std::{fmt::Debug, sync::Arc};
+
+use axum::{Router, routing, Extension};
+
+use crate::{service::{sessions::SessionService, tokens::TokenService, refresh_tokens::RefreshTokenService}, control::tokens::{post_tokens, post_refresh, get_jwks}};
+
+pub fn tokens_router<S, T, R>(sessions_service: Arc<S>, token_service: Arc<T>, refresh_service: Arc<R>) -> Router
+where
+    S: SessionService + Debug + Send + Sync + 'static,
+    T: TokenService + Debug + Send + Sync + 'static,
+    R: RefreshTokenService + Debug + Send + Sync + 'static
+{
+    let token_handler = routing::post(post_tokens::<Arc<S>, Arc<T>, Arc<R>>);
+    let refresh_handler = routing::post(post_refresh::<Arc<S>, Arc<T>, Arc<R>>);
+    let jwks_handler = routing::get(get_jwks::<Arc<T>>);
+
+    Router::new()
+        .route("/sessions/token", token_handler)
+        .route("/sessions/refresh", refresh_handler)
+        .route("/.well-known/jwks.json", jwks_handler)
+        .layer(Extension(sessions_service))
+        .layer(Extension(token_service))
+        .layer(Extension(refresh_service))
+}
@@ -1,13 +1,23 @@
+use std::{fmt::Debug, sync::Arc};
+
 use axum::{Router, Extension};
 
-use crate::{control::users::post_users, service::{users::HashUserService, hash::BcryptHashService}, repository::users::HttpUserRepository};
+use crate::{control::users::{post_users, put_password, get_password_policy}, service::{users::UserService, sessions::SessionService}, passwords::BreachedPasswords};
+
+pub fn users_router<U, S>(users_service: Arc<U>, sessions_service: Arc<S>, breached_passwords: Arc<BreachedPasswords>) -> Router
+where
+    U: UserService + Debug + Send + Sync + 'static,
+    S: SessionService + Debug + Send + Sync + 'static
+{
+    let users_handler = axum::routing::post(post_users::<Arc<U>>);
+    let password_handler = axum::routing::put(put_password::<Arc<U>, Arc<S>>);
+    let password_policy_handler = axum::routing::get(get_password_policy);
 
-pub fn users_router(
-    users_service: HashUserService<HttpUserRepository, BcryptHashService>
-) -> Router {
-    let users_handler = axum::routing::post(post_users::<HashUserService<HttpUserRepository, BcryptHashService>>);
-    
     Router::new()
         .route("/", users_handler)
+        .route("/password", password_handler)
+        .route("/password-policy", password_policy_handler)
         .layer(Extension(users_service))
+        .layer(Extension(sessions_service))
+        .layer(Extension(breached_passwords))
 }
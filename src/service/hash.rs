@@ -22,6 +22,12 @@ impl BcryptHashService {
     }
 }
 
+impl Default for BcryptHashService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl HashService for BcryptHashService {
     fn hash(&self, input: &str) -> Result<String> {
         bcrypt::hash(input, self.hash_cost).map_err(Error::from)
@@ -1,3 +1,18 @@
 pub mod hash;
+pub mod personal_tokens;
+pub mod refresh_tokens;
 pub mod sessions;
+pub mod tokens;
 pub mod users;
+
+use rand::{Rng, distributions::Alphanumeric};
+
+/// Random string of ASCII letters and digits, about 5.95 bits of entropy per character,
+/// used for every secret identifier handed out.
+pub fn random_alphanumeric(len: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(len)
+        .map(char::from)
+        .collect()
+}
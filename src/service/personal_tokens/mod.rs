@@ -0,0 +1,170 @@
+use std::sync::Arc;
+
+use axum::async_trait;
+use chrono::{Utc, Duration};
+use mockall::automock;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::{domain::personal_tokens::{PersonalToken, PersonalTokenData, PersonalTokenRequest, CreatedPersonalToken}, repository::personal_tokens::{PersonalTokenRepository, PersonalTokenInsertError, PersonalTokenGetError, PersonalTokenDeleteError}, constants::{PERSONAL_TOKEN_PREFIX, PERSONAL_TOKEN_LENGTH}};
+
+use super::random_alphanumeric;
+
+#[derive(PartialEq, Debug)]
+pub enum PersonalTokenCreationError {
+    Unknown
+}
+
+#[derive(PartialEq, Debug)]
+pub enum PersonalTokenVerifyError {
+    Missing,
+    Unknown
+}
+
+#[derive(PartialEq, Debug)]
+pub enum PersonalTokenListError {
+    Unknown
+}
+
+#[derive(PartialEq, Debug)]
+pub enum PersonalTokenRevokeError {
+    Missing,
+    Unknown
+}
+
+#[automock]
+#[async_trait]
+pub trait PersonalTokenService {
+    async fn create(&self, user_id: i32, request: PersonalTokenRequest) -> Result<CreatedPersonalToken, PersonalTokenCreationError>;
+    async fn verify(&self, token: &str) -> Result<PersonalToken, PersonalTokenVerifyError>;
+    async fn list(&self, user_id: i32) -> Result<Vec<PersonalToken>, PersonalTokenListError>;
+    async fn revoke(&self, user_id: i32, id: i32) -> Result<(), PersonalTokenRevokeError>;
+}
+
+#[async_trait]
+impl<T: PersonalTokenService + Send + Sync + ?Sized> PersonalTokenService for Arc<T> {
+    async fn create(&self, user_id: i32, request: PersonalTokenRequest) -> Result<CreatedPersonalToken, PersonalTokenCreationError> {
+        (**self).create(user_id, request).await
+    }
+
+    async fn verify(&self, token: &str) -> Result<PersonalToken, PersonalTokenVerifyError> {
+        (**self).verify(token).await
+    }
+
+    async fn list(&self, user_id: i32) -> Result<Vec<PersonalToken>, PersonalTokenListError> {
+        (**self).list(user_id).await
+    }
+
+    async fn revoke(&self, user_id: i32, id: i32) -> Result<(), PersonalTokenRevokeError> {
+        (**self).revoke(user_id, id).await
+    }
+}
+
+pub fn is_personal_token(token: &str) -> bool {
+    token.starts_with(PERSONAL_TOKEN_PREFIX)
+}
+
+#[derive(Debug, Clone)]
+pub struct HashPersonalTokenService<R>
+where
+    R: PersonalTokenRepository + Send + Sync
+{
+    repository: R,
+    max_retries: u32
+}
+
+impl<R> HashPersonalTokenService<R>
+where
+    R: PersonalTokenRepository + Send + Sync
+{
+    pub fn new(repository: R, max_retries: u32) -> Self {
+        Self { repository, max_retries }
+    }
+
+    pub fn generate_token(token_len: usize) -> String {
+        String::from(PERSONAL_TOKEN_PREFIX) + &random_alphanumeric(token_len)
+    }
+
+    /// Tokens carry enough entropy that a plain digest is sufficient,
+    /// and unlike bcrypt it can be used to look the token up.
+    pub fn hash_token(token: &str) -> String {
+        format!("{:x}", Sha256::digest(token.as_bytes()))
+    }
+}
+
+#[async_trait]
+impl<R> PersonalTokenService for HashPersonalTokenService<R>
+where
+    R: PersonalTokenRepository + Send + Sync
+{
+    #[tracing::instrument(skip_all, fields(user_id = user_id, name = request.name))]
+    async fn create(&self, user_id: i32, request: PersonalTokenRequest) -> Result<CreatedPersonalToken, PersonalTokenCreationError> {
+        info!("Attempting to create personal token");
+        let expires = request.expires_in_days.map(|days| (Utc::now() + Duration::days(days)).timestamp());
+
+        for _ in 0..self.max_retries {
+            let token = Self::generate_token(PERSONAL_TOKEN_LENGTH);
+            let token_data = PersonalTokenData {
+                user_id,
+                name: request.name.clone(),
+                token_hash: Self::hash_token(&token),
+                scopes: request.scopes.clone(),
+                expires
+            };
+
+            match self.repository.insert(&token_data).await {
+                Ok(details) => {
+                    info!("Personal token creation succeeded");
+                    return Ok(CreatedPersonalToken { token, details });
+                },
+                Err(PersonalTokenInsertError::Duplicate) => continue,
+                Err(PersonalTokenInsertError::Unknown) => return Err(PersonalTokenCreationError::Unknown)
+            }
+        };
+
+        Err(PersonalTokenCreationError::Unknown)
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn verify(&self, token: &str) -> Result<PersonalToken, PersonalTokenVerifyError> {
+        if !is_personal_token(token) {
+            return Err(PersonalTokenVerifyError::Missing);
+        }
+
+        let details = match self.repository.get_by_hash(&Self::hash_token(token)).await {
+            Ok(details) => details,
+            Err(PersonalTokenGetError::Missing) => return Err(PersonalTokenVerifyError::Missing),
+            Err(PersonalTokenGetError::Unknown) => return Err(PersonalTokenVerifyError::Unknown)
+        };
+
+        if matches!(details.expires, Some(expires) if expires < Utc::now().timestamp()) {
+            warn!("Personal token {} has expired", details.id);
+            return Err(PersonalTokenVerifyError::Missing);
+        }
+
+        Ok(details)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn list(&self, user_id: i32) -> Result<Vec<PersonalToken>, PersonalTokenListError> {
+        self.repository
+            .list(user_id)
+            .await
+            .map_err(|_| PersonalTokenListError::Unknown)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn revoke(&self, user_id: i32, id: i32) -> Result<(), PersonalTokenRevokeError> {
+        match self.repository.delete(user_id, id).await {
+            Ok(()) => {
+                info!("Personal token revoked");
+                Ok(())
+            },
+            Err(PersonalTokenDeleteError::Missing) => Err(PersonalTokenRevokeError::Missing),
+            Err(PersonalTokenDeleteError::Unknown) => Err(PersonalTokenRevokeError::Unknown)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,138 @@
+use mockall::predicate;
+
+use crate::{repository::personal_tokens::MockPersonalTokenRepository, constants::SESSION_ID_GEN_RETRIES};
+
+use super::*;
+
+fn mock_token() -> String {
+    String::from(PERSONAL_TOKEN_PREFIX) + "token"
+}
+
+fn mock_request() -> PersonalTokenRequest {
+    PersonalTokenRequest {
+        name: String::from("ci"),
+        scopes: vec![String::from("documents:compile")],
+        expires_in_days: Some(30)
+    }
+}
+
+fn mock_details(expires: Option<i64>) -> PersonalToken {
+    PersonalToken {
+        id: 1,
+        user_id: 1,
+        name: String::from("ci"),
+        scopes: vec![String::from("documents:compile")],
+        expires
+    }
+}
+
+fn service(repository: MockPersonalTokenRepository) -> HashPersonalTokenService<MockPersonalTokenRepository> {
+    HashPersonalTokenService::new(repository, *SESSION_ID_GEN_RETRIES)
+}
+
+#[tokio::test]
+async fn hash_impl_create_normal() {
+    let mut repository = MockPersonalTokenRepository::new();
+
+    repository
+        .expect_insert()
+        .withf(|token_data| token_data.user_id == 1 && token_data.expires.is_some() && !token_data.token_hash.starts_with(PERSONAL_TOKEN_PREFIX))
+        .times(1)
+        .returning(|_| Ok(mock_details(None)));
+
+    let created = service(repository).create(1, mock_request()).await.unwrap();
+    assert!(is_personal_token(&created.token));
+    assert_eq!(PERSONAL_TOKEN_PREFIX.len() + PERSONAL_TOKEN_LENGTH, created.token.len());
+    assert_eq!(mock_details(None), created.details);
+}
+
+#[tokio::test]
+async fn hash_impl_create_stores_hash() {
+    let mut repository = MockPersonalTokenRepository::new();
+    let stored = std::sync::Arc::new(std::sync::Mutex::new(String::new()));
+    let stored_cpy = stored.clone();
+
+    repository
+        .expect_insert()
+        .times(1)
+        .returning(move |token_data| {
+            *stored_cpy.lock().unwrap() = token_data.token_hash.clone();
+            Ok(mock_details(None))
+        });
+
+    let created = service(repository).create(1, mock_request()).await.unwrap();
+    assert_eq!(HashPersonalTokenService::<MockPersonalTokenRepository>::hash_token(&created.token), *stored.lock().unwrap());
+}
+
+#[tokio::test]
+async fn hash_impl_create_duplicate_exhausted() {
+    let mut repository = MockPersonalTokenRepository::new();
+
+    repository
+        .expect_insert()
+        .times(*SESSION_ID_GEN_RETRIES as usize)
+        .returning(|_| Err(PersonalTokenInsertError::Duplicate));
+
+    assert_eq!(Err(PersonalTokenCreationError::Unknown), service(repository).create(1, mock_request()).await);
+}
+
+#[tokio::test]
+async fn hash_impl_verify_normal() {
+    let mut repository = MockPersonalTokenRepository::new();
+
+    repository
+        .expect_get_by_hash()
+        .with(predicate::eq(HashPersonalTokenService::<MockPersonalTokenRepository>::hash_token(&mock_token())))
+        .times(1)
+        .returning(|_| Ok(mock_details(None)));
+
+    assert_eq!(Ok(mock_details(None)), service(repository).verify(&mock_token()).await);
+}
+
+#[tokio::test]
+async fn hash_impl_verify_wrong_prefix() {
+    let mut repository = MockPersonalTokenRepository::new();
+
+    repository
+        .expect_get_by_hash()
+        .never();
+
+    assert_eq!(Err(PersonalTokenVerifyError::Missing), service(repository).verify("session_id").await);
+}
+
+#[tokio::test]
+async fn hash_impl_verify_expired() {
+    let mut repository = MockPersonalTokenRepository::new();
+
+    repository
+        .expect_get_by_hash()
+        .times(1)
+        .returning(|_| Ok(mock_details(Some(Utc::now().timestamp() - 100))));
+
+    assert_eq!(Err(PersonalTokenVerifyError::Missing), service(repository).verify(&mock_token()).await);
+}
+
+#[tokio::test]
+async fn hash_impl_verify_unknown_error() {
+    let mut repository = MockPersonalTokenRepository::new();
+
+    repository
+        .expect_get_by_hash()
+        .times(1)
+        .returning(|_| Err(PersonalTokenGetError::Unknown));
+
+    assert_eq!(Err(PersonalTokenVerifyError::Unknown), service(repository).verify(&mock_token()).await);
+}
+
+#[tokio::test]
+async fn hash_impl_revoke_missing() {
+    let mut repository = MockPersonalTokenRepository::new();
+
+    repository
+        .expect_delete()
+        .with(predicate::eq(1), predicate::eq(2))
+        .times(1)
+        .returning(|_, _| Err(PersonalTokenDeleteError::Missing));
+
+    assert_eq!(Err(PersonalTokenRevokeError::Missing), service(repository).revoke(1, 2).await);
+}
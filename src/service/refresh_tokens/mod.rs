@@ -0,0 +1,145 @@
+use std::sync::Arc;
+
+use axum::async_trait;
+use chrono::Utc;
+use mockall::automock;
+use tracing::{error, info, warn};
+
+use crate::{domain::tokens::RefreshTokenData, repository::refresh_tokens::{RefreshTokenRepository, RefreshTokenInsertError, RefreshTokenGetError, RefreshTokenUseError}, constants::REFRESH_TOKEN_LENGTH};
+
+use super::random_alphanumeric;
+
+#[derive(PartialEq, Debug)]
+pub enum RefreshTokenIssueError {
+    Unknown
+}
+
+#[derive(PartialEq, Debug)]
+pub enum RefreshError {
+    Missing,
+    Reused,
+    Unknown
+}
+
+#[automock]
+#[async_trait]
+pub trait RefreshTokenService {
+    async fn issue(&self, user_id: i32, session_id: &str) -> Result<RefreshTokenData, RefreshTokenIssueError>;
+    async fn refresh(&self, id: &str) -> Result<RefreshTokenData, RefreshError>;
+}
+
+#[async_trait]
+impl<T: RefreshTokenService + Send + Sync + ?Sized> RefreshTokenService for Arc<T> {
+    async fn issue(&self, user_id: i32, session_id: &str) -> Result<RefreshTokenData, RefreshTokenIssueError> {
+        (**self).issue(user_id, session_id).await
+    }
+
+    async fn refresh(&self, id: &str) -> Result<RefreshTokenData, RefreshError> {
+        (**self).refresh(id).await
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RotatingRefreshTokenService<R>
+where
+    R: RefreshTokenRepository + Send + Sync
+{
+    repository: R,
+    token_length_seconds: i64,
+    max_retries: u32
+}
+
+impl<R> RotatingRefreshTokenService<R>
+where
+    R: RefreshTokenRepository + Send + Sync
+{
+    pub fn new(repository: R, token_length_seconds: i64, max_retries: u32) -> Self {
+        Self { repository, token_length_seconds, max_retries }
+    }
+
+    pub fn generate_token_id(id_len: usize) -> String {
+        random_alphanumeric(id_len)
+    }
+
+    async fn insert(&self, family_id: &str, user_id: i32, session_id: &str) -> Option<RefreshTokenData> {
+        for _ in 0..self.max_retries {
+            let token_data = RefreshTokenData {
+                id: Self::generate_token_id(REFRESH_TOKEN_LENGTH),
+                family_id: String::from(family_id),
+                user_id,
+                session_id: String::from(session_id),
+                expires: Utc::now().timestamp() + self.token_length_seconds
+            };
+
+            match self.repository.insert(&token_data).await {
+                Ok(()) => return Some(token_data),
+                Err(RefreshTokenInsertError::Duplicate) => continue,
+                Err(RefreshTokenInsertError::Unknown) => return None
+            }
+        };
+
+        None
+    }
+
+    async fn revoke_family(&self, family_id: &str) -> RefreshError {
+        warn!("Revoking refresh token family {}", family_id);
+        match self.repository.revoke_family(family_id).await {
+            Ok(()) => RefreshError::Reused,
+            Err(_) => {
+                error!("Unable to revoke refresh token family {}", family_id);
+                RefreshError::Unknown
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<R> RefreshTokenService for RotatingRefreshTokenService<R>
+where
+    R: RefreshTokenRepository + Send + Sync
+{
+    #[tracing::instrument(skip_all, fields(user_id = user_id))]
+    async fn issue(&self, user_id: i32, session_id: &str) -> Result<RefreshTokenData, RefreshTokenIssueError> {
+        info!("Issuing new refresh token family");
+        let family_id = Self::generate_token_id(REFRESH_TOKEN_LENGTH);
+        self.insert(&family_id, user_id, session_id)
+            .await
+            .ok_or(RefreshTokenIssueError::Unknown)
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn refresh(&self, id: &str) -> Result<RefreshTokenData, RefreshError> {
+        let token = match self.repository.get(id).await {
+            Ok(token) => token,
+            Err(RefreshTokenGetError::Missing) => return Err(RefreshError::Missing),
+            Err(RefreshTokenGetError::Unknown) => return Err(RefreshError::Unknown)
+        };
+
+        if token.used {
+            warn!("Refresh token reuse detected for user {}", token.user_id);
+            return Err(self.revoke_family(&token.family_id).await);
+        }
+
+        if token.expires < Utc::now().timestamp() {
+            return Err(RefreshError::Missing);
+        }
+
+        match self.repository.mark_used(id).await {
+            Ok(()) => (),
+            Err(RefreshTokenUseError::AlreadyUsed) => {
+                warn!("Concurrent refresh token reuse detected for user {}", token.user_id);
+                return Err(self.revoke_family(&token.family_id).await);
+            },
+            Err(RefreshTokenUseError::Missing) => return Err(RefreshError::Missing),
+            Err(RefreshTokenUseError::Unknown) => return Err(RefreshError::Unknown)
+        };
+
+        info!("Rotating refresh token");
+        self.insert(&token.family_id, token.user_id, &token.session_id)
+            .await
+            .ok_or(RefreshError::Unknown)
+    }
+}
+
+#[cfg(test)]
+mod tests;
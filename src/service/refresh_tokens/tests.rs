@@ -0,0 +1,255 @@
+use mockall::predicate;
+
+use crate::{repository::refresh_tokens::{MockRefreshTokenRepository, RefreshTokenRevokeError}, domain::tokens::RefreshToken, constants::{SESSION_ID_GEN_RETRIES, REFRESH_TOKEN_LENGTH_SECONDS}};
+
+use super::*;
+
+fn mock_token_id() -> String {
+    String::from("token_id")
+}
+
+fn mock_family_id() -> String {
+    String::from("family_id")
+}
+
+fn mock_session_id() -> String {
+    String::from("session_id")
+}
+
+fn mock_token(used: bool, expires: i64) -> RefreshToken {
+    RefreshToken {
+        id: mock_token_id(),
+        family_id: mock_family_id(),
+        user_id: 1,
+        session_id: mock_session_id(),
+        expires,
+        used
+    }
+}
+
+fn mock_ok_token() -> RefreshToken {
+    mock_token(false, Utc::now().timestamp() + *REFRESH_TOKEN_LENGTH_SECONDS)
+}
+
+fn mock_used_token() -> RefreshToken {
+    mock_token(true, Utc::now().timestamp() + *REFRESH_TOKEN_LENGTH_SECONDS)
+}
+
+fn mock_expired_token() -> RefreshToken {
+    mock_token(false, Utc::now().timestamp() - 100)
+}
+
+fn service(repository: MockRefreshTokenRepository) -> RotatingRefreshTokenService<MockRefreshTokenRepository> {
+    RotatingRefreshTokenService::new(repository, *REFRESH_TOKEN_LENGTH_SECONDS, *SESSION_ID_GEN_RETRIES)
+}
+
+#[tokio::test]
+async fn rotating_impl_issue_normal() {
+    let mut repository = MockRefreshTokenRepository::new();
+
+    repository
+        .expect_insert()
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let token_data = service(repository).issue(1, &mock_session_id()).await.unwrap();
+    assert_eq!(1, token_data.user_id);
+    assert_eq!(mock_session_id(), token_data.session_id);
+    assert_eq!(REFRESH_TOKEN_LENGTH, token_data.id.len());
+}
+
+#[tokio::test]
+async fn rotating_impl_issue_duplicate_retry() {
+    let mut repository = MockRefreshTokenRepository::new();
+    let mut seq = mockall::Sequence::new();
+
+    repository
+        .expect_insert()
+        .times(1)
+        .in_sequence(&mut seq)
+        .returning(|_| Err(RefreshTokenInsertError::Duplicate));
+
+    repository
+        .expect_insert()
+        .times(1)
+        .in_sequence(&mut seq)
+        .returning(|_| Ok(()));
+
+    assert!(service(repository).issue(1, &mock_session_id()).await.is_ok());
+}
+
+#[tokio::test]
+async fn rotating_impl_issue_duplicate_exhausted() {
+    let mut repository = MockRefreshTokenRepository::new();
+
+    repository
+        .expect_insert()
+        .times(*SESSION_ID_GEN_RETRIES as usize)
+        .returning(|_| Err(RefreshTokenInsertError::Duplicate));
+
+    assert_eq!(Err(RefreshTokenIssueError::Unknown), service(repository).issue(1, &mock_session_id()).await);
+}
+
+#[tokio::test]
+async fn rotating_impl_refresh_normal() {
+    let mut repository = MockRefreshTokenRepository::new();
+
+    repository
+        .expect_get()
+        .with(predicate::eq(mock_token_id()))
+        .times(1)
+        .returning(|_| Ok(mock_ok_token()));
+
+    repository
+        .expect_mark_used()
+        .with(predicate::eq(mock_token_id()))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    repository
+        .expect_insert()
+        .withf(|token_data| token_data.family_id == mock_family_id() && token_data.session_id == mock_session_id())
+        .times(1)
+        .returning(|_| Ok(()));
+
+    repository
+        .expect_revoke_family()
+        .never();
+
+    let token_data = service(repository).refresh(&mock_token_id()).await.unwrap();
+    assert_ne!(mock_token_id(), token_data.id);
+    assert_eq!(1, token_data.user_id);
+}
+
+#[tokio::test]
+async fn rotating_impl_refresh_missing() {
+    let mut repository = MockRefreshTokenRepository::new();
+
+    repository
+        .expect_get()
+        .with(predicate::eq(mock_token_id()))
+        .times(1)
+        .returning(|_| Err(RefreshTokenGetError::Missing));
+
+    repository
+        .expect_mark_used()
+        .never();
+
+    assert_eq!(Err(RefreshError::Missing), service(repository).refresh(&mock_token_id()).await);
+}
+
+#[tokio::test]
+async fn rotating_impl_refresh_expired() {
+    let mut repository = MockRefreshTokenRepository::new();
+
+    repository
+        .expect_get()
+        .with(predicate::eq(mock_token_id()))
+        .times(1)
+        .returning(|_| Ok(mock_expired_token()));
+
+    repository
+        .expect_mark_used()
+        .never();
+
+    assert_eq!(Err(RefreshError::Missing), service(repository).refresh(&mock_token_id()).await);
+}
+
+#[tokio::test]
+async fn rotating_impl_refresh_reused_revokes_family() {
+    let mut repository = MockRefreshTokenRepository::new();
+
+    repository
+        .expect_get()
+        .with(predicate::eq(mock_token_id()))
+        .times(1)
+        .returning(|_| Ok(mock_used_token()));
+
+    repository
+        .expect_revoke_family()
+        .with(predicate::eq(mock_family_id()))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    repository
+        .expect_mark_used()
+        .never();
+
+    repository
+        .expect_insert()
+        .never();
+
+    assert_eq!(Err(RefreshError::Reused), service(repository).refresh(&mock_token_id()).await);
+}
+
+#[tokio::test]
+async fn rotating_impl_refresh_concurrent_reuse_revokes_family() {
+    let mut repository = MockRefreshTokenRepository::new();
+
+    repository
+        .expect_get()
+        .with(predicate::eq(mock_token_id()))
+        .times(1)
+        .returning(|_| Ok(mock_ok_token()));
+
+    repository
+        .expect_mark_used()
+        .with(predicate::eq(mock_token_id()))
+        .times(1)
+        .returning(|_| Err(RefreshTokenUseError::AlreadyUsed));
+
+    repository
+        .expect_revoke_family()
+        .with(predicate::eq(mock_family_id()))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    repository
+        .expect_insert()
+        .never();
+
+    assert_eq!(Err(RefreshError::Reused), service(repository).refresh(&mock_token_id()).await);
+}
+
+#[tokio::test]
+async fn rotating_impl_refresh_revoke_error() {
+    let mut repository = MockRefreshTokenRepository::new();
+
+    repository
+        .expect_get()
+        .with(predicate::eq(mock_token_id()))
+        .times(1)
+        .returning(|_| Ok(mock_used_token()));
+
+    repository
+        .expect_revoke_family()
+        .with(predicate::eq(mock_family_id()))
+        .times(1)
+        .returning(|_| Err(RefreshTokenRevokeError::Unknown));
+
+    assert_eq!(Err(RefreshError::Unknown), service(repository).refresh(&mock_token_id()).await);
+}
+
+#[tokio::test]
+async fn rotating_impl_refresh_insert_error() {
+    let mut repository = MockRefreshTokenRepository::new();
+
+    repository
+        .expect_get()
+        .with(predicate::eq(mock_token_id()))
+        .times(1)
+        .returning(|_| Ok(mock_ok_token()));
+
+    repository
+        .expect_mark_used()
+        .with(predicate::eq(mock_token_id()))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    repository
+        .expect_insert()
+        .times(1)
+        .returning(|_| Err(RefreshTokenInsertError::Unknown));
+
+    assert_eq!(Err(RefreshError::Unknown), service(repository).refresh(&mock_token_id()).await);
+}
@@ -0,0 +1,154 @@
+use std::{net::IpAddr, str::FromStr};
+
+use crate::domain::sessions::{ClientInfo, SessionBinding};
+
+/// Which client properties a session is bound to when it is created.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SessionBindingMode {
+    None,
+    UserAgent,
+    Subnet,
+    Both
+}
+
+impl FromStr for SessionBindingMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "user-agent" => Ok(Self::UserAgent),
+            "subnet" => Ok(Self::Subnet),
+            "both" => Ok(Self::Both),
+            other => Err(format!("Unknown session binding: {}", other))
+        }
+    }
+}
+
+/// What happens to a request whose client doesn't match the session binding.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BindingEnforcement {
+    /// The session is treated as invalid for this request.
+    Reject,
+    /// The request goes through and the mismatch is only logged as a risk event.
+    Flag
+}
+
+impl FromStr for BindingEnforcement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(Self::Reject),
+            "flag" => Ok(Self::Flag),
+            other => Err(format!("Unknown binding enforcement: {}", other))
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionBindingPolicy {
+    pub mode: SessionBindingMode,
+    pub enforcement: BindingEnforcement,
+    pub ipv4_prefix: u8,
+    pub ipv6_prefix: u8
+}
+
+impl Default for SessionBindingPolicy {
+    fn default() -> Self {
+        Self {
+            mode: SessionBindingMode::None,
+            enforcement: BindingEnforcement::Reject,
+            ipv4_prefix: 24,
+            ipv6_prefix: 64
+        }
+    }
+}
+
+impl SessionBindingPolicy {
+    fn binds_user_agent(&self) -> bool {
+        matches!(self.mode, SessionBindingMode::UserAgent | SessionBindingMode::Both)
+    }
+
+    fn binds_subnet(&self) -> bool {
+        matches!(self.mode, SessionBindingMode::Subnet | SessionBindingMode::Both)
+    }
+
+    pub fn bind(&self, client: &ClientInfo) -> SessionBinding {
+        SessionBinding {
+            user_agent_family: self.binds_user_agent()
+                .then(|| client.user_agent.as_deref().map(user_agent_family))
+                .flatten(),
+            ip_subnet: self.binds_subnet()
+                .then(|| client.ip.map(|ip| self.subnet(ip)))
+                .flatten()
+        }
+    }
+
+    /// The properties of `client` that differ from the recorded ones, as they were recorded at login.
+    /// Properties that weren't recorded, e.g. for sessions created before binding was enabled, always match.
+    pub fn mismatch(&self, binding: &SessionBinding, client: &ClientInfo) -> Option<SessionBinding> {
+        let actual = SessionBinding {
+            user_agent_family: client.user_agent.as_deref().map(user_agent_family),
+            ip_subnet: client.ip.map(|ip| self.subnet(ip))
+        };
+
+        let user_agent_matches = !self.binds_user_agent()
+            || binding.user_agent_family.is_none()
+            || binding.user_agent_family == actual.user_agent_family;
+        let subnet_matches = !self.binds_subnet()
+            || binding.ip_subnet.is_none()
+            || binding.ip_subnet == actual.ip_subnet;
+
+        if user_agent_matches && subnet_matches {
+            None
+        } else {
+            Some(actual)
+        }
+    }
+
+    /// Network of `ip` in CIDR notation, e.g. `203.0.113.0/24`.
+    pub fn subnet(&self, ip: IpAddr) -> String {
+        let ip = match ip {
+            IpAddr::V6(ip) => ip.to_ipv4_mapped().map_or(IpAddr::V6(ip), IpAddr::V4),
+            ip => ip
+        };
+
+        match ip {
+            IpAddr::V4(ip) => {
+                let prefix = self.ipv4_prefix.min(32);
+                let mask = u32::MAX.checked_shl(32 - u32::from(prefix)).unwrap_or(0);
+                format!("{}/{}", std::net::Ipv4Addr::from(u32::from(ip) & mask), prefix)
+            },
+            IpAddr::V6(ip) => {
+                let prefix = self.ipv6_prefix.min(128);
+                let mask = u128::MAX.checked_shl(128 - u32::from(prefix)).unwrap_or(0);
+                format!("{}/{}", std::net::Ipv6Addr::from(u128::from(ip) & mask), prefix)
+            }
+        }
+    }
+}
+
+/// Browser or client family, stable across version updates, e.g. `Firefox` or `curl`.
+pub fn user_agent_family(user_agent: &str) -> String {
+    // order matters, Chromium based browsers also announce Chrome and Safari
+    const FAMILIES: [(&str, &str); 6] = [
+        ("Edg/", "Edge"),
+        ("OPR/", "Opera"),
+        ("Firefox/", "Firefox"),
+        ("Chrome/", "Chrome"),
+        ("CriOS/", "Chrome"),
+        ("Safari/", "Safari")
+    ];
+
+    FAMILIES
+        .iter()
+        .find(|(token, _)| user_agent.contains(token))
+        .map(|(_, family)| String::from(*family))
+        .unwrap_or_else(|| user_agent
+            .split(['/', ' '])
+            .next()
+            .unwrap_or_default()
+            .to_owned()
+        )
+}
@@ -1,12 +1,17 @@
+mod binding;
+
+use std::sync::Arc;
+
 use axum::async_trait;
 use mockall::automock;
-use rand::{Rng, distributions::Alphanumeric};
 use chrono::{Utc, NaiveDateTime, DateTime};
 use tracing::{warn, error, info};
 
-use crate::{domain::{users::{Credentials, User}, sessions::SessionData}, repository::{sessions::{SessionRepository, SessionInsertError, SessionGetError}, users::{UserRepository, UserGetError}}, constants::{SESSION_LENGTH_SECONDS, SESSION_ID_LENGTH}};
+use crate::{domain::{users::{Credentials, User}, sessions::{Session, SessionData, SessionBinding, Revocation, ClientInfo}}, repository::{sessions::{SessionRepository, SessionInsertError, SessionGetError, SessionUpdateError}, users::{UserRepository, UserGetError}, revocation::RevocationPublisher}, constants::{SESSION_LENGTH_SECONDS, SESSION_ID_LENGTH, SESSION_ROTATION_GRACE_SECONDS}, passwords::normalize};
+
+use super::{hash::HashService, random_alphanumeric};
 
-use super::hash::HashService;
+pub use binding::{SessionBindingMode, BindingEnforcement, SessionBindingPolicy, user_agent_family};
 
 #[derive(PartialEq, Debug)]
 pub enum LoginError {
@@ -17,19 +22,77 @@ pub enum LoginError {
 #[derive(PartialEq, Debug)]
 pub enum SessionVerifyError {
     Missing,
+    /// The session is valid but was created by a different client, see `SessionBindingPolicy`.
+    BindingMismatch,
+    /// The session is valid but the user hasn't authenticated recently enough, see `SessionService::verify_recent`.
+    StaleAuthentication,
     Unknown
 }
 
+#[derive(PartialEq, Debug)]
 pub enum LogoutError {
     Unknown
 }
 
+#[derive(PartialEq, Debug)]
+pub enum ReauthenticateError {
+    Missing,
+    WrongPassword,
+    Unknown
+}
+
+#[derive(PartialEq, Debug)]
+pub enum RotateError {
+    Missing,
+    Unknown
+}
+
 #[automock]
 #[async_trait]
 pub trait SessionService {
-    async fn login(&self, credentials: Credentials) -> Result<SessionData, LoginError>;
-    async fn verify(&self, id: &str) -> Result<User, SessionVerifyError>;
+    async fn login(&self, credentials: Credentials, client: &ClientInfo) -> Result<SessionData, LoginError>;
+    async fn verify(&self, id: &str, client: &ClientInfo) -> Result<User, SessionVerifyError>;
+    /// Like `verify`, but also requires the user to have authenticated within the last `max_age` seconds.
+    async fn verify_recent(&self, id: &str, client: &ClientInfo, max_age: i64) -> Result<User, SessionVerifyError>;
+    /// Confirms the password of the session's user and restarts the `verify_recent` window.
+    async fn reauthenticate(&self, id: &str, password: &str, client: &ClientInfo) -> Result<(), ReauthenticateError>;
     async fn logout(&self, id: &str) -> Result<(), LogoutError>;
+    /// Moves a session to a new id, the old one keeps working for `SESSION_ROTATION_GRACE_SECONDS`.
+    async fn rotate(&self, id: &str) -> Result<SessionData, RotateError>;
+    /// Drops the user's sessions from every instance's cache after their data, such as the password hash, changed.
+    async fn refresh_user(&self, user_id: i32);
+}
+
+/// Lets a single service instance be shared between routers without requiring it to be `Clone`.
+#[async_trait]
+impl<T: SessionService + Send + Sync + ?Sized> SessionService for Arc<T> {
+    async fn login(&self, credentials: Credentials, client: &ClientInfo) -> Result<SessionData, LoginError> {
+        (**self).login(credentials, client).await
+    }
+
+    async fn verify(&self, id: &str, client: &ClientInfo) -> Result<User, SessionVerifyError> {
+        (**self).verify(id, client).await
+    }
+
+    async fn verify_recent(&self, id: &str, client: &ClientInfo, max_age: i64) -> Result<User, SessionVerifyError> {
+        (**self).verify_recent(id, client, max_age).await
+    }
+
+    async fn reauthenticate(&self, id: &str, password: &str, client: &ClientInfo) -> Result<(), ReauthenticateError> {
+        (**self).reauthenticate(id, password, client).await
+    }
+
+    async fn logout(&self, id: &str) -> Result<(), LogoutError> {
+        (**self).logout(id).await
+    }
+
+    async fn rotate(&self, id: &str) -> Result<SessionData, RotateError> {
+        (**self).rotate(id).await
+    }
+
+    async fn refresh_user(&self, user_id: i32) {
+        (**self).refresh_user(user_id).await
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -42,7 +105,9 @@ where
     session_repository: S,
     user_repository: U,
     hash_service: H,
-    max_retries: u32
+    max_retries: u32,
+    revocations: Option<RevocationPublisher>,
+    binding_policy: SessionBindingPolicy
 }
 
 impl<S, U, H> HashSessionService<S, U, H>
@@ -52,15 +117,106 @@ where
     H: HashService + Send + Sync
 {
     pub fn new(session_repository: S, user_repository: U, hash_service: H, max_retries: u32) -> Self {
-        Self { session_repository, user_repository, hash_service, max_retries }
+        Self {
+            session_repository,
+            user_repository,
+            hash_service,
+            max_retries,
+            revocations: None,
+            binding_policy: SessionBindingPolicy::default()
+        }
+    }
+
+    /// Binds new sessions to the client that created them.
+    pub fn with_binding(mut self, binding_policy: SessionBindingPolicy) -> Self {
+        self.binding_policy = binding_policy;
+        self
+    }
+
+    /// Tells other instances about logouts so they can drop the session from their caches.
+    pub fn with_revocations(mut self, revocations: RevocationPublisher) -> Self {
+        self.revocations = Some(revocations);
+        self
+    }
+
+    /// Peers that miss a revocation still drop the session once their cache entry expires,
+    /// so a failed publish is logged rather than failing the revocation itself.
+    async fn broadcast(&self, revocation: Revocation) {
+        if let Some(revocations) = &self.revocations {
+            if revocations.publish(&revocation).await.is_err() {
+                error!("Unable to broadcast {:?}", revocation);
+            }
+        }
+    }
+
+    /// Fetches a session that is still valid for `client`, dropping it from the repository once expired.
+    async fn load(&self, id: &str, client: &ClientInfo) -> Result<Session, SessionVerifyError> {
+        let session = match self.session_repository.get(id).await {
+            Ok(session) => session,
+            Err(SessionGetError::Missing) => return Err(SessionVerifyError::Missing),
+            Err(SessionGetError::Unknown) => return Err(SessionVerifyError::Unknown)
+        };
+
+        let expires = match NaiveDateTime::from_timestamp_opt(session.expires, 0) {
+            Some(expires) => expires,
+            None => {
+                return Err(match self.session_repository.delete(id).await {
+                    Ok(()) => SessionVerifyError::Missing,
+                    Err(_) => SessionVerifyError::Unknown
+                });
+            }
+        };
+
+        if DateTime::<Utc>::from_utc(expires, Utc) < Utc::now() {
+            return Err(match self.session_repository.delete(id).await {
+                Ok(()) => SessionVerifyError::Missing,
+                Err(_) => SessionVerifyError::Unknown
+            });
+        }
+
+        if let Some(actual) = self.binding_policy.mismatch(&session.binding, client) {
+            warn!(
+                user_id = session.user.id,
+                session = &id[..id.len().min(8)],
+                expected = ?session.binding,
+                actual = ?actual,
+                user_agent = ?client.user_agent,
+                ip = ?client.ip,
+                enforcement = ?self.binding_policy.enforcement,
+                "Session used by a different client than the one it was created by"
+            );
+            if self.binding_policy.enforcement == BindingEnforcement::Reject {
+                return Err(SessionVerifyError::BindingMismatch);
+            }
+        }
+
+        Ok(session)
+    }
+
+    /// Inserts a session under a fresh id, retrying when the id is already taken.
+    async fn create_session(&self, user_id: i32, expires: i64, authenticated_at: i64, binding: SessionBinding) -> Option<SessionData> {
+        for _ in 0..self.max_retries {
+            let session_data = SessionData {
+                id: Self::generate_session_id(SESSION_ID_LENGTH),
+                user_id,
+                expires,
+                authenticated_at,
+                binding: binding.clone()
+            };
+
+            match self.session_repository.insert(&session_data).await {
+                Ok(()) => return Some(session_data),
+                Err(SessionInsertError::Duplicate) => continue,
+                Err(SessionInsertError::Unknown) => return None
+            }
+        };
+
+        error!("Unable to generate a unique session id");
+        None
     }
 
     pub fn generate_session_id(id_len: usize) -> String {
-        rand::thread_rng()
-            .sample_iter(&Alphanumeric)
-            .take(id_len)
-            .map(char::from)
-            .collect()
+        random_alphanumeric(id_len)
     }
 }
 
@@ -72,7 +228,7 @@ where
     H: HashService + Send + Sync
 {
     #[tracing::instrument(skip_all, field(email = credentials.email))]
-    async fn login(&self, credentials: Credentials) -> Result<SessionData, LoginError> {
+    async fn login(&self, credentials: Credentials, client: &ClientInfo) -> Result<SessionData, LoginError> {
         info!("Attempting to login user");
         let user = match self.user_repository.get_by_email(&credentials.email).await {
             Ok(user) => user,
@@ -83,7 +239,7 @@ where
             Err(UserGetError::Unknown) => return Err(LoginError::Unknown)
         };
 
-        match self.hash_service.verify(&credentials.password, &user.password_hash) {
+        match self.hash_service.verify(&normalize(&credentials.password), &user.password_hash) {
             Err(err) => {
                 error!(%err);
                 return Err(LoginError::Unknown);
@@ -96,52 +252,62 @@ where
         };
 
 
-        for _ in 0..self.max_retries {
-            let session_data = SessionData {
-                id: Self::generate_session_id(SESSION_ID_LENGTH),
-                user_id: user.id,
-                expires: Utc::now().timestamp() + *SESSION_LENGTH_SECONDS
-            };
-            
-            match self.session_repository.insert(&session_data).await {
-                Ok(()) => {
-                    info!("Login attempt succeeded");
-                    return Ok(session_data);
-                }
-                Err(SessionInsertError::Duplicate) => continue,
-                Err(SessionInsertError::Unknown) => return Err(LoginError::Unknown)
-            }
-        };
+        let now = Utc::now().timestamp();
+        match self.create_session(user.id, now + *SESSION_LENGTH_SECONDS, now, self.binding_policy.bind(client)).await {
+            Some(session_data) => {
+                info!("Login attempt succeeded");
+                Ok(session_data)
+            },
+            None => Err(LoginError::Unknown)
+        }
+    }
 
-        Err(LoginError::Unknown)
+    #[tracing::instrument(skip_all)]
+    async fn verify(&self, id: &str, client: &ClientInfo) -> Result<User, SessionVerifyError> {
+        self.load(id, client).await.map(|session| session.user)
     }
 
     #[tracing::instrument(skip_all)]
-    async fn verify(&self, id: &str) -> Result<User, SessionVerifyError> {
-        let session = match self.session_repository.get(id).await {
+    async fn verify_recent(&self, id: &str, client: &ClientInfo, max_age: i64) -> Result<User, SessionVerifyError> {
+        let session = self.load(id, client).await?;
+        if session.authenticated_at < Utc::now().timestamp() - max_age {
+            info!(user_id = session.user.id, authenticated_at = session.authenticated_at, "Session needs to be re-authenticated");
+            return Err(SessionVerifyError::StaleAuthentication);
+        }
+
+        Ok(session.user)
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn reauthenticate(&self, id: &str, password: &str, client: &ClientInfo) -> Result<(), ReauthenticateError> {
+        let session = match self.load(id, client).await {
             Ok(session) => session,
-            Err(SessionGetError::Missing) => return Err(SessionVerifyError::Missing),
-            Err(SessionGetError::Unknown) => return Err(SessionVerifyError::Unknown)
+            Err(SessionVerifyError::Unknown) => return Err(ReauthenticateError::Unknown),
+            Err(_) => return Err(ReauthenticateError::Missing)
         };
 
-        let expires = match NaiveDateTime::from_timestamp_opt(session.expires, 0) {
-            Some(expires) => expires,
-            None => {
-                return Err(match self.session_repository.delete(id).await {
-                    Ok(()) => SessionVerifyError::Missing,
-                    Err(_) => SessionVerifyError::Unknown
-                });
+        match self.hash_service.verify(&normalize(password), &session.user.password_hash) {
+            Ok(true) => (),
+            Ok(false) => {
+                warn!(user_id = session.user.id, "Re-authentication attempt failed");
+                return Err(ReauthenticateError::WrongPassword);
+            },
+            Err(err) => {
+                error!(%err);
+                return Err(ReauthenticateError::Unknown);
             }
         };
 
-        if DateTime::<Utc>::from_utc(expires, Utc) < Utc::now() {
-            return Err(match self.session_repository.delete(id).await {
-                Ok(()) => SessionVerifyError::Missing,
-                Err(_) => SessionVerifyError::Unknown
-            });
+        match self.session_repository.mark_authenticated(id, Utc::now().timestamp()).await {
+            Ok(()) => {
+                // peers may have cached the session with its previous authentication time
+                self.broadcast(Revocation::Session { id: String::from(id) }).await;
+                info!(user_id = session.user.id, "Re-authentication succeeded");
+                Ok(())
+            },
+            Err(SessionUpdateError::Missing) => Err(ReauthenticateError::Missing),
+            Err(SessionUpdateError::Unknown) => Err(ReauthenticateError::Unknown)
         }
-
-        Ok(session.user)
     }
 
     #[tracing::instrument(skip_all)]
@@ -149,7 +315,47 @@ where
         self.session_repository
             .delete(id)
             .await
-            .map_err(|_| LogoutError::Unknown)
+            .map_err(|_| LogoutError::Unknown)?;
+
+        self.broadcast(Revocation::Session { id: String::from(id) }).await;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn rotate(&self, id: &str) -> Result<SessionData, RotateError> {
+        let session = match self.session_repository.get(id).await {
+            Ok(session) => session,
+            Err(SessionGetError::Missing) => return Err(RotateError::Missing),
+            Err(SessionGetError::Unknown) => return Err(RotateError::Unknown)
+        };
+
+        // the new id inherits the expiry, authentication time and binding, rotating never extends a session
+        let Some(session_data) = self.create_session(session.user.id, session.expires, session.authenticated_at, session.binding).await else {
+            return Err(RotateError::Unknown);
+        };
+
+        let grace_expires = Utc::now().timestamp() + *SESSION_ROTATION_GRACE_SECONDS;
+        let res = self.session_repository.shorten(id, grace_expires).await;
+        if res.is_err() {
+            // don't hand out a second id while the old one stays valid for the full session
+            if self.session_repository.delete(&session_data.id).await.is_err() {
+                error!("Unable to delete the new session after a failed rotation");
+            }
+            return Err(match res {
+                Err(SessionUpdateError::Missing) => RotateError::Missing,
+                _ => RotateError::Unknown
+            });
+        }
+
+        // peers may have cached the old id with its full expiry, and rotation follows changes to the user
+        self.broadcast(Revocation::User { user_id: session.user.id }).await;
+        info!(user_id = session.user.id, "Session rotated");
+        Ok(session_data)
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn refresh_user(&self, user_id: i32) {
+        self.broadcast(Revocation::User { user_id }).await;
     }
 }
 
@@ -1,6 +1,6 @@
 use mockall::predicate;
 
-use crate::{repository::{sessions::{MockSessionRepository, SessionDeleteError}, users::MockUserRepository}, service::hash::MockHashService, domain::sessions::Session, constants::SESSION_ID_GEN_RETRIES};
+use crate::{repository::{sessions::{MockSessionRepository, SessionDeleteError, SessionUpdateError}, users::MockUserRepository, revocation::{MockRevocationTransport, RevocationPublishError}}, service::hash::MockHashService, domain::sessions::{Session, SessionBinding}, constants::SESSION_ID_GEN_RETRIES};
 
 use super::*;
 
@@ -43,7 +43,9 @@ fn mock_ok_session() -> Session {
     Session {
         id: mock_session_id(),
         user: mock_user(),
-        expires: Utc::now().timestamp() + *SESSION_LENGTH_SECONDS
+        expires: Utc::now().timestamp() + *SESSION_LENGTH_SECONDS,
+        authenticated_at: 0,
+        binding: SessionBinding::default()
     }
 }
 
@@ -51,7 +53,9 @@ fn mock_out_of_range_timestamp_session() -> Session {
     Session {
         id: mock_session_id(),
         user: mock_user(),
-        expires: 1000*1000*1000*1000*1000
+        expires: 1000*1000*1000*1000*1000,
+        authenticated_at: 0,
+        binding: SessionBinding::default()
     }
 }
 
@@ -59,7 +63,9 @@ fn mock_expired_timestamp_session() -> Session {
     Session {
         id: mock_session_id(),
         user: mock_user(),
-        expires: Utc::now().timestamp() - 100*1000
+        expires: Utc::now().timestamp() - 100*1000,
+        authenticated_at: 0,
+        binding: SessionBinding::default()
     }
 }
 
@@ -88,7 +94,7 @@ async fn hash_impl_login_normal() -> Result<(), LoginError> {
 
     let service = HashSessionService::new(session_repository, user_repository, hash_service, *SESSION_ID_GEN_RETRIES);
 
-    let session_data = service.login(mock_credentials()).await?;
+    let session_data = service.login(mock_credentials(), &ClientInfo::default()).await?;
     assert_eq!(session_data.user_id, 1);
 
     Ok(())
@@ -116,7 +122,7 @@ async fn hash_impl_login_get_user_error_missing() {
 
     let service = HashSessionService::new(session_repository, user_repository, hash_service, *SESSION_ID_GEN_RETRIES);
 
-    assert_eq!(Err(LoginError::NoUser), service.login(mock_credentials()).await);
+    assert_eq!(Err(LoginError::NoUser), service.login(mock_credentials(), &ClientInfo::default()).await);
 }
 
 #[tokio::test]
@@ -141,7 +147,7 @@ async fn hash_impl_login_get_user_error_unknown() {
 
     let service = HashSessionService::new(session_repository, user_repository, hash_service, *SESSION_ID_GEN_RETRIES);
 
-    assert_eq!(Err(LoginError::Unknown), service.login(mock_credentials()).await);
+    assert_eq!(Err(LoginError::Unknown), service.login(mock_credentials(), &ClientInfo::default()).await);
 }
 
 #[tokio::test]
@@ -168,7 +174,7 @@ async fn hash_impl_login_hash_verify_false() {
 
     let service = HashSessionService::new(session_repository, user_repository, hash_service, *SESSION_ID_GEN_RETRIES);
 
-    assert_eq!(Err(LoginError::NoUser), service.login(mock_credentials()).await);
+    assert_eq!(Err(LoginError::NoUser), service.login(mock_credentials(), &ClientInfo::default()).await);
 }
 
 #[tokio::test]
@@ -195,7 +201,7 @@ async fn hash_impl_login_hash_verify_error() {
 
     let service = HashSessionService::new(session_repository, user_repository, hash_service, *SESSION_ID_GEN_RETRIES);
 
-    assert_eq!(Err(LoginError::Unknown), service.login(mock_credentials()).await);
+    assert_eq!(Err(LoginError::Unknown), service.login(mock_credentials(), &ClientInfo::default()).await);
 }
 
 #[tokio::test]
@@ -223,7 +229,7 @@ async fn hash_impl_login_session_insert_error() {
 
     let service = HashSessionService::new(session_repository, user_repository, hash_service, *SESSION_ID_GEN_RETRIES);
 
-    assert_eq!(Err(LoginError::Unknown), service.login(mock_credentials()).await);
+    assert_eq!(Err(LoginError::Unknown), service.login(mock_credentials(), &ClientInfo::default()).await);
 }
 
 #[tokio::test]
@@ -240,7 +246,7 @@ async fn hash_impl_verify_normal() {
 
     let service = HashSessionService::new(session_repository, user_repository, hash_service, *SESSION_ID_GEN_RETRIES);
 
-    assert_eq!(Ok(mock_user()), service.verify(&mock_session_id()).await);
+    assert_eq!(Ok(mock_user()), service.verify(&mock_session_id(), &ClientInfo::default()).await);
 }
 
 #[tokio::test]
@@ -263,7 +269,7 @@ async fn hash_impl_verify_invalid_timestamp() {
 
     let service = HashSessionService::new(session_repository, user_repository, hash_service, *SESSION_ID_GEN_RETRIES);
 
-    assert_eq!(Err(SessionVerifyError::Missing), service.verify(&mock_session_id()).await);
+    assert_eq!(Err(SessionVerifyError::Missing), service.verify(&mock_session_id(), &ClientInfo::default()).await);
 }
 
 #[tokio::test]
@@ -286,7 +292,7 @@ async fn hash_impl_verify_invalid_timestamp_delete_error() {
 
     let service = HashSessionService::new(session_repository, user_repository, hash_service, *SESSION_ID_GEN_RETRIES);
 
-    assert_eq!(Err(SessionVerifyError::Unknown), service.verify(&mock_session_id()).await);
+    assert_eq!(Err(SessionVerifyError::Unknown), service.verify(&mock_session_id(), &ClientInfo::default()).await);
 }
 
 #[tokio::test]
@@ -309,7 +315,7 @@ async fn hash_impl_verify_expired_timestamp() {
 
     let service = HashSessionService::new(session_repository, user_repository, hash_service, *SESSION_ID_GEN_RETRIES);
 
-    assert_eq!(Err(SessionVerifyError::Missing), service.verify(&mock_session_id()).await);
+    assert_eq!(Err(SessionVerifyError::Missing), service.verify(&mock_session_id(), &ClientInfo::default()).await);
 }
 
 #[tokio::test]
@@ -332,5 +338,459 @@ async fn hash_impl_verify_expired_timestamp_delete_error() {
 
     let service = HashSessionService::new(session_repository, user_repository, hash_service, *SESSION_ID_GEN_RETRIES);
 
-    assert_eq!(Err(SessionVerifyError::Unknown), service.verify(&mock_session_id()).await);
+    assert_eq!(Err(SessionVerifyError::Unknown), service.verify(&mock_session_id(), &ClientInfo::default()).await);
+}
+
+#[tokio::test]
+async fn hash_impl_logout_broadcasts() {
+    let mut session_repository = MockSessionRepository::new();
+    let mut transport = MockRevocationTransport::new();
+
+    session_repository
+        .expect_delete()
+        .with(predicate::eq(mock_session_id()))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    transport
+        .expect_publish()
+        .with(predicate::eq(Revocation::Session { id: mock_session_id() }))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let service = HashSessionService::new(session_repository, MockUserRepository::new(), MockHashService::new(), *SESSION_ID_GEN_RETRIES)
+        .with_revocations(RevocationPublisher::new(transport));
+
+    assert_eq!(Ok(()), service.logout(&mock_session_id()).await);
+}
+
+#[tokio::test]
+async fn hash_impl_logout_broadcast_error() {
+    let mut session_repository = MockSessionRepository::new();
+    let mut transport = MockRevocationTransport::new();
+
+    session_repository
+        .expect_delete()
+        .times(1)
+        .returning(|_| Ok(()));
+
+    transport
+        .expect_publish()
+        .times(1)
+        .returning(|_| Err(RevocationPublishError::Unknown));
+
+    let service = HashSessionService::new(session_repository, MockUserRepository::new(), MockHashService::new(), *SESSION_ID_GEN_RETRIES)
+        .with_revocations(RevocationPublisher::new(transport));
+
+    assert_eq!(Ok(()), service.logout(&mock_session_id()).await);
+}
+
+#[tokio::test]
+async fn hash_impl_logout_delete_error() {
+    let mut session_repository = MockSessionRepository::new();
+    let mut transport = MockRevocationTransport::new();
+
+    session_repository
+        .expect_delete()
+        .times(1)
+        .returning(|_| Err(SessionDeleteError::Unknown));
+
+    transport
+        .expect_publish()
+        .never();
+
+    let service = HashSessionService::new(session_repository, MockUserRepository::new(), MockHashService::new(), *SESSION_ID_GEN_RETRIES)
+        .with_revocations(RevocationPublisher::new(transport));
+
+    assert_eq!(Err(LogoutError::Unknown), service.logout(&mock_session_id()).await);
+}
+
+fn mock_firefox_client() -> ClientInfo {
+    ClientInfo {
+        user_agent: Some(String::from("Mozilla/5.0 (X11; Linux x86_64; rv:109.0) Gecko/20100101 Firefox/113.0")),
+        ip: Some("203.0.113.7".parse().unwrap())
+    }
+}
+
+fn mock_binding_policy(mode: SessionBindingMode, enforcement: BindingEnforcement) -> SessionBindingPolicy {
+    SessionBindingPolicy { mode, enforcement, ..SessionBindingPolicy::default() }
+}
+
+fn mock_bound_session() -> Session {
+    Session {
+        binding: SessionBinding {
+            user_agent_family: Some(String::from("Firefox")),
+            ip_subnet: Some(String::from("203.0.113.0/24"))
+        },
+        ..mock_ok_session()
+    }
+}
+
+fn binding_service(policy: SessionBindingPolicy) -> HashSessionService<MockSessionRepository, MockUserRepository, MockHashService> {
+    let mut session_repository = MockSessionRepository::new();
+    session_repository
+        .expect_get()
+        .with(predicate::eq(mock_session_id()))
+        .returning(|_| Ok(mock_bound_session()));
+    session_repository
+        .expect_delete()
+        .never();
+
+    HashSessionService::new(session_repository, MockUserRepository::new(), MockHashService::new(), *SESSION_ID_GEN_RETRIES)
+        .with_binding(policy)
+}
+
+#[test]
+fn user_agent_families() {
+    assert_eq!("Firefox", user_agent_family("Mozilla/5.0 (X11; Linux x86_64; rv:109.0) Gecko/20100101 Firefox/113.0"));
+    assert_eq!("Chrome", user_agent_family("Mozilla/5.0 (Windows NT 10.0) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/113.0.0.0 Safari/537.36"));
+    assert_eq!("Edge", user_agent_family("Mozilla/5.0 (Windows NT 10.0) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/113.0.0.0 Safari/537.36 Edg/113.0.1774.42"));
+    assert_eq!("Safari", user_agent_family("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/16.4 Safari/605.1.15"));
+    assert_eq!("curl", user_agent_family("curl/8.0.1"));
+}
+
+#[test]
+fn binding_subnets() {
+    let policy = SessionBindingPolicy::default();
+    assert_eq!("203.0.113.0/24", policy.subnet("203.0.113.7".parse().unwrap()));
+    assert_eq!("203.0.113.0/24", policy.subnet("::ffff:203.0.113.7".parse().unwrap()));
+    assert_eq!("2001:db8:1:2::/64", policy.subnet("2001:db8:1:2:3:4:5:6".parse().unwrap()));
+
+    let policy = SessionBindingPolicy { ipv4_prefix: 16, ipv6_prefix: 48, ..policy };
+    assert_eq!("203.0.0.0/16", policy.subnet("203.0.113.7".parse().unwrap()));
+    assert_eq!("2001:db8:1::/48", policy.subnet("2001:db8:1:2:3:4:5:6".parse().unwrap()));
+}
+
+#[test]
+fn binding_bind() {
+    let binding = mock_binding_policy(SessionBindingMode::UserAgent, BindingEnforcement::Reject).bind(&mock_firefox_client());
+    assert_eq!(SessionBinding { user_agent_family: Some(String::from("Firefox")), ip_subnet: None }, binding);
+
+    let binding = mock_binding_policy(SessionBindingMode::None, BindingEnforcement::Reject).bind(&mock_firefox_client());
+    assert_eq!(SessionBinding::default(), binding);
+
+    let binding = mock_binding_policy(SessionBindingMode::Both, BindingEnforcement::Reject).bind(&mock_firefox_client());
+    assert_eq!(mock_bound_session().binding, binding);
+}
+
+#[tokio::test]
+async fn hash_impl_login_records_binding() {
+    let mut session_repository = MockSessionRepository::new();
+    let mut user_repository = MockUserRepository::new();
+    let mut hash_service = MockHashService::new();
+
+    user_repository
+        .expect_get_by_email()
+        .returning(|_| Ok(mock_user()));
+    hash_service
+        .expect_verify()
+        .returning(|_, _| Ok(true));
+    session_repository
+        .expect_insert()
+        .withf(|session_data| session_data.binding == mock_bound_session().binding)
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let service = HashSessionService::new(session_repository, user_repository, hash_service, *SESSION_ID_GEN_RETRIES)
+        .with_binding(mock_binding_policy(SessionBindingMode::Both, BindingEnforcement::Reject));
+
+    assert!(service.login(mock_credentials(), &mock_firefox_client()).await.is_ok());
+}
+
+#[tokio::test]
+async fn hash_impl_verify_binding_match() {
+    let service = binding_service(mock_binding_policy(SessionBindingMode::Both, BindingEnforcement::Reject));
+
+    let client = ClientInfo { ip: Some("203.0.113.200".parse().unwrap()), ..mock_firefox_client() };
+    assert_eq!(Ok(mock_user()), service.verify(&mock_session_id(), &client).await);
+}
+
+#[tokio::test]
+async fn hash_impl_verify_binding_mismatch_reject() {
+    let service = binding_service(mock_binding_policy(SessionBindingMode::Subnet, BindingEnforcement::Reject));
+
+    let client = ClientInfo { ip: Some("198.51.100.7".parse().unwrap()), ..mock_firefox_client() };
+    assert_eq!(Err(SessionVerifyError::BindingMismatch), service.verify(&mock_session_id(), &client).await);
+
+    let client = ClientInfo { user_agent: Some(String::from("curl/8.0.1")), ..mock_firefox_client() };
+    assert_eq!(Ok(mock_user()), service.verify(&mock_session_id(), &client).await);
+}
+
+#[tokio::test]
+async fn hash_impl_verify_binding_mismatch_flag() {
+    let service = binding_service(mock_binding_policy(SessionBindingMode::Both, BindingEnforcement::Flag));
+
+    let client = ClientInfo { user_agent: Some(String::from("curl/8.0.1")), ..mock_firefox_client() };
+    assert_eq!(Ok(mock_user()), service.verify(&mock_session_id(), &client).await);
+}
+
+#[tokio::test]
+async fn hash_impl_verify_unbound_session() {
+    let mut session_repository = MockSessionRepository::new();
+    session_repository
+        .expect_get()
+        .returning(|_| Ok(mock_ok_session()));
+
+    let service = HashSessionService::new(session_repository, MockUserRepository::new(), MockHashService::new(), *SESSION_ID_GEN_RETRIES)
+        .with_binding(mock_binding_policy(SessionBindingMode::Both, BindingEnforcement::Reject));
+
+    assert_eq!(Ok(mock_user()), service.verify(&mock_session_id(), &mock_firefox_client()).await);
+}
+
+#[tokio::test]
+async fn hash_impl_rotate_normal() {
+    let mut session_repository = MockSessionRepository::new();
+    let mut transport = MockRevocationTransport::new();
+    let old_session = mock_bound_session();
+    let expires = old_session.expires;
+
+    session_repository
+        .expect_get()
+        .with(predicate::eq(mock_session_id()))
+        .times(1)
+        .returning(move |_| Ok(old_session.clone()));
+
+    session_repository
+        .expect_insert()
+        .withf(move |session_data| session_data.id != mock_session_id()
+            && session_data.user_id == 1
+            && session_data.expires == expires
+            && session_data.binding == mock_bound_session().binding)
+        .times(1)
+        .returning(|_| Ok(()));
+
+    session_repository
+        .expect_shorten()
+        .withf(|id, expires| id == mock_session_id()
+            && (*expires - Utc::now().timestamp() - *SESSION_ROTATION_GRACE_SECONDS).abs() <= 1)
+        .times(1)
+        .returning(|_, _| Ok(()));
+
+    transport
+        .expect_publish()
+        .with(predicate::eq(Revocation::User { user_id: 1 }))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let service = HashSessionService::new(session_repository, MockUserRepository::new(), MockHashService::new(), *SESSION_ID_GEN_RETRIES)
+        .with_revocations(RevocationPublisher::new(transport));
+
+    let session_data = service.rotate(&mock_session_id()).await.unwrap();
+    assert_eq!(SESSION_ID_LENGTH, session_data.id.len());
+    assert_eq!(expires, session_data.expires);
+}
+
+#[tokio::test]
+async fn hash_impl_refresh_user_broadcasts() {
+    let mut transport = MockRevocationTransport::new();
+
+    transport
+        .expect_publish()
+        .with(predicate::eq(Revocation::User { user_id: 1 }))
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let service = HashSessionService::new(MockSessionRepository::new(), MockUserRepository::new(), MockHashService::new(), *SESSION_ID_GEN_RETRIES)
+        .with_revocations(RevocationPublisher::new(transport));
+
+    service.refresh_user(1).await;
+}
+
+#[tokio::test]
+async fn hash_impl_rotate_duplicate_id() {
+    let mut session_repository = MockSessionRepository::new();
+    let mut attempts = 0;
+
+    session_repository
+        .expect_get()
+        .returning(|_| Ok(mock_ok_session()));
+
+    session_repository
+        .expect_insert()
+        .times(2)
+        .returning(move |_| {
+            attempts += 1;
+            match attempts {
+                1 => Err(SessionInsertError::Duplicate),
+                _ => Ok(())
+            }
+        });
+
+    session_repository
+        .expect_shorten()
+        .times(1)
+        .returning(|_, _| Ok(()));
+
+    let service = HashSessionService::new(session_repository, MockUserRepository::new(), MockHashService::new(), *SESSION_ID_GEN_RETRIES);
+
+    assert!(service.rotate(&mock_session_id()).await.is_ok());
+}
+
+#[tokio::test]
+async fn hash_impl_rotate_missing() {
+    let mut session_repository = MockSessionRepository::new();
+
+    session_repository
+        .expect_get()
+        .returning(|_| Err(SessionGetError::Missing));
+
+    session_repository
+        .expect_insert()
+        .never();
+
+    let service = HashSessionService::new(session_repository, MockUserRepository::new(), MockHashService::new(), *SESSION_ID_GEN_RETRIES);
+
+    assert_eq!(Err(RotateError::Missing), service.rotate(&mock_session_id()).await);
+}
+
+#[tokio::test]
+async fn hash_impl_rotate_shorten_error() {
+    let mut session_repository = MockSessionRepository::new();
+    let mut transport = MockRevocationTransport::new();
+
+    session_repository
+        .expect_get()
+        .returning(|_| Ok(mock_ok_session()));
+
+    session_repository
+        .expect_insert()
+        .times(1)
+        .returning(|_| Ok(()));
+
+    session_repository
+        .expect_shorten()
+        .times(1)
+        .returning(|_, _| Err(SessionUpdateError::Unknown));
+
+    session_repository
+        .expect_delete()
+        .withf(|id| id != mock_session_id())
+        .times(1)
+        .returning(|_| Ok(()));
+
+    transport
+        .expect_publish()
+        .never();
+
+    let service = HashSessionService::new(session_repository, MockUserRepository::new(), MockHashService::new(), *SESSION_ID_GEN_RETRIES)
+        .with_revocations(RevocationPublisher::new(transport));
+
+    assert_eq!(Err(RotateError::Unknown), service.rotate(&mock_session_id()).await);
+}
+
+fn mock_authenticated_session(authenticated_at: i64) -> Session {
+    Session {
+        authenticated_at,
+        ..mock_ok_session()
+    }
+}
+
+#[tokio::test]
+async fn hash_impl_login_records_authenticated_at() {
+    let mut session_repository = MockSessionRepository::new();
+    let mut user_repository = MockUserRepository::new();
+    let mut hash_service = MockHashService::new();
+
+    user_repository
+        .expect_get_by_email()
+        .returning(|_| Ok(mock_user()));
+
+    hash_service
+        .expect_verify()
+        .returning(|_, _| Ok(true));
+
+    session_repository
+        .expect_insert()
+        .withf(|session_data| (session_data.authenticated_at - Utc::now().timestamp()).abs() <= 1)
+        .times(1)
+        .returning(|_| Ok(()));
+
+    let service = HashSessionService::new(session_repository, user_repository, hash_service, *SESSION_ID_GEN_RETRIES);
+
+    assert!(service.login(mock_credentials(), &ClientInfo::default()).await.is_ok());
+}
+
+#[tokio::test]
+async fn hash_impl_verify_recent_fresh() {
+    let mut session_repository = MockSessionRepository::new();
+    session_repository
+        .expect_get()
+        .returning(|_| Ok(mock_authenticated_session(Utc::now().timestamp() - 60)));
+
+    let service = HashSessionService::new(session_repository, MockUserRepository::new(), MockHashService::new(), *SESSION_ID_GEN_RETRIES);
+
+    assert_eq!(Ok(mock_user()), service.verify_recent(&mock_session_id(), &ClientInfo::default(), 600).await);
+}
+
+#[tokio::test]
+async fn hash_impl_verify_recent_stale() {
+    let mut session_repository = MockSessionRepository::new();
+    session_repository
+        .expect_get()
+        .returning(|_| Ok(mock_authenticated_session(Utc::now().timestamp() - 601)));
+
+    let service = HashSessionService::new(session_repository, MockUserRepository::new(), MockHashService::new(), *SESSION_ID_GEN_RETRIES);
+
+    assert_eq!(Err(SessionVerifyError::StaleAuthentication), service.verify_recent(&mock_session_id(), &ClientInfo::default(), 600).await);
+}
+
+#[tokio::test]
+async fn hash_impl_reauthenticate_normal() {
+    let mut session_repository = MockSessionRepository::new();
+    let mut hash_service = MockHashService::new();
+
+    session_repository
+        .expect_get()
+        .returning(|_| Ok(mock_authenticated_session(0)));
+
+    hash_service
+        .expect_verify()
+        .with(predicate::eq(mock_password()), predicate::eq(mock_hashed_password()))
+        .times(1)
+        .returning(|_, _| Ok(true));
+
+    session_repository
+        .expect_mark_authenticated()
+        .withf(|id, authenticated_at| id == mock_session_id() && (*authenticated_at - Utc::now().timestamp()).abs() <= 1)
+        .times(1)
+        .returning(|_, _| Ok(()));
+
+    let service = HashSessionService::new(session_repository, MockUserRepository::new(), hash_service, *SESSION_ID_GEN_RETRIES);
+
+    assert_eq!(Ok(()), service.reauthenticate(&mock_session_id(), &mock_password(), &ClientInfo::default()).await);
+}
+
+#[tokio::test]
+async fn hash_impl_reauthenticate_wrong_password() {
+    let mut session_repository = MockSessionRepository::new();
+    let mut hash_service = MockHashService::new();
+
+    session_repository
+        .expect_get()
+        .returning(|_| Ok(mock_authenticated_session(0)));
+
+    hash_service
+        .expect_verify()
+        .times(1)
+        .returning(|_, _| Ok(false));
+
+    session_repository
+        .expect_mark_authenticated()
+        .never();
+
+    let service = HashSessionService::new(session_repository, MockUserRepository::new(), hash_service, *SESSION_ID_GEN_RETRIES);
+
+    assert_eq!(Err(ReauthenticateError::WrongPassword), service.reauthenticate(&mock_session_id(), &mock_password(), &ClientInfo::default()).await);
+}
+
+#[tokio::test]
+async fn hash_impl_reauthenticate_missing() {
+    let mut session_repository = MockSessionRepository::new();
+    session_repository
+        .expect_get()
+        .returning(|_| Err(SessionGetError::Missing));
+
+    let service = HashSessionService::new(session_repository, MockUserRepository::new(), MockHashService::new(), *SESSION_ID_GEN_RETRIES);
+
+    assert_eq!(Err(ReauthenticateError::Missing), service.reauthenticate(&mock_session_id(), &mock_password(), &ClientInfo::default()).await);
 }
@@ -0,0 +1,160 @@
+use std::{fmt::Debug, fs, sync::Arc};
+
+use anyhow::{Context, Error};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::Utc;
+use jsonwebtoken::{
+    Algorithm, EncodingKey, Header,
+    jwk::{AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse}
+};
+use mockall::automock;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use sha2::{Digest, Sha256};
+use tracing::{error, info};
+
+use crate::domain::tokens::{AccessClaims, AccessToken};
+
+#[derive(PartialEq, Debug)]
+pub enum TokenIssueError {
+    Unknown
+}
+
+#[automock]
+pub trait TokenService {
+    fn issue(&self, user_id: i32, session_id: &str) -> Result<AccessToken, TokenIssueError>;
+    fn jwks(&self) -> JwkSet;
+}
+
+impl<T: TokenService + ?Sized> TokenService for Arc<T> {
+    fn issue(&self, user_id: i32, session_id: &str) -> Result<AccessToken, TokenIssueError> {
+        (**self).issue(user_id, session_id)
+    }
+
+    fn jwks(&self) -> JwkSet {
+        (**self).jwks()
+    }
+}
+
+/// Ed25519 key used to sign access tokens, identified by its key ID.
+#[derive(Clone)]
+pub struct SigningKey {
+    kid: String,
+    encoding_key: EncodingKey,
+    public_key: Vec<u8>
+}
+
+impl Debug for SigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SigningKey").field("kid", &self.kid).finish_non_exhaustive()
+    }
+}
+
+impl SigningKey {
+    pub fn from_pkcs8_der(kid: &str, der: &[u8]) -> anyhow::Result<Self> {
+        let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(der)
+            .map_err(|err| Error::msg(format!("Invalid Ed25519 key {}: {}", kid, err)))?;
+
+        Ok(Self {
+            kid: String::from(kid),
+            encoding_key: EncodingKey::from_ed_der(der),
+            public_key: key_pair.public_key().as_ref().to_vec()
+        })
+    }
+
+    pub fn from_pem_file(kid: &str, path: &str) -> anyhow::Result<Self> {
+        let contents = fs::read(path).with_context(|| format!("Unable to read signing key {}", path))?;
+        let pem = pem::parse(contents)?;
+        Self::from_pkcs8_der(kid, &pem.contents)
+    }
+
+    fn jwk(&self) -> Jwk {
+        Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                algorithm: Some(Algorithm::EdDSA),
+                key_id: Some(self.kid.clone()),
+                ..Default::default()
+            },
+            algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: URL_SAFE_NO_PAD.encode(&self.public_key)
+            })
+        }
+    }
+}
+
+/// Parses a `kid=path,kid=path` list of PKCS#8 PEM files.
+/// The first key signs new tokens, the remaining ones are only published
+/// so that tokens signed before a rotation keep verifying until they expire.
+pub fn load_signing_keys(spec: &str) -> anyhow::Result<Vec<SigningKey>> {
+    spec.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.split_once('=') {
+            Some((kid, path)) => SigningKey::from_pem_file(kid.trim(), path.trim()),
+            None => Err(Error::msg(format!("Malformed signing key entry: {}", entry)))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct JwtTokenService {
+    keys: Arc<[SigningKey]>,
+    token_length_seconds: i64
+}
+
+impl JwtTokenService {
+    pub fn new(keys: Vec<SigningKey>, token_length_seconds: i64) -> Self {
+        assert!(!keys.is_empty(), "At least one signing key is required");
+        Self { keys: keys.into(), token_length_seconds }
+    }
+}
+
+/// Hex SHA-256 of a session ID. Lets token consumers tell sessions apart without the ID itself,
+/// which is the session's secret and would otherwise be readable by anyone holding the token.
+pub fn session_handle(session_id: &str) -> String {
+    format!("{:x}", Sha256::digest(session_id.as_bytes()))
+}
+
+impl TokenService for JwtTokenService {
+    #[tracing::instrument(skip_all, fields(user_id = user_id))]
+    fn issue(&self, user_id: i32, session_id: &str) -> Result<AccessToken, TokenIssueError> {
+        let key = &self.keys[0];
+        let iat = Utc::now().timestamp();
+        let claims = AccessClaims {
+            sub: user_id.to_string(),
+            sid: session_handle(session_id),
+            iat,
+            exp: iat + self.token_length_seconds
+        };
+
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some(key.kid.clone());
+
+        match jsonwebtoken::encode(&header, &claims, &key.encoding_key) {
+            Ok(access_token) => {
+                info!("Issued access token signed with {}", key.kid);
+                Ok(AccessToken {
+                    access_token,
+                    token_type: String::from("Bearer"),
+                    expires_in: self.token_length_seconds,
+                    refresh_token: None
+                })
+            },
+            Err(err) => {
+                error!(%err);
+                Err(TokenIssueError::Unknown)
+            }
+        }
+    }
+
+    fn jwks(&self) -> JwkSet {
+        JwkSet {
+            keys: self.keys.iter().map(SigningKey::jwk).collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,93 @@
+use jsonwebtoken::{DecodingKey, Validation};
+use ring::rand::SystemRandom;
+
+use super::*;
+
+fn mock_session_id() -> String {
+    String::from("session_id")
+}
+
+fn mock_signing_key(kid: &str) -> SigningKey {
+    let der = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+    SigningKey::from_pkcs8_der(kid, der.as_ref()).unwrap()
+}
+
+fn decode(service: &JwtTokenService, token: &str) -> AccessClaims {
+    let kid = jsonwebtoken::decode_header(token).unwrap().kid.unwrap();
+    let jwks = service.jwks();
+    let jwk = jwks.find(&kid).unwrap();
+    let key = match &jwk.algorithm {
+        AlgorithmParameters::OctetKeyPair(params) => DecodingKey::from_ed_components(&params.x).unwrap(),
+        _ => panic!("Unexpected key type")
+    };
+
+    jsonwebtoken::decode::<AccessClaims>(token, &key, &Validation::new(Algorithm::EdDSA)).unwrap().claims
+}
+
+#[test]
+fn jwt_impl_issue_normal() {
+    let service = JwtTokenService::new(vec![mock_signing_key("current")], 300);
+
+    let token = service.issue(1, &mock_session_id()).unwrap();
+    assert_eq!("Bearer", token.token_type);
+    assert_eq!(300, token.expires_in);
+
+    let claims = decode(&service, &token.access_token);
+    assert_eq!("1", claims.sub);
+    assert_eq!(session_handle(&mock_session_id()), claims.sid);
+    assert_eq!(claims.iat + 300, claims.exp);
+}
+
+#[test]
+fn jwt_impl_issue_hides_session_id() {
+    let service = JwtTokenService::new(vec![mock_signing_key("current")], 300);
+
+    let token = service.issue(1, &mock_session_id()).unwrap();
+    let claims = decode(&service, &token.access_token);
+
+    assert_ne!(mock_session_id(), claims.sid);
+    assert!(!claims.sid.contains(&mock_session_id()));
+    assert_eq!(64, claims.sid.len());
+}
+
+#[test]
+fn jwt_impl_issue_signs_with_first_key() {
+    let service = JwtTokenService::new(vec![mock_signing_key("current"), mock_signing_key("previous")], 300);
+
+    let token = service.issue(1, &mock_session_id()).unwrap();
+    let header = jsonwebtoken::decode_header(&token.access_token).unwrap();
+
+    assert_eq!(Algorithm::EdDSA, header.alg);
+    assert_eq!(Some(String::from("current")), header.kid);
+}
+
+#[test]
+fn jwt_impl_jwks_publishes_all_keys() {
+    let service = JwtTokenService::new(vec![mock_signing_key("current"), mock_signing_key("previous")], 300);
+
+    let jwks = service.jwks();
+    let kids: Vec<_> = jwks.keys.iter().map(|jwk| jwk.common.key_id.clone().unwrap()).collect();
+
+    assert_eq!(vec!["current", "previous"], kids);
+}
+
+#[test]
+fn jwt_impl_rotated_token_still_verifies() {
+    let previous = mock_signing_key("previous");
+    let old_service = JwtTokenService::new(vec![previous.clone()], 300);
+    let token = old_service.issue(1, &mock_session_id()).unwrap();
+
+    let service = JwtTokenService::new(vec![mock_signing_key("current"), previous], 300);
+
+    assert_eq!("1", decode(&service, &token.access_token).sub);
+}
+
+#[test]
+fn load_signing_keys_malformed_entry() {
+    assert!(load_signing_keys("no_path").is_err());
+}
+
+#[test]
+fn load_signing_keys_empty() {
+    assert!(load_signing_keys("").unwrap().is_empty());
+}
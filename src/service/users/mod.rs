@@ -1,8 +1,10 @@
+use std::sync::Arc;
+
 use axum::async_trait;
 use mockall::automock;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
-use crate::{domain::users::{Credentials, UserData}, repository::users::{UserInsertError, UserRepository}};
+use crate::{domain::users::{Credentials, PasswordChange, User, UserData}, repository::users::{UserInsertError, UserRepository, UserUpdateError}, passwords::normalize};
 
 use super::hash::HashService;
 
@@ -12,10 +14,29 @@ pub enum UserCreationError {
     Unknown
 }
 
+#[derive(PartialEq, Debug)]
+pub enum PasswordChangeError {
+    WrongPassword,
+    Unknown
+}
+
 #[automock]
 #[async_trait]
 pub trait UserService {
     async fn register(&self, credentials: Credentials) -> Result<(), UserCreationError>;
+    /// Replaces the password of `user` once the current one is confirmed.
+    async fn change_password(&self, user: &User, change: PasswordChange) -> Result<(), PasswordChangeError>;
+}
+
+#[async_trait]
+impl<T: UserService + Send + Sync + ?Sized> UserService for Arc<T> {
+    async fn register(&self, credentials: Credentials) -> Result<(), UserCreationError> {
+        (**self).register(credentials).await
+    }
+
+    async fn change_password(&self, user: &User, change: PasswordChange) -> Result<(), PasswordChangeError> {
+        (**self).change_password(user, change).await
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -50,7 +71,7 @@ where
     #[tracing::instrument(skip_all, fields(email = credentials.email))]
     async fn register(&self, credentials: Credentials) -> Result<(), UserCreationError> {
         info!("Attempting to register user");
-        let password_hash = match self.hash_service.hash(&credentials.password) {
+        let password_hash = match self.hash_service.hash(&normalize(&credentials.password)) {
             Ok(hash) => hash,
             Err(err) => {
                 error!(%err);
@@ -67,6 +88,38 @@ where
             Err(UserInsertError::Unknown) => Err(UserCreationError::Unknown)
         }
     }
+
+    #[tracing::instrument(skip_all, fields(user_id = user.id))]
+    async fn change_password(&self, user: &User, change: PasswordChange) -> Result<(), PasswordChangeError> {
+        info!("Attempting to change password");
+        match self.hash_service.verify(&normalize(&change.current_password), &user.password_hash) {
+            Ok(true) => (),
+            Ok(false) => {
+                warn!("Password change attempt with a wrong password");
+                return Err(PasswordChangeError::WrongPassword);
+            },
+            Err(err) => {
+                error!(%err);
+                return Err(PasswordChangeError::Unknown);
+            }
+        };
+
+        let password_hash = match self.hash_service.hash(&normalize(&change.new_password)) {
+            Ok(hash) => hash,
+            Err(err) => {
+                error!(%err);
+                return Err(PasswordChangeError::Unknown);
+            }
+        };
+
+        match self.repository.update_password(user.id, &password_hash).await {
+            Ok(()) => {
+                info!("Password change succeeded");
+                Ok(())
+            },
+            Err(UserUpdateError::Missing | UserUpdateError::Unknown) => Err(PasswordChangeError::Unknown)
+        }
+    }
 }
 
 
@@ -119,3 +119,92 @@ async fn hash_impl_register_insertion_error_unknown() {
 
     assert_eq!(Err(UserCreationError::Unknown), service.register(mock_credentials()).await);
 }
+
+fn mock_user() -> User {
+    User {
+        id: 1,
+        email: mock_email(),
+        password_hash: mock_hashed_password()
+    }
+}
+
+fn mock_password_change() -> PasswordChange {
+    PasswordChange {
+        current_password: mock_password(),
+        new_password: String::from("new_password")
+    }
+}
+
+#[tokio::test]
+async fn hash_impl_change_password_normal() {
+    let mut user_repository = MockUserRepository::new();
+    let mut hash_service = MockHashService::new();
+
+    hash_service
+        .expect_verify()
+        .with(predicate::eq(mock_password()), predicate::eq(mock_hashed_password()))
+        .times(1)
+        .returning(|_, _| Ok(true));
+
+    hash_service
+        .expect_hash()
+        .with(predicate::eq("new_password"))
+        .times(1)
+        .returning(|_| Ok(String::from("new_hash")));
+
+    user_repository
+        .expect_update_password()
+        .with(predicate::eq(1), predicate::eq("new_hash"))
+        .times(1)
+        .returning(|_, _| Ok(()));
+
+    let service = HashUserService::new(user_repository, hash_service);
+
+    assert_eq!(Ok(()), service.change_password(&mock_user(), mock_password_change()).await);
+}
+
+#[tokio::test]
+async fn hash_impl_change_password_wrong_password() {
+    let mut user_repository = MockUserRepository::new();
+    let mut hash_service = MockHashService::new();
+
+    hash_service
+        .expect_verify()
+        .times(1)
+        .returning(|_, _| Ok(false));
+
+    hash_service
+        .expect_hash()
+        .never();
+
+    user_repository
+        .expect_update_password()
+        .never();
+
+    let service = HashUserService::new(user_repository, hash_service);
+
+    assert_eq!(Err(PasswordChangeError::WrongPassword), service.change_password(&mock_user(), mock_password_change()).await);
+}
+
+#[tokio::test]
+async fn hash_impl_change_password_update_error() {
+    let mut user_repository = MockUserRepository::new();
+    let mut hash_service = MockHashService::new();
+
+    hash_service
+        .expect_verify()
+        .returning(|_, _| Ok(true));
+
+    hash_service
+        .expect_hash()
+        .returning(|_| Ok(String::from("new_hash")));
+
+    user_repository
+        .expect_update_password()
+        .times(1)
+        .returning(|_, _| Err(UserUpdateError::Unknown));
+
+    let service = HashUserService::new(user_repository, hash_service);
+
+    assert_eq!(Err(PasswordChangeError::Unknown), service.change_password(&mock_user(), mock_password_change()).await);
+}
@@ -0,0 +1,158 @@
+use std::{sync::{Arc, atomic::{AtomicU64, Ordering}}, time::Duration};
+
+use chrono::Utc;
+use rand::Rng;
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{error, info};
+
+use crate::repository::{sessions::SessionRepository, cache::CachedSessionRepository};
+
+/// Background jobs started next to the server, stopped together once it shuts down.
+#[derive(Debug)]
+pub struct BackgroundTasks {
+    shutdown: watch::Sender<bool>,
+    handles: Vec<JoinHandle<()>>
+}
+
+impl BackgroundTasks {
+    pub fn new() -> Self {
+        let (shutdown, _) = watch::channel(false);
+        Self { shutdown, handles: Vec::new() }
+    }
+
+    /// Receives `true` once the tasks should stop.
+    pub fn shutdown_signal(&self) -> watch::Receiver<bool> {
+        self.shutdown.subscribe()
+    }
+
+    pub fn push(&mut self, handle: JoinHandle<()>) {
+        self.handles.push(handle);
+    }
+
+    pub async fn shutdown(self) {
+        info!("Stopping background tasks");
+        let _ = self.shutdown.send(true);
+        for handle in self.handles {
+            if let Err(err) = handle.await {
+                error!(%err);
+            }
+        }
+    }
+}
+
+impl Default for BackgroundTasks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SessionPurgeConfig {
+    pub interval: Duration,
+    /// Upper bound of the random delay added to every interval, so replicas do not purge in lockstep.
+    pub jitter: Duration
+}
+
+#[derive(Debug, Default)]
+pub struct SessionPurgeMetrics {
+    pub runs: AtomicU64,
+    pub failures: AtomicU64,
+    pub deleted: AtomicU64
+}
+
+async fn purge_expired_sessions<R: SessionRepository>(repository: &R, metrics: &SessionPurgeMetrics) {
+    metrics.runs.fetch_add(1, Ordering::Relaxed);
+    match repository.delete_expired(Utc::now().timestamp()).await {
+        Ok(deleted) => {
+            let total = metrics.deleted.fetch_add(deleted, Ordering::Relaxed) + deleted;
+            info!(deleted, total, "Purged expired sessions");
+        },
+        Err(_) => {
+            metrics.failures.fetch_add(1, Ordering::Relaxed);
+            error!("Unable to purge expired sessions");
+        }
+    }
+}
+
+pub fn spawn_session_purge<R>(
+    repository: R,
+    config: SessionPurgeConfig,
+    metrics: Arc<SessionPurgeMetrics>,
+    mut shutdown: watch::Receiver<bool>
+) -> JoinHandle<()>
+where
+    R: SessionRepository + Send + Sync + 'static
+{
+    tokio::spawn(async move {
+        loop {
+            let jitter = rand::thread_rng().gen_range(Duration::ZERO..=config.jitter);
+            tokio::select! {
+                _ = tokio::time::sleep(config.interval + jitter) => purge_expired_sessions(&repository, &metrics).await,
+                _ = shutdown.changed() => {
+                    info!("Session purge stopped");
+                    return;
+                }
+            }
+        }
+    })
+}
+
+/// Counters logged by `spawn_metrics_report`, totals since the start of the process.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MetricsReport {
+    pub purge_runs: u64,
+    pub purge_failures: u64,
+    pub purged_sessions: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64
+}
+
+impl MetricsReport {
+    pub fn collect<R>(purge: &SessionPurgeMetrics, cache: &CachedSessionRepository<R>) -> Self {
+        let cache_stats = cache.stats();
+        Self {
+            purge_runs: purge.runs.load(Ordering::Relaxed),
+            purge_failures: purge.failures.load(Ordering::Relaxed),
+            purged_sessions: purge.deleted.load(Ordering::Relaxed),
+            cache_hits: cache_stats.hits,
+            cache_misses: cache_stats.misses
+        }
+    }
+
+    fn log(&self) {
+        info!(
+            purge_runs = self.purge_runs,
+            purge_failures = self.purge_failures,
+            purged_sessions = self.purged_sessions,
+            cache_hits = self.cache_hits,
+            cache_misses = self.cache_misses,
+            "Metrics"
+        );
+    }
+}
+
+/// Logs a `MetricsReport` every `interval` and once more on shutdown.
+pub fn spawn_metrics_report<R>(
+    purge: Arc<SessionPurgeMetrics>,
+    cache: CachedSessionRepository<R>,
+    interval: Duration,
+    mut shutdown: watch::Receiver<bool>
+) -> JoinHandle<()>
+where
+    R: Send + Sync + 'static
+{
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => MetricsReport::collect(&purge, &cache).log(),
+                _ = shutdown.changed() => {
+                    MetricsReport::collect(&purge, &cache).log();
+                    return;
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,132 @@
+use mockall::predicate;
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+
+use crate::repository::{sessions::{MockSessionRepository, SessionDeleteError, SessionGetError}, cache::{CachedSessionRepository, SessionCacheConfig}};
+
+use super::*;
+
+#[tokio::test]
+async fn purge_records_metrics() {
+    let mut repository = MockSessionRepository::new();
+    repository
+        .expect_delete_expired()
+        .with(predicate::function(|before: &i64| (Utc::now().timestamp() - before).abs() <= 1))
+        .times(1)
+        .returning(|_| Ok(3));
+    repository
+        .expect_delete_expired()
+        .times(1)
+        .returning(|_| Err(SessionDeleteError::Unknown));
+
+    let metrics = SessionPurgeMetrics::default();
+    purge_expired_sessions(&repository, &metrics).await;
+    purge_expired_sessions(&repository, &metrics).await;
+
+    assert_eq!(2, metrics.runs.load(Ordering::Relaxed));
+    assert_eq!(1, metrics.failures.load(Ordering::Relaxed));
+    assert_eq!(3, metrics.deleted.load(Ordering::Relaxed));
+}
+
+#[tokio::test(start_paused = true)]
+async fn purge_runs_until_shutdown() {
+    let mut repository = MockSessionRepository::new();
+    repository
+        .expect_delete_expired()
+        .times(3)
+        .returning(|_| Ok(1));
+
+    let config = SessionPurgeConfig {
+        interval: Duration::from_secs(60),
+        jitter: Duration::ZERO
+    };
+    let mut tasks = BackgroundTasks::new();
+    let metrics = Arc::new(SessionPurgeMetrics::default());
+    tasks.push(spawn_session_purge(repository, config, metrics.clone(), tasks.shutdown_signal()));
+    tokio::task::yield_now().await;
+
+    tokio::time::advance(config.interval - Duration::from_secs(1)).await;
+    assert_eq!(0, metrics.runs.load(Ordering::Relaxed));
+    for runs in 1..=3 {
+        tokio::time::advance(Duration::from_secs(1)).await;
+        tokio::task::yield_now().await;
+        assert_eq!(runs, metrics.runs.load(Ordering::Relaxed));
+        tokio::time::advance(config.interval - Duration::from_secs(1)).await;
+    }
+    tasks.shutdown().await;
+
+    tokio::time::advance(config.interval * 2).await;
+    assert_eq!(3, metrics.runs.load(Ordering::Relaxed));
+    assert_eq!(3, metrics.deleted.load(Ordering::Relaxed));
+}
+
+fn cached(repository: MockSessionRepository) -> CachedSessionRepository<MockSessionRepository> {
+    CachedSessionRepository::new(repository, SessionCacheConfig {
+        capacity: 16,
+        ttl: Duration::from_secs(60),
+        negative_ttl: Duration::from_secs(60)
+    })
+}
+
+#[tokio::test]
+async fn metrics_report_collects_counters() {
+    let mut repository = MockSessionRepository::new();
+    repository
+        .expect_get()
+        .times(1)
+        .returning(|_| Err(SessionGetError::Missing));
+    repository
+        .expect_delete_expired()
+        .times(1)
+        .returning(|_| Ok(2));
+
+    let metrics = SessionPurgeMetrics::default();
+    purge_expired_sessions(&repository, &metrics).await;
+    let cache = cached(repository);
+    for _ in 0..3 {
+        assert!(cache.get("id").await.is_err());
+    }
+
+    let expected = MetricsReport {
+        purge_runs: 1,
+        purge_failures: 0,
+        purged_sessions: 2,
+        cache_hits: 2,
+        cache_misses: 1
+    };
+    assert_eq!(expected, MetricsReport::collect(&metrics, &cache));
+}
+
+/// Counts the `Metrics` events logged by `MetricsReport::log`.
+struct ReportCounter(Arc<AtomicU64>);
+
+impl<S: Subscriber> Layer<S> for ReportCounter {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        if event.metadata().fields().field("purge_runs").is_some() {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+#[tokio::test(start_paused = true)]
+async fn metrics_report_runs_until_shutdown() {
+    let reports = Arc::new(AtomicU64::new(0));
+    // the test runtime has a single thread, so the spawned task logs to this subscriber too
+    let _subscriber = tracing::subscriber::set_default(tracing_subscriber::registry().with(ReportCounter(reports.clone())));
+
+    let interval = Duration::from_secs(60);
+    let mut tasks = BackgroundTasks::new();
+    let cache = cached(MockSessionRepository::new());
+    tasks.push(spawn_metrics_report(Arc::default(), cache, interval, tasks.shutdown_signal()));
+    tokio::task::yield_now().await;
+
+    for expected in 1..=2 {
+        tokio::time::advance(interval).await;
+        tokio::task::yield_now().await;
+        assert_eq!(expected, reports.load(Ordering::Relaxed));
+    }
+
+    // one last report on the way out
+    tasks.shutdown().await;
+    assert_eq!(3, reports.load(Ordering::Relaxed));
+}
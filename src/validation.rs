@@ -1,5 +1,6 @@
 use axum::{async_trait, extract::{FromRequest, rejection::JsonRejection}, Json, response::IntoResponse};
 use http::{Request, StatusCode};
+use serde_json::Value;
 use validator::{Validate, ValidationErrors};
 
 pub enum ValidatedJsonRejection {
@@ -11,11 +12,32 @@ impl IntoResponse for ValidatedJsonRejection {
     fn into_response(self) -> axum::response::Response {
         match self {
             Self::JsonRejection(rejection) => rejection.into_response(),
-            Self::ValidationRejection(errs) => (StatusCode::UNPROCESSABLE_ENTITY, format!("{}", errs)).into_response()
+            Self::ValidationRejection(errs) => (StatusCode::UNPROCESSABLE_ENTITY, Json(without_values(&errs))).into_response()
         }
     }
 }
 
+/// Validation errors as JSON, keyed by field. The rejected values themselves are left out
+/// so that passwords and tokens are never echoed back.
+fn without_values(errs: &ValidationErrors) -> Value {
+    fn strip(value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::Object(params)) = map.get_mut("params") {
+                    params.remove("value");
+                }
+                map.values_mut().for_each(strip);
+            },
+            Value::Array(values) => values.iter_mut().for_each(strip),
+            _ => ()
+        }
+    }
+
+    let mut value = serde_json::to_value(errs).unwrap_or_default();
+    strip(&mut value);
+    value
+}
+
 pub struct ValidatedJson<T>(pub T);
 
 #[async_trait]